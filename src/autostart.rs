@@ -0,0 +1,294 @@
+//! Starting applications listed in the XDG autostart directories.
+//!
+//! See the [Desktop Application Autostart
+//! Specification](https://specifications.freedesktop.org/autostart-spec/autostart-spec-latest.html).
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use error_chain::{bail, ChainedError};
+use log::{info, log, warn};
+
+use crate::errors::*;
+use crate::spawn::Spawn;
+
+/// Name we go by in `OnlyShowIn` and `NotShowIn`, when `$XDG_CURRENT_DESKTOP`
+/// is not set.
+pub const DESKTOP_NAME: &str = "Lanta";
+
+/// Returns the applications to start, from the `.desktop` files in
+/// `$XDG_CONFIG_HOME/autostart` and `$XDG_CONFIG_DIRS/autostart`.
+///
+/// A file in `$XDG_CONFIG_HOME` hides the files of the same name in
+/// `$XDG_CONFIG_DIRS`. Invalid files are logged and skipped.
+pub fn entries() -> Result<Vec<Spawn>> {
+    let xdg_dirs = xdg::BaseDirectories::new()?;
+    let desktops = current_desktops();
+    let mut entries = vec![];
+    for path in xdg_dirs.list_config_files_once("autostart") {
+        if path.extension().is_none_or(|extension| extension != "desktop") {
+            continue;
+        }
+        let entry = fs::read_to_string(&path)
+            .chain_err(|| "Could not read file")
+            .and_then(|source| DesktopEntry::parse(&source));
+        match entry {
+            Ok(entry) => {
+                if let Some(spawn) = entry.to_spawn(&desktops) {
+                    info!("Autostarting {} from {}", spawn, path.display());
+                    entries.push(spawn);
+                }
+            }
+            Err(error) => warn!(
+                "Skipping autostart entry {}: {}",
+                path.display(),
+                error.display_chain().to_string()
+            ),
+        }
+    }
+    Ok(entries)
+}
+
+/// Returns the names in `$XDG_CURRENT_DESKTOP`, or `DESKTOP_NAME`.
+fn current_desktops() -> Vec<String> {
+    match env::var("XDG_CURRENT_DESKTOP") {
+        Ok(ref desktops) if !desktops.is_empty() => {
+            desktops.split(':').map(str::to_owned).collect()
+        }
+        _ => vec![DESKTOP_NAME.to_owned()],
+    }
+}
+
+/// The keys of the `[Desktop Entry]` group of a `.desktop` file.
+#[derive(Debug)]
+struct DesktopEntry {
+    keys: HashMap<String, String>,
+}
+
+impl DesktopEntry {
+    fn parse(source: &str) -> Result<DesktopEntry> {
+        let mut keys = HashMap::new();
+        let mut group = None;
+        for (number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                group = Some(&line[1..line.len() - 1]);
+            } else if group == Some("Desktop Entry") {
+                match line.find('=') {
+                    Some(ix) => {
+                        let key = line[..ix].trim().to_owned();
+                        let value = unescape(line[ix + 1..].trim());
+                        keys.entry(key).or_insert(value);
+                    }
+                    None => bail!("line {}: Expected 'key=value'", number + 1),
+                }
+            }
+        }
+        if group.is_none() {
+            bail!("No [Desktop Entry] group");
+        }
+        Ok(DesktopEntry { keys })
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.keys.get(key).map(String::as_str)
+    }
+
+    fn get_bool(&self, key: &str) -> bool {
+        self.get(key) == Some("true")
+    }
+
+    /// Splits a list value, e.g. `GNOME;KDE;`.
+    fn get_list(&self, key: &str) -> Option<Vec<&str>> {
+        self.get(key)
+            .map(|value| value.split(';').filter(|s| !s.is_empty()).collect())
+    }
+
+    /// Returns what to start for the entry, or `None` if it should not be
+    /// started in the given desktops.
+    fn to_spawn(&self, desktops: &[String]) -> Option<Spawn> {
+        if self.get("Type").is_some_and(|kind| kind != "Application") {
+            return None;
+        }
+        if self.get_bool("Hidden") {
+            return None;
+        }
+        let shown_in = |key| {
+            self.get_list(key)
+                .map(|list| list.iter().any(|name| desktops.iter().any(|d| d == name)))
+        };
+        if shown_in("OnlyShowIn") == Some(false) || shown_in("NotShowIn") == Some(true) {
+            return None;
+        }
+        if let Some(program) = self.get("TryExec") {
+            if find_program(program).is_none() {
+                info!("Not autostarting {}: it is not installed", program);
+                return None;
+            }
+        }
+
+        let argv = match self.get("Exec").map(split_exec) {
+            Some(Ok(ref argv)) if !argv.is_empty() => argv.clone(),
+            Some(Err(error)) => {
+                warn!("Invalid Exec in autostart entry: {}", error);
+                return None;
+            }
+            _ => return None,
+        };
+        let mut spawn = Spawn::argv(argv);
+        if let Some(dir) = self.get("Path").filter(|dir| !dir.is_empty()) {
+            spawn = spawn.current_dir(dir);
+        }
+        Some(spawn)
+    }
+}
+
+/// Undoes the escapes allowed in string values.
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Splits an `Exec` value into arguments, dropping the field codes: we never
+/// have files or URLs to pass on.
+fn split_exec(exec: &str) -> Result<Vec<String>> {
+    let mut argv = vec![];
+    let mut arg: Option<String> = None;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' => argv.extend(arg.take()),
+            '"' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => arg.push(c),
+                            None => bail!("Unterminated quote in '{}'", exec),
+                        },
+                        Some('%') if chars.as_str().starts_with('%') => {
+                            chars.next();
+                            arg.push('%');
+                        }
+                        Some(c) => arg.push(c),
+                        None => bail!("Unterminated quote in '{}'", exec),
+                    }
+                }
+            }
+            '%' => match chars.next() {
+                Some('%') => arg.get_or_insert_with(String::new).push('%'),
+                Some(code) if "fFuUdDnNickvm".contains(code) => {}
+                Some(code) => bail!("Unknown field code '%{}' in '{}'", code, exec),
+                None => bail!("Unterminated field code in '{}'", exec),
+            },
+            '\\' => {
+                let escaped = chars.next().unwrap_or('\\');
+                arg.get_or_insert_with(String::new).push(escaped);
+            }
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    argv.extend(arg);
+    Ok(argv)
+}
+
+/// Finds an executable, looking in `$PATH` unless the path is absolute.
+fn find_program(program: &str) -> Option<PathBuf> {
+    let is_executable = |path: &Path| {
+        fs::metadata(path)
+            .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    };
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+        return if is_executable(&path) { Some(path) } else { None };
+    }
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|path| is_executable(path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{split_exec, DesktopEntry};
+
+    fn to_spawn(source: &str, desktop: &str) -> Option<String> {
+        let entry = DesktopEntry::parse(source).unwrap();
+        entry
+            .to_spawn(&[desktop.to_owned()])
+            .map(|spawn| spawn.to_string())
+    }
+
+    #[test]
+    fn test_split_exec() {
+        assert_eq!(split_exec("nm-applet").unwrap(), vec!["nm-applet"]);
+        assert_eq!(
+            split_exec(r#"sh -c "echo \"100%%\"" %U"#).unwrap(),
+            vec!["sh", "-c", "echo \"100%\""]
+        );
+        assert_eq!(split_exec("a  b%%c %f").unwrap(), vec!["a", "b%c"]);
+        assert!(split_exec("\"unterminated").is_err());
+        assert!(split_exec("app %z").is_err());
+    }
+
+    #[test]
+    fn test_to_spawn() {
+        let source = "\
+# A comment
+[Desktop Entry]
+Type=Application
+Name=Applet
+Exec=nm-applet --indicator %u
+Exec=ignored
+
+[Desktop Action New]
+Exec=also-ignored
+";
+        assert_eq!(to_spawn(source, "Lanta"), Some("nm-applet --indicator".to_owned()));
+
+        let hidden = source.replace("Type=Application", "Hidden=true");
+        assert_eq!(to_spawn(&hidden, "Lanta"), None);
+
+        let only = source.replace("Type=Application", "OnlyShowIn=GNOME;KDE;");
+        assert_eq!(to_spawn(&only, "Lanta"), None);
+        assert!(to_spawn(&only, "KDE").is_some());
+        let not = source.replace("Type=Application", "NotShowIn=Lanta;");
+        assert_eq!(to_spawn(&not, "Lanta"), None);
+        assert!(to_spawn(&not, "GNOME").is_some());
+
+        let try_exec = source.replace("Type=Application", "TryExec=/nonexistent/nm-applet");
+        assert_eq!(to_spawn(&try_exec, "Lanta"), None);
+        let try_exec = source.replace("Type=Application", "TryExec=sh");
+        assert!(to_spawn(&try_exec, "Lanta").is_some());
+
+        assert!(DesktopEntry::parse("Exec=nm-applet").is_err());
+        assert!(DesktopEntry::parse("[Desktop Entry]\nnonsense\n").is_err());
+    }
+}
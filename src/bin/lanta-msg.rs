@@ -0,0 +1,56 @@
+//! Sends a command to the running Lanta instance.
+//!
+//! Usage: `lanta-msg <command> [args...]`, e.g. `lanta-msg switch_workspace web`.
+//! Query results are printed as JSON.
+//!
+//! `lanta-msg subscribe [events...]` prints the current workspace list and then
+//! each event as it happens, one JSON object per line.
+
+#[macro_use]
+extern crate error_chain;
+
+use std::env;
+use std::io::{self, Write};
+use std::path::Path;
+
+use lanta::{errors::*, ipc};
+
+fn run() -> Result<()> {
+    let mut args = env::args().skip(1);
+    let command = match args.next() {
+        Some(command) => command,
+        None => bail!("Usage: lanta-msg <command> [args...]"),
+    };
+    let args: Vec<String> = args.collect();
+
+    let path = ipc::socket_path()?;
+    if command == "subscribe" {
+        return subscribe(&path, &args);
+    }
+    let response = ipc::send_request(&path, &command, &args)?;
+    if !response.success {
+        bail!(response.error.unwrap_or_else(|| "Unknown error".to_owned()));
+    }
+    if let Some(data) = response.data {
+        let data = serde_json::to_string_pretty(&data).chain_err(|| "Invalid response")?;
+        println!("{}", data);
+    }
+
+    Ok(())
+}
+
+fn subscribe(path: &Path, events: &[String]) -> Result<()> {
+    let (response, events) = ipc::subscribe(path, events)?;
+    if !response.success {
+        bail!(response.error.unwrap_or_else(|| "Unknown error".to_owned()));
+    }
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for value in response.data.into_iter().map(Ok).chain(events) {
+        writeln!(stdout, "{}", value?)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+quick_main!(run);
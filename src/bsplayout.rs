@@ -1,5 +1,6 @@
 use crate::utils::Rectangle;
-use log::{error, info, log};
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeMap, Serializer};
 
 #[derive(Clone)]
 pub struct BSPLayout<W: Clone> {
@@ -15,28 +16,23 @@ impl<W: Clone> Default for BSPLayout<W> {
 
 #[derive(Clone)]
 pub enum Node<W: Clone> {
-    Node { split: Split, proportion: f64 },
+    Branch { split: Split, proportion: f64 },
     Leaf(W),
     Empty,
 }
 
 impl<W: Clone> Node<W> {
     pub fn is_empty(&self) -> bool {
-        match self {
-            Node::Empty => true,
-            _ => false,
-        }
+        matches!(self, Node::Empty)
     }
 
     pub fn is_leaf(&self) -> bool {
-        match self {
-            Node::Leaf(_) => true,
-            _ => false,
-        }
+        matches!(self, Node::Leaf(_))
     }
 }
 
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Split {
     /// Up and down windows split
     Horizontal,
@@ -45,7 +41,7 @@ pub enum Split {
 }
 
 pub fn get_side_leaf(ix: usize) -> usize {
-    if (ix + 1) % 2 == 0 {
+    if (ix + 1).is_multiple_of(2) {
         ix + 1
     } else {
         ix - 1
@@ -54,7 +50,16 @@ pub fn get_side_leaf(ix: usize) -> usize {
 
 /// Get index postion to parent, return -1 on top case
 pub fn get_parent(ix: usize) -> usize {
-    ((ix + 1) / 2) - 1
+    ix.div_ceil(2) - 1
+}
+
+/// Get the depth of a position, the root being at level 0
+pub fn get_level(ix: usize) -> usize {
+    let mut level = 0;
+    while (ix + 1) >> (level + 1) > 0 {
+        level += 1;
+    }
+    level
 }
 
 impl<W: Clone> BSPLayout<W> {
@@ -83,28 +88,33 @@ impl<W: Clone> BSPLayout<W> {
         found
     }
 
-    /// Insert a new value in the first empty node, it keeps a balanced tree
+    /// Insert a new value in the first empty node, it keeps a balanced tree.
+    /// Splits vertically on even levels of the tree and horizontally on odd
+    /// ones, so windows alternate between side by side and stacked.
     /// O(n)
-    pub fn add_leaf(&mut self, elem: W, split: Split) {
-        match self.find(Node::is_empty) {
-            Some(ix) => self.insert(ix, elem, split),
-            None => self.insert(self.nodes.len(), elem, split),
-        }
+    pub fn push(&mut self, elem: W) {
+        let ix = self.find(Node::is_empty).unwrap_or(self.nodes.len());
+        let split = if get_level(get_parent(ix.max(1))).is_multiple_of(2) {
+            Split::Vertical
+        } else {
+            Split::Horizontal
+        };
+        self.insert(ix, elem, split)
     }
 
     /// UNSAFE (could break internal structure logic)
     /// O(n) Almost O(1) except when is necesary to increase the vector
     pub fn insert(&mut self, ix: usize, elem: W, split: Split) {
-        let parent = get_parent(ix);
         if ix == 0 {
             self.nodes[0] = Node::Leaf(elem)
         } else {
+            let parent = get_parent(ix);
             if self.nodes.len() <= 2 * parent + 1 {
                 self.increase_resolution();
             }
             self.nodes.swap(2 * parent + 1, parent);
             self.nodes[2 * parent + 2] = Node::Leaf(elem);
-            self.nodes[parent] = Node::Node {
+            self.nodes[parent] = Node::Branch {
                 split,
                 proportion: 0.5,
             };
@@ -113,53 +123,100 @@ impl<W: Clone> BSPLayout<W> {
 
     /// UNSAFE (could break internal structure logic)
     /// O(n) Almost O(1) except when is necesary to decrease the vector
+    ///
+    /// The sibling of the removed leaf (and all of its subtree) takes the
+    /// place of their parent.
     pub fn remove(&mut self, ix: usize) {
-        let parent = get_parent(ix);
         if ix == 0 {
             *self = Self::empty();
         } else {
+            let parent = get_parent(ix);
             let sibling = get_side_leaf(ix);
-            self.nodes.swap(parent, sibling);
             self.nodes[ix] = Node::Empty;
-            self.nodes[sibling] = Node::Empty;
-            if self.can_decrease_resolution() {
+            let subtree = self.take_subtree(sibling);
+            self.put_subtree(parent, subtree);
+            while self.can_decrease_resolution() {
                 self.decrease_resolution()
             }
         }
     }
 
+    /// Detaches the subtree rooted at `ix`, leaving its nodes empty.
+    fn take_subtree(&mut self, ix: usize) -> Subtree<W> {
+        if ix >= self.nodes.len() {
+            return Subtree(Node::Empty, None);
+        }
+        let node = std::mem::replace(&mut self.nodes[ix], Node::Empty);
+        let children = match node {
+            Node::Branch { .. } => Some(Box::new((
+                self.take_subtree(2 * ix + 1),
+                self.take_subtree(2 * ix + 2),
+            ))),
+            _ => None,
+        };
+        Subtree(node, children)
+    }
+
+    /// Attaches a subtree detached by `take_subtree` at `ix`.
+    fn put_subtree(&mut self, ix: usize, subtree: Subtree<W>) {
+        let Subtree(node, children) = subtree;
+        self.nodes[ix] = node;
+        if let Some(children) = children {
+            let (fst, snd) = *children;
+            self.put_subtree(2 * ix + 1, fst);
+            self.put_subtree(2 * ix + 2, snd);
+        }
+    }
+
     /// UNSAFE (vector unbounds)
     /// Try to change two leaf in the tree, at worst case doesn't do anything
     pub fn interchange_leaf(&mut self, ix: usize, ix2: usize) {
-        if self.nodes[ix].is_leaf() && self.nodes[ix].is_leaf() {
+        if self.nodes[ix].is_leaf() && self.nodes[ix2].is_leaf() {
             self.nodes.swap(ix, ix2)
         }
     }
 
+    /// Returns the position and value of every leaf, ordered from left to
+    /// right (or top to bottom).
+    pub fn leaves(&self) -> Vec<(usize, &W)> {
+        let mut leaves = vec![];
+        self.collect_leaves(0, &mut leaves);
+        leaves
+    }
+
+    fn collect_leaves<'a>(&'a self, ix: usize, leaves: &mut Vec<(usize, &'a W)>) {
+        match self.nodes.get(ix) {
+            Some(Node::Branch { .. }) => {
+                self.collect_leaves(2 * ix + 1, leaves);
+                self.collect_leaves(2 * ix + 2, leaves);
+            }
+            Some(Node::Leaf(elem)) => leaves.push((ix, elem)),
+            _ => {}
+        }
+    }
+
     /// Gaps, decorations, borders, draggers
     /// Build squares for each leaf and node. it share the position than `self.nodes`
     pub fn build_squares(&self, root: Rectangle) -> Vec<Option<Rectangle>> {
-        let mut squares = vec![Some(root)]; // same capacity as self.nodes
-        let mut func = |node: &Node<W>, parent_rec_ix: usize| match node {
-            Node::Node { proportion, split } => {
-                let parent_rec: Rectangle = squares[parent_rec_ix].clone().expect("Parent Node");
-                let mut fst = parent_rec.clone();
-                let mut snd = parent_rec.clone();
+        let mut squares = vec![None; self.nodes.len()];
+        squares[0] = Some(root);
+        let mut func = |node: &Node<W>, parent_rec_ix: usize| {
+            if let Node::Branch { proportion, split } = node {
+                let parent_rec: Rectangle = squares[parent_rec_ix].expect("Parent Node");
+                let mut fst = parent_rec;
+                let mut snd = parent_rec;
                 if *split == Split::Horizontal {
-                    let new_height = parent_rec.height as f64 * *proportion;
-                    fst.y = new_height.ceil() as u32;
-                    fst.height = new_height.ceil() as u32;
-                    snd.height = parent_rec.width - new_height.floor() as u32;
+                    fst.height = (f64::from(parent_rec.height) * *proportion).round() as u32;
+                    snd.y = parent_rec.y + fst.height;
+                    snd.height = parent_rec.height - fst.height;
                 } else {
-                    let new_width = parent_rec.width as f64 * *proportion;
-                    fst.width = new_width.ceil() as u32;
-                    snd.width = new_width.ceil() as u32;
-                    snd.x = parent_rec.width - new_width.floor() as u32;
+                    fst.width = (f64::from(parent_rec.width) * *proportion).round() as u32;
+                    snd.x = parent_rec.x + fst.width;
+                    snd.width = parent_rec.width - fst.width;
                 }
                 squares[2 * parent_rec_ix + 1] = Some(fst);
                 squares[2 * parent_rec_ix + 2] = Some(snd);
             }
-            _ => return,
         };
         self.map_nodes_from(0, &mut |ix| func(&self.nodes[ix], ix));
         squares
@@ -167,15 +224,12 @@ impl<W: Clone> BSPLayout<W> {
 
     pub fn map_nodes_from<F>(&self, ix: usize, mapper: &mut F)
     where
-        F: FnMut(usize) -> (),
+        F: FnMut(usize),
     {
-        match self.nodes[ix] {
-            Node::Node { .. } => {
-                mapper(ix);
-                self.map_nodes_from(2 * ix + 1, mapper);
-                self.map_nodes_from(2 * ix + 2, mapper);
-            }
-            _ => return,
+        if let Node::Branch { .. } = self.nodes[ix] {
+            mapper(ix);
+            self.map_nodes_from(2 * ix + 1, mapper);
+            self.map_nodes_from(2 * ix + 2, mapper);
         }
     }
 
@@ -202,20 +256,242 @@ impl<W: Clone> BSPLayout<W> {
     // }
 
     pub fn increase_resolution(&mut self) {
-        self.max_level = self.max_level << 1;
+        self.max_level <<= 1;
         self.nodes.append(&mut vec![Node::Empty; self.max_level]);
     }
 
     pub fn decrease_resolution(&mut self) {
         self.nodes.truncate(self.nodes.len() - self.max_level);
-        self.max_level = self.max_level >> 1
+        self.max_level >>= 1;
     }
 
     /// Improvement: Take a number of left Leaf nodes in the last level
     pub fn can_decrease_resolution(&self) -> bool {
-        self.nodes[self.nodes.len() - self.max_level..]
-            .iter()
-            .find(|node| !node.is_empty())
-            .is_some()
+        self.max_level > 1
+            && self.nodes[self.nodes.len() - self.max_level..]
+                .iter()
+                .all(Node::is_empty)
+    }
+}
+
+/// A detached piece of the tree, used to move subtrees around.
+struct Subtree<W: Clone>(Node<W>, Option<Box<(Subtree<W>, Subtree<W>)>>);
+
+impl<W: Clone + PartialEq> BSPLayout<W> {
+    /// Find the position of the leaf holding `elem`
+    pub fn find_leaf(&self, elem: &W) -> Option<usize> {
+        self.find(|node| match node {
+            Node::Leaf(value) => value == elem,
+            _ => false,
+        })
+    }
+}
+
+/// The layout is serialized as nested nodes: `{"split", "proportion",
+/// "children": [first, second]}` for splits, `{"leaf": value}` for leaves and
+/// `null` for empty nodes.
+impl<W: Clone + Serialize> Serialize for BSPLayout<W> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedNode { layout: self, ix: 0 }.serialize(serializer)
+    }
+}
+
+struct SerializedNode<'a, W: Clone> {
+    layout: &'a BSPLayout<W>,
+    ix: usize,
+}
+
+impl<'a, W: Clone + Serialize> Serialize for SerializedNode<'a, W> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.layout.nodes.get(self.ix) {
+            Some(Node::Branch { split, proportion }) => {
+                let children = [
+                    SerializedNode {
+                        layout: self.layout,
+                        ix: 2 * self.ix + 1,
+                    },
+                    SerializedNode {
+                        layout: self.layout,
+                        ix: 2 * self.ix + 2,
+                    },
+                ];
+                let mut map = serializer.serialize_map(Some(3))?;
+                map.serialize_entry("split", split)?;
+                map.serialize_entry("proportion", proportion)?;
+                map.serialize_entry("children", &children)?;
+                map.end()
+            }
+            Some(Node::Leaf(elem)) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("leaf", elem)?;
+                map.end()
+            }
+            _ => serializer.serialize_none(),
+        }
     }
-}
\ No newline at end of file
+}
+
+/// Reads back the format written by `Serialize`.
+impl<'de, W: Clone + Deserialize<'de>> Deserialize<'de> for BSPLayout<W> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut layout = BSPLayout::empty();
+        if let Some(root) = Option::<DeserializedNode<W>>::deserialize(deserializer)? {
+            layout.put_deserialized(0, root);
+        }
+        Ok(layout)
+    }
+}
+
+type DeserializedChildren<W> = (Option<DeserializedNode<W>>, Option<DeserializedNode<W>>);
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum DeserializedNode<W> {
+    Node {
+        split: Split,
+        proportion: f64,
+        children: Box<DeserializedChildren<W>>,
+    },
+    Leaf {
+        leaf: W,
+    },
+}
+
+impl<W: Clone> BSPLayout<W> {
+    fn put_deserialized(&mut self, ix: usize, node: DeserializedNode<W>) {
+        while ix >= self.nodes.len() {
+            self.increase_resolution();
+        }
+        match node {
+            DeserializedNode::Node {
+                split,
+                proportion,
+                children,
+            } => {
+                self.nodes[ix] = Node::Branch { split, proportion };
+                let (first, second) = *children;
+                if let Some(first) = first {
+                    self.put_deserialized(2 * ix + 1, first);
+                }
+                if let Some(second) = second {
+                    self.put_deserialized(2 * ix + 2, second);
+                }
+            }
+            DeserializedNode::Leaf { leaf } => self.nodes[ix] = Node::Leaf(leaf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_level, BSPLayout};
+    use crate::utils::Rectangle;
+
+    fn values(layout: &BSPLayout<u8>) -> Vec<u8> {
+        layout.leaves().into_iter().map(|(_, v)| *v).collect()
+    }
+
+    #[test]
+    fn test_get_level() {
+        assert_eq!(get_level(0), 0);
+        assert_eq!(get_level(1), 1);
+        assert_eq!(get_level(2), 1);
+        assert_eq!(get_level(3), 2);
+        assert_eq!(get_level(6), 2);
+        assert_eq!(get_level(7), 3);
+    }
+
+    #[test]
+    fn test_push() {
+        let mut layout = BSPLayout::empty();
+        layout.push(1);
+        assert_eq!(values(&layout), vec![1]);
+        layout.push(2);
+        assert_eq!(values(&layout), vec![1, 2]);
+        layout.push(3);
+        assert_eq!(values(&layout), vec![1, 3, 2]);
+        assert_eq!(layout.find_leaf(&3), Some(4));
+    }
+
+    #[test]
+    fn test_remove_moves_subtree_up() {
+        let mut layout = BSPLayout::empty();
+        layout.push(1);
+        layout.push(2);
+        layout.push(3);
+        layout.push(4);
+        assert_eq!(values(&layout), vec![1, 3, 2, 4]);
+
+        // Removing 1 moves the subtree holding 3 up, along with its children.
+        layout.remove(layout.find_leaf(&1).unwrap());
+        assert_eq!(values(&layout), vec![3, 2, 4]);
+
+        layout.remove(layout.find_leaf(&2).unwrap());
+        layout.remove(layout.find_leaf(&4).unwrap());
+        assert_eq!(values(&layout), vec![3]);
+        assert_eq!(layout.nodes.len(), 1);
+
+        layout.remove(layout.find_leaf(&3).unwrap());
+        assert_eq!(values(&layout), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_build_squares() {
+        let mut layout = BSPLayout::empty();
+        layout.push(1);
+        layout.push(2);
+        layout.push(3);
+        let root = Rectangle {
+            x: 10,
+            y: 20,
+            width: 100,
+            height: 50,
+        };
+        let squares = layout.build_squares(root);
+        let square = |v| squares[layout.find_leaf(&v).unwrap()].unwrap();
+
+        assert_eq!(square(1), Rectangle { x: 10, y: 20, width: 50, height: 25 });
+        assert_eq!(square(3), Rectangle { x: 10, y: 45, width: 50, height: 25 });
+        assert_eq!(square(2), Rectangle { x: 60, y: 20, width: 50, height: 50 });
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut layout = BSPLayout::empty();
+        assert_eq!(serde_json::to_value(&layout).unwrap(), serde_json::Value::Null);
+        layout.push(1u8);
+        layout.push(2);
+        layout.push(3);
+        assert_eq!(
+            serde_json::to_value(&layout).unwrap(),
+            serde_json::json!({
+                "split": "vertical",
+                "proportion": 0.5,
+                "children": [
+                    {
+                        "split": "horizontal",
+                        "proportion": 0.5,
+                        "children": [{ "leaf": 1 }, { "leaf": 3 }],
+                    },
+                    { "leaf": 2 },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let mut layout = BSPLayout::empty();
+        for value in 1..6u8 {
+            layout.push(value);
+        }
+        let json = serde_json::to_value(&layout).unwrap();
+        let restored: BSPLayout<u8> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(values(&restored), values(&layout));
+        assert_eq!(serde_json::to_value(&restored).unwrap(), json);
+
+        let empty: BSPLayout<u8> = serde_json::from_str("null").unwrap();
+        assert!(values(&empty).is_empty());
+        assert!(serde_json::from_str::<BSPLayout<u8>>(r#"{"split": "diagonal"}"#).is_err());
+    }
+}
@@ -1,10 +1,195 @@
 use std::rc::Rc;
+use std::time::Duration;
 
+use error_chain::bail;
 
 use crate::core::Lanta;
 use crate::errors::*;
+use crate::layout::Message;
+use crate::spawn::Spawn;
 
-pub type Command = Rc<Fn(&mut Lanta) -> Result<()>>;
+pub type Command = Rc<dyn Fn(&mut Lanta) -> Result<()>>;
+
+
+/// Parses a textual command, as used in the configuration file.
+///
+/// A command is its name followed by its arguments, separated by whitespace,
+/// e.g. `focus_next` or `spawn urxvt -e htop`. The names match the functions
+/// in `cmd::lazy`.
+pub fn parse(command: &str) -> Result<Command> {
+    let mut words = command.split_whitespace();
+    let name = match words.next() {
+        Some(name) => name,
+        None => bail!("Empty command"),
+    };
+    let args: Vec<&str> = words.collect();
+    from_args(name, &args)
+}
+
+/// Parses the arguments of the named timer commands: a name, a number of
+/// milliseconds, called `what` in errors, and a command.
+fn timer_args<'a>(
+    name: &str,
+    what: &str,
+    args: &[&'a str],
+) -> Result<(&'a str, Duration, Command)> {
+    let (timer, millis, command) = match args {
+        [timer, millis, command @ ..] => (*timer, *millis, command),
+        _ => bail!("Command '{}' needs a name, {} and a command", name, what),
+    };
+    let millis: u64 = millis
+        .parse()
+        .chain_err(|| format!("Invalid {} '{}', expected milliseconds", noun(what), millis))?;
+    if millis == 0 {
+        bail!("Invalid {} '0', expected at least 1 millisecond", noun(what));
+    }
+    let command = match command.split_first() {
+        Some((command_name, args)) => from_args(command_name, args)?,
+        None => bail!("Command '{}' needs a command to run", name),
+    };
+    Ok((timer, Duration::from_millis(millis), command))
+}
+
+/// Drops the article of "an interval" or "a delay".
+fn noun(what: &str) -> &str {
+    what.split_whitespace().last().unwrap_or(what)
+}
+
+/// Builds a command from its name and its already split arguments.
+pub fn from_args<S: AsRef<str>>(name: &str, args: &[S]) -> Result<Command> {
+    let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+
+    let no_args = |command: Command| -> Result<Command> {
+        if !args.is_empty() {
+            bail!("Command '{}' takes no arguments", name);
+        }
+        Ok(command)
+    };
+    let one_arg = || -> Result<String> {
+        match args.as_slice() {
+            [arg] => Ok((*arg).to_owned()),
+            _ => bail!("Command '{}' takes exactly one argument", name),
+        }
+    };
+
+    match name {
+        "close_focused_window" => no_args(lazy::close_focused_window()),
+        "focus_next" => no_args(lazy::focus_next()),
+        "focus_previous" => no_args(lazy::focus_previous()),
+        "shuffle_next" => no_args(lazy::shuffle_next()),
+        "shuffle_previous" => no_args(lazy::shuffle_previous()),
+        "reload_config" => no_args(lazy::reload_config()),
+        "quit" => no_args(lazy::quit()),
+        "restart" => no_args(lazy::restart()),
+        "switch_workspace" => Ok(lazy::switch_workspace(one_arg()?)),
+        "view_workspace" => Ok(lazy::view_workspace(one_arg()?)),
+        "focus_screen" => Ok(lazy::focus_screen(one_arg()?.parse()?)),
+        "move_window_to_screen" => Ok(lazy::move_window_to_screen(one_arg()?.parse()?)),
+        "swap_screens" => match args.as_slice() {
+            [] => Ok(lazy::swap_screens(None)),
+            [screen] => match screen.parse() {
+                Ok(screen) => Ok(lazy::swap_screens(Some(screen))),
+                Err(_) => bail!("Invalid screen '{}', expected its position", screen),
+            },
+            _ => bail!("Command 'swap_screens' takes at most one argument"),
+        },
+        "move_window_to_workspace" => Ok(lazy::move_window_to_workspace(one_arg()?)),
+        "add_workspace" => Ok(lazy::add_workspace(one_arg()?)),
+        "rename_workspace" => match args.as_slice() {
+            [new_name] => Ok(lazy::rename_workspace(None, *new_name)),
+            [name, new_name] => Ok(lazy::rename_workspace(Some(*name), *new_name)),
+            _ => bail!(
+                "Command 'rename_workspace' takes a new name, optionally after the old one"
+            ),
+        },
+        "remove_workspace" => match args.as_slice() {
+            [name] => Ok(lazy::remove_workspace(*name, None)),
+            [name, merge_into] => Ok(lazy::remove_workspace(*name, Some(*merge_into))),
+            _ => bail!(
+                "Command 'remove_workspace' takes a workspace and optionally one to merge it into"
+            ),
+        },
+        "move_workspace" => match args.as_slice() {
+            [name, position] => match position.parse() {
+                Ok(position) => Ok(lazy::move_workspace(*name, position)),
+                Err(_) => bail!("Invalid position '{}'", position),
+            },
+            _ => bail!("Command 'move_workspace' takes a workspace and a position"),
+        },
+        "toggle_scratchpad" => Ok(lazy::toggle_scratchpad(one_arg()?)),
+        "layout_next" => no_args(lazy::layout_next()),
+        "layout_set" => Ok(lazy::layout_set(one_arg()?)),
+        "inc_master" | "dec_master" => {
+            let count: i32 = match args.as_slice() {
+                [] => 1,
+                [count] => match count.parse() {
+                    Ok(count) => count,
+                    Err(_) => bail!("Invalid number of windows '{}'", count),
+                },
+                _ => bail!("Command '{}' takes at most one argument", name),
+            };
+            let count = if name == "dec_master" { -count } else { count };
+            Ok(lazy::layout_message(Message::IncMaster(count)))
+        }
+        "expand_master" => no_args(lazy::layout_message(Message::Expand)),
+        "shrink_master" => no_args(lazy::layout_message(Message::Shrink)),
+        "toggle_maximize" => no_args(lazy::toggle_maximize()),
+        "toggle_sticky" => no_args(lazy::toggle_sticky()),
+        "toggle_floating" => no_args(lazy::toggle_floating()),
+        "minimize" => no_args(lazy::minimize()),
+        "restore_last" => no_args(lazy::restore_last()),
+        "restore" => {
+            if args.is_empty() {
+                bail!("Command 'restore' needs a window id or title");
+            }
+            Ok(lazy::restore(args.join(" ")))
+        }
+        "after" => match args.split_first() {
+            Some((delay, command)) => {
+                let delay: u64 = delay.parse().chain_err(|| {
+                    format!("Invalid delay '{}', expected milliseconds", delay)
+                })?;
+                let (name, args) = match command.split_first() {
+                    Some(command) => command,
+                    None => bail!("Command 'after' needs a command to run"),
+                };
+                let command = from_args(name, args)?;
+                Ok(lazy::after(Duration::from_millis(delay), command))
+            }
+            None => bail!("Command 'after' needs a delay and a command"),
+        },
+        "every" => {
+            let (timer, interval, command) = timer_args(name, "an interval", &args)?;
+            Ok(lazy::every(timer, interval, command))
+        }
+        "idle" => {
+            let (timer, delay, command) = timer_args(name, "a delay", &args)?;
+            Ok(lazy::idle(timer, delay, command))
+        }
+        "reset_timer" => Ok(lazy::reset_timer(one_arg()?)),
+        "cancel_timer" => Ok(lazy::cancel_timer(one_arg()?)),
+        "warp_pointer" => no_args(lazy::warp_pointer()),
+        "spawn" => {
+            if args.is_empty() {
+                bail!("Command 'spawn' needs a program to run");
+            }
+            Ok(lazy::spawn(Spawn::argv(&args)))
+        }
+        "spawn_shell" => {
+            if args.is_empty() {
+                bail!("Command 'spawn_shell' needs a command line to run");
+            }
+            Ok(lazy::spawn(Spawn::shell(args.join(" "))))
+        }
+        "spawn_on" => match args.split_first() {
+            Some((workspace, argv)) if !argv.is_empty() => {
+                Ok(lazy::spawn(Spawn::argv(argv).workspace(*workspace)))
+            }
+            _ => bail!("Command 'spawn_on' needs a workspace and a program to run"),
+        },
+        _ => bail!("Unknown command '{}'", name),
+    }
+}
 
 
 /// Lazy-functions which return a `Command` to do the requested action.
@@ -12,93 +197,349 @@ pub type Command = Rc<Fn(&mut Lanta) -> Result<()>>;
 // wrappers for them.
 pub mod lazy {
 
-    // use std::process;
-    // use std::rc::Rc;
-    // use std::sync::Mutex;
-    
-    // use log::{log, info};
-
-    // use crate::errors::*;
-    // use super::Command;
-
-    // /// Closes the currently focused window.
-    // pub fn close_focused_window() -> Command {
-    //     Rc::new(|ref mut wm| {
-    //         wm.group_mut().close_focused();
-    //         Ok(())
-    //     })
-    // }
-
-    // /// Moves the focus to the next window in the current group's stack.
-    // pub fn focus_next() -> Command {
-    //     Rc::new(|ref mut wm| {
-    //         wm.group_mut().focus_next();
-    //         Ok(())
-    //     })
-    // }
-
-    // /// Moves the focus to the previous window in the current group's stack.
-    // pub fn focus_previous() -> Command {
-    //     Rc::new(|ref mut wm| {
-    //         wm.group_mut().focus_previous();
-    //         Ok(())
-    //     })
-    // }
-
-    // /// Shuffles the focused window to the next position in the current group's
-    // /// stack.
-    // pub fn shuffle_next() -> Command {
-    //     Rc::new(|ref mut wm| {
-    //         wm.group_mut().shuffle_next();
-    //         Ok(())
-    //     })
-    // }
-
-    // /// Shuffles the focused window to the previous position in the current
-    // /// group's stack.
-    // pub fn shuffle_previous() -> Command {
-    //     Rc::new(|ref mut wm| {
-    //         wm.group_mut().shuffle_previous();
-    //         Ok(())
-    //     })
-    // }
-
-    // /// Cycles to the next layout of the current group.
-    // pub fn layout_next() -> Command {
-    //     Rc::new(|ref mut wm| {
-    //         wm.group_mut().layout_next();
-    //         Ok(())
-    //     })
-    // }
-
-    // /// Spawns the specified command.
-    // ///
-    // /// The returned `Command` will spawn the `Command` each time it is called.
-    // pub fn spawn(command: process::Command) -> Command {
-    //     let mutex = Mutex::new(command);
-    //     Rc::new(move |_| {
-    //         let mut command = mutex.lock().unwrap();
-    //         info!("Spawning: {:?}", *command);
-    //         command
-    //             .spawn()
-    //             .chain_err(|| format!("Could not spawn command: {:?}", *command))?;
-    //         Ok(())
-    //     })
-    // }
-
-    // /// Switches to the group specified by name.
-    // pub fn switch_group(name: &'static str) -> Command {
-    //     Rc::new(move |wm| {
-    //         wm.switch_group(name);
-    //         Ok(())
-    //     })
-    // }
-
-    // /// Moves the focused window on the active group to another group.
-    // pub fn move_window_to_group(name: &'static str) -> Command {
-    //     Rc::new(move |wm| {
-    //         wm.move_focused_to_group(name);
-    //         Ok(())
-    //     })
-    // }
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use crate::layout::Message;
+    use crate::spawn::Spawn;
+    use crate::window_set::ScreenTarget;
+    use super::Command;
+
+    /// Closes the currently focused window.
+    pub fn close_focused_window() -> Command {
+        Rc::new(|wm| {
+            wm.close_focused_window();
+            Ok(())
+        })
+    }
+
+    /// Moves the focus to the next window in the current workspace.
+    pub fn focus_next() -> Command {
+        Rc::new(|wm| {
+            wm.focus_next();
+            Ok(())
+        })
+    }
+
+    /// Moves the focus to the previous window in the current workspace.
+    pub fn focus_previous() -> Command {
+        Rc::new(|wm| {
+            wm.focus_previous();
+            Ok(())
+        })
+    }
+
+    /// Swaps the focused window with the next one in the current workspace's
+    /// tree.
+    pub fn shuffle_next() -> Command {
+        Rc::new(|wm| {
+            wm.shuffle_next();
+            Ok(())
+        })
+    }
+
+    /// Swaps the focused window with the previous one in the current
+    /// workspace's tree.
+    pub fn shuffle_previous() -> Command {
+        Rc::new(|wm| {
+            wm.shuffle_previous();
+            Ok(())
+        })
+    }
+
+    /// Reloads the configuration file.
+    ///
+    /// If the new configuration is invalid, the previous one is kept.
+    pub fn reload_config() -> Command {
+        Rc::new(|wm| wm.reload_config())
+    }
+
+    /// Stops the WM.
+    pub fn quit() -> Command {
+        Rc::new(|wm| {
+            wm.quit();
+            Ok(())
+        })
+    }
+
+    /// Spawns the specified process.
+    ///
+    /// The returned `Command` will spawn the process each time it is called.
+    pub fn spawn(spawn: Spawn) -> Command {
+        Rc::new(move |wm| wm.spawn(&spawn))
+    }
+
+    /// Runs a command once `delay` has passed.
+    pub fn after(delay: Duration, command: Command) -> Command {
+        Rc::new(move |wm| {
+            wm.after(delay, command.clone());
+            Ok(())
+        })
+    }
+
+    /// Runs a command every `interval`, until the timer named `name` is
+    /// cancelled.
+    pub fn every<S: Into<String>>(name: S, interval: Duration, command: Command) -> Command {
+        let name = name.into();
+        Rc::new(move |wm| {
+            wm.every_named(&name, interval, command.clone());
+            Ok(())
+        })
+    }
+
+    /// Runs a command once no key was pressed for `delay`, and again after
+    /// each later key press followed by that much inactivity, until the timer
+    /// named `name` is cancelled.
+    pub fn idle<S: Into<String>>(name: S, delay: Duration, command: Command) -> Command {
+        let name = name.into();
+        Rc::new(move |wm| {
+            wm.idle_named(&name, delay, command.clone());
+            Ok(())
+        })
+    }
+
+    /// Restarts the timer named `name`, so that it expires a whole delay
+    /// from now.
+    pub fn reset_timer<S: Into<String>>(name: S) -> Command {
+        let name = name.into();
+        Rc::new(move |wm| wm.reset_named_timer(&name))
+    }
+
+    /// Cancels the timer named `name`.
+    pub fn cancel_timer<S: Into<String>>(name: S) -> Command {
+        let name = name.into();
+        Rc::new(move |wm| wm.cancel_named_timer(&name))
+    }
+
+    /// Moves the pointer to the middle of the focused window.
+    pub fn warp_pointer() -> Command {
+        Rc::new(|wm| {
+            wm.warp_pointer();
+            Ok(())
+        })
+    }
+
+    /// Restarts the WM, keeping the windows where they are.
+    pub fn restart() -> Command {
+        Rc::new(|wm| {
+            wm.restart();
+            Ok(())
+        })
+    }
+
+    /// Shows the workspace specified by name on the focused screen, swapping
+    /// workspaces with the screen showing it if there is one.
+    pub fn switch_workspace<S: Into<String>>(name: S) -> Command {
+        let name = name.into();
+        Rc::new(move |wm| wm.switch_workspace(&name))
+    }
+
+    /// Shows the workspace specified by name, focusing the screen showing it
+    /// if there is one.
+    pub fn view_workspace<S: Into<String>>(name: S) -> Command {
+        let name = name.into();
+        Rc::new(move |wm| wm.view_workspace(&name))
+    }
+
+    /// Swaps the workspaces of the focused screen and another one, by default
+    /// the next.
+    pub fn swap_screens(other: Option<usize>) -> Command {
+        Rc::new(move |wm| wm.swap_screens(other))
+    }
+
+    /// Focuses another screen.
+    pub fn focus_screen(target: ScreenTarget) -> Command {
+        Rc::new(move |wm| wm.focus_screen(target))
+    }
+
+    /// Moves the focused window to the workspace shown on another screen.
+    pub fn move_window_to_screen(target: ScreenTarget) -> Command {
+        Rc::new(move |wm| wm.move_focused_to_screen(target))
+    }
+
+    /// Moves the focused window on the active workspace to another workspace.
+    pub fn move_window_to_workspace<S: Into<String>>(name: S) -> Command {
+        let name = name.into();
+        Rc::new(move |wm| wm.move_focused_to_workspace(&name))
+    }
+
+    /// Appends a new, empty workspace.
+    pub fn add_workspace<S: Into<String>>(name: S) -> Command {
+        let name = name.into();
+        Rc::new(move |wm| wm.add_workspace(&name))
+    }
+
+    /// Renames the named workspace, or the focused one.
+    pub fn rename_workspace<S: Into<String>>(name: Option<&str>, new_name: S) -> Command {
+        let name = name.map(str::to_owned);
+        let new_name = new_name.into();
+        Rc::new(move |wm| wm.rename_workspace(name.as_deref(), &new_name))
+    }
+
+    /// Removes a workspace, moving its windows to the `merge_into` one.
+    pub fn remove_workspace<S: Into<String>>(name: S, merge_into: Option<&str>) -> Command {
+        let name = name.into();
+        let merge_into = merge_into.map(str::to_owned);
+        Rc::new(move |wm| wm.remove_workspace(&name, merge_into.as_deref()))
+    }
+
+    /// Moves a workspace to another position, counted from 0.
+    pub fn move_workspace<S: Into<String>>(name: S, position: usize) -> Command {
+        let name = name.into();
+        Rc::new(move |wm| wm.move_workspace(&name, position))
+    }
+
+    /// Shows or hides the named scratchpad.
+    pub fn toggle_scratchpad<S: Into<String>>(name: S) -> Command {
+        let name = name.into();
+        Rc::new(move |wm| wm.toggle_scratchpad(&name))
+    }
+
+    /// Switches the focused workspace to its next layout.
+    pub fn layout_next() -> Command {
+        Rc::new(|wm| {
+            wm.layout_next();
+            Ok(())
+        })
+    }
+
+    /// Switches the focused workspace to the named layout.
+    pub fn layout_set<S: Into<String>>(name: S) -> Command {
+        let name = name.into();
+        Rc::new(move |wm| wm.set_layout(&name))
+    }
+
+    /// Sends a message to the layout of the focused workspace, e.g. to
+    /// change its number of master windows.
+    pub fn layout_message(message: Message) -> Command {
+        Rc::new(move |wm| {
+            wm.send_layout_message(message);
+            Ok(())
+        })
+    }
+
+    /// Shows the focused window over the whole screen, or goes back to the
+    /// layout.
+    pub fn toggle_maximize() -> Command {
+        Rc::new(|wm| {
+            wm.toggle_maximize();
+            Ok(())
+        })
+    }
+
+    /// Makes the focused window float where it is, or tiles it again.
+    pub fn toggle_floating() -> Command {
+        Rc::new(|wm| {
+            wm.toggle_floating();
+            Ok(())
+        })
+    }
+
+    /// Shows the focused window on every workspace of its screen, or only
+    /// on the workspace it's on.
+    pub fn toggle_sticky() -> Command {
+        Rc::new(|wm| {
+            wm.toggle_sticky();
+            Ok(())
+        })
+    }
+
+    /// Minimizes the focused window.
+    pub fn minimize() -> Command {
+        Rc::new(|wm| {
+            wm.minimize_focused_window();
+            Ok(())
+        })
+    }
+
+    /// Restores the most recently minimized window.
+    pub fn restore_last() -> Command {
+        Rc::new(|wm| wm.restore_last_window())
+    }
+
+    /// Restores a minimized window given its id or part of its title.
+    pub fn restore<S: Into<String>>(name: S) -> Command {
+        let name = name.into();
+        Rc::new(move |wm| wm.restore_named_window(&name))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::{from_args, parse};
+
+    #[test]
+    fn test_parse() {
+        assert!(parse("focus_next").is_ok());
+        assert!(parse("  switch_workspace   web ").is_ok());
+        assert!(parse("spawn urxvt -e htop").is_ok());
+        assert!(from_args("spawn", &["sh", "-c", "echo a b"]).is_ok());
+        assert!(parse("after 500 switch_workspace web").is_ok());
+        assert!(parse("every clock 1000 spawn xsetroot -name now").is_ok());
+        assert!(parse("cancel_timer clock").is_ok());
+        assert!(parse("idle warp 500 warp_pointer").is_ok());
+        assert!(parse("reset_timer warp").is_ok());
+        assert!(parse("spawn_shell cd ~/src && urxvt").is_ok());
+        assert!(parse("spawn_on web firefox --new-window").is_ok());
+        assert!(parse("toggle_scratchpad term").is_ok());
+        assert!(parse("restore 41943043").is_ok());
+        assert!(parse("restore Mozilla Firefox").is_ok());
+        assert!(parse("swap_screens").is_ok());
+        assert!(parse("swap_screens 1").is_ok());
+        assert!(parse("focus_screen left").is_ok());
+        assert!(parse("move_window_to_screen 0").is_ok());
+        assert!(parse("layout_next").is_ok());
+        assert!(parse("layout_set monocle").is_ok());
+        assert!(parse("inc_master").is_ok());
+        assert!(parse("dec_master 2").is_ok());
+        assert!(parse("shrink_master").is_ok());
+        assert!(parse("add_workspace mail").is_ok());
+        assert!(parse("rename_workspace chat").is_ok());
+        assert!(parse("rename_workspace mail chat").is_ok());
+        assert!(parse("remove_workspace chat").is_ok());
+        assert!(parse("remove_workspace chat web").is_ok());
+        assert!(parse("move_workspace chat 0").is_ok());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |command| parse(command).err().unwrap().to_string();
+        assert_eq!(error(""), "Empty command");
+        assert_eq!(error("nope"), "Unknown command 'nope'");
+        assert_eq!(error("focus_next now"), "Command 'focus_next' takes no arguments");
+        assert_eq!(
+            error("switch_workspace"),
+            "Command 'switch_workspace' takes exactly one argument"
+        );
+        assert_eq!(error("spawn"), "Command 'spawn' needs a program to run");
+        assert!(error("focus_screen sideways").starts_with("Invalid screen 'sideways'"));
+        assert_eq!(
+            error("swap_screens left"),
+            "Invalid screen 'left', expected its position"
+        );
+        assert_eq!(
+            error("spawn_on web"),
+            "Command 'spawn_on' needs a workspace and a program to run"
+        );
+        assert_eq!(error("after 500"), "Command 'after' needs a command to run");
+        assert_eq!(error("move_workspace chat first"), "Invalid position 'first'");
+        assert_eq!(error("inc_master all"), "Invalid number of windows 'all'");
+        assert_eq!(error("after soon quit"), "Invalid delay 'soon', expected milliseconds");
+        assert_eq!(error("after 500 nope"), "Unknown command 'nope'");
+        assert_eq!(error("every 1000 quit"), "Invalid interval 'quit', expected milliseconds");
+        assert_eq!(error("every clock 1000"), "Command 'every' needs a command to run");
+        assert_eq!(
+            error("every clock 0 quit"),
+            "Invalid interval '0', expected at least 1 millisecond"
+        );
+        assert_eq!(error("idle warp soon quit"), "Invalid delay 'soon', expected milliseconds");
+        assert_eq!(error("idle warp"), "Command 'idle' needs a name, a delay and a command");
+        assert_eq!(error("warp_pointer now"), "Command 'warp_pointer' takes no arguments");
+        assert_eq!(
+            error("every clock"),
+            "Command 'every' needs a name, an interval and a command"
+        );
+        assert_eq!(error("cancel_timer"), "Command 'cancel_timer' takes exactly one argument");
+    }
 }
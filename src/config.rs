@@ -9,14 +9,789 @@
        Position
        Behavior (Float|Maximize|Minimized|BSPTiled)
        Looks (Buttons Order take xmobar config as reference)
-   * Autostarts apps use xdg standard
 */
 
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use error_chain::bail;
+use log::{info, log};
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::cmd;
+use crate::errors::*;
+use crate::keys::{KeyCombo, KeyHandlers};
+use crate::layout::{self, Layout};
+use crate::spawn::Spawn;
+
+/// The configuration used when there is no configuration file.
+pub const DEFAULT_CONFIG: &str = include_str!("default_config.toml");
+
+#[derive(Clone)]
 pub struct Config {
     pub workspaces_config: Vec<WorkspaceConfig>,
+    /// Space left around each tiled window.
+    pub gaps: u32,
+    /// Moves the pointer to the screen focused by a command.
+    pub warp_pointer: bool,
+    /// Creates workspaces switched to by an unknown name, and removes those
+    /// that are not configured once they are empty and hidden.
+    pub dynamic_workspaces: bool,
+    /// Puts every window into a frame of ours, even those without a title
+    /// bar.
+    pub reparent: bool,
+    pub border: BorderConfig,
+    pub tab_bar: TabBarConfig,
+    pub decorations: DecorationConfig,
+    /// Rules applied to new windows, the first matching one wins.
+    pub rules: Vec<Rule>,
+    pub autostart: AutostartConfig,
+    pub scratchpads: Vec<ScratchpadConfig>,
+    /// Layouts each workspace can use, in the order `layout_next` goes
+    /// through them. Workspaces start with the first one unless configured
+    /// otherwise.
+    pub layouts: Vec<Box<dyn Layout>>,
 }
 
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            workspaces_config: vec![],
+            gaps: 0,
+            warp_pointer: false,
+            dynamic_workspaces: false,
+            reparent: false,
+            border: BorderConfig::default(),
+            tab_bar: TabBarConfig::default(),
+            decorations: DecorationConfig::default(),
+            rules: vec![],
+            autostart: AutostartConfig::default(),
+            scratchpads: vec![],
+            layouts: layout::defaults(),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct WorkspaceConfig {
     pub name: String,
-    pub rules: (),
-}
\ No newline at end of file
+    /// Name of the layout used when the workspace is created, the first
+    /// one if empty.
+    pub layout: String,
+}
+
+impl WorkspaceConfig {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        WorkspaceConfig {
+            name: name.into(),
+            layout: String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BorderConfig {
+    pub width: u32,
+    /// Pixel value of the focused window's border.
+    pub focused: u32,
+    /// Pixel value of the other windows' borders.
+    pub unfocused: u32,
+}
+
+impl Default for BorderConfig {
+    fn default() -> Self {
+        BorderConfig {
+            width: 0,
+            focused: 0x005294e2,
+            unfocused: 0x002f343f,
+        }
+    }
+}
+
+/// How the tab bars of layouts such as `TabbedLayout` look.
+#[derive(Clone, Debug)]
+pub struct TabBarConfig {
+    /// Name of the core X font of the titles, e.g. `fixed` or an XLFD.
+    pub font: String,
+    /// Pixel value of the focused window's tab.
+    pub focused: u32,
+    /// Pixel value of the other windows' tabs.
+    pub unfocused: u32,
+    /// Pixel value of the tabs of windows asking for attention.
+    pub urgent: u32,
+    /// Pixel value of the titles.
+    pub text: u32,
+}
+
+impl Default for TabBarConfig {
+    fn default() -> Self {
+        TabBarConfig {
+            font: "fixed".to_owned(),
+            focused: 0x005294e2,
+            unfocused: 0x002f343f,
+            urgent: 0x00e25252,
+            text: 0x00ffffff,
+        }
+    }
+}
+
+/// Which windows get a title bar.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Decorated {
+    None,
+    Floating,
+    All,
+}
+
+impl FromStr for Decorated {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "none" => Decorated::None,
+            "floating" => Decorated::Floating,
+            "all" => Decorated::All,
+            _ => bail!("Invalid windows '{}', expected none, floating or all", s),
+        })
+    }
+}
+
+/// Side of the window the title bar goes on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TitlePosition {
+    Top,
+    Bottom,
+}
+
+impl FromStr for TitlePosition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "top" => TitlePosition::Top,
+            "bottom" => TitlePosition::Bottom,
+            _ => bail!("Invalid position '{}', expected top or bottom", s),
+        })
+    }
+}
+
+/// A title bar button.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Button {
+    /// Closes the window.
+    Close,
+    /// Makes the window float, or tiles it again.
+    Float,
+    /// Maximizes the window, or restores it.
+    Maximize,
+    /// Minimizes the window.
+    Minimize,
+}
+
+impl FromStr for Button {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "close" => Button::Close,
+            "float" => Button::Float,
+            "maximize" => Button::Maximize,
+            "minimize" => Button::Minimize,
+            _ => bail!(
+                "Invalid button '{}', expected close, float, maximize or minimize",
+                s
+            ),
+        })
+    }
+}
+
+/// The title bars Lanta draws around windows, in frame windows it puts the
+/// windows in.
+#[derive(Clone, Debug)]
+pub struct DecorationConfig {
+    pub windows: Decorated,
+    pub position: TitlePosition,
+    /// Height of the title bar, and size of its buttons.
+    pub height: u32,
+    /// Buttons on the left of the title, from left to right.
+    pub left_buttons: Vec<Button>,
+    /// Buttons on the right of the title, from left to right.
+    pub right_buttons: Vec<Button>,
+    /// Name of the core X font of the titles.
+    pub font: String,
+    /// Pixel value of the focused window's title bar.
+    pub focused: u32,
+    /// Pixel value of the other windows' title bars.
+    pub unfocused: u32,
+    /// Pixel value of the title bars of windows asking for attention.
+    pub urgent: u32,
+    /// Pixel value of the titles and button labels.
+    pub text: u32,
+}
+
+impl Default for DecorationConfig {
+    fn default() -> Self {
+        DecorationConfig {
+            windows: Decorated::None,
+            position: TitlePosition::Top,
+            height: 20,
+            left_buttons: vec![],
+            right_buttons: vec![Button::Minimize, Button::Maximize, Button::Float, Button::Close],
+            font: "fixed".to_owned(),
+            focused: 0x005294e2,
+            unfocused: 0x002f343f,
+            urgent: 0x00e25252,
+            text: 0x00ffffff,
+        }
+    }
+}
+
+/// What to run once Lanta has become the WM. Nothing is run again after a
+/// restart.
+#[derive(Clone)]
+pub struct AutostartConfig {
+    /// Whether to start the applications in the XDG autostart directories.
+    pub xdg: bool,
+    /// Commands run after the XDG autostart applications are started.
+    pub commands: Vec<cmd::Command>,
+}
+
+impl Default for AutostartConfig {
+    fn default() -> Self {
+        AutostartConfig {
+            xdg: true,
+            commands: vec![],
+        }
+    }
+}
+
+/// A window kept hidden until toggled, e.g. a terminal or a calculator.
+#[derive(Clone, Debug)]
+pub struct ScratchpadConfig {
+    pub name: String,
+    /// Started when the scratchpad is toggled and has no window. Its first
+    /// window becomes the scratchpad's.
+    pub spawn: Option<Spawn>,
+    /// Size of the window, as a fraction of the screen's viewport.
+    pub width: f32,
+    pub height: f32,
+}
+
+impl ScratchpadConfig {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        ScratchpadConfig {
+            name: name.into(),
+            spawn: None,
+            width: 0.6,
+            height: 0.6,
+        }
+    }
+}
+
+/// Decides where a new window goes, based on its class and title.
+#[derive(Clone, Debug, Default)]
+pub struct Rule {
+    /// Matches the class part of WM_CLASS exactly.
+    pub class: Option<String>,
+    /// Matches if the title contains this string.
+    pub title: Option<String>,
+    /// Name of the workspace the window is sent to.
+    pub workspace: Option<String>,
+    pub floating: bool,
+    /// Name of the scratchpad the window becomes, if it has none.
+    pub scratchpad: Option<String>,
+    /// Shows the window on every workspace of its screen.
+    pub sticky: bool,
+}
+
+impl Rule {
+    pub fn matches(&self, class: Option<&str>, title: Option<&str>) -> bool {
+        let class_matches = match self.class {
+            Some(ref rule_class) => class == Some(rule_class),
+            None => true,
+        };
+        let title_matches = match self.title {
+            Some(ref rule_title) => title.is_some_and(|t| t.contains(rule_title.as_str())),
+            None => true,
+        };
+        class_matches && title_matches
+    }
+}
+
+/// Returns the default location of the configuration file:
+/// `$XDG_CONFIG_HOME/lanta/config.toml`.
+pub fn default_path() -> Result<PathBuf> {
+    let xdg_dirs = xdg::BaseDirectories::with_prefix("lanta")?;
+    Ok(xdg_dirs.get_config_home().join("config.toml"))
+}
+
+/// Loads the configuration file, falling back to `DEFAULT_CONFIG` if it does
+/// not exist.
+pub(crate) fn load(path: &Path) -> Result<(KeyHandlers, Config)> {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => {
+            info!("No configuration at {}, using defaults", path.display());
+            DEFAULT_CONFIG.to_owned()
+        }
+        Err(error) => {
+            return Err(error).chain_err(|| format!("Could not read {}", path.display()));
+        }
+    };
+    parse(&source).chain_err(|| format!("Invalid configuration in {}", path.display()))
+}
+
+/// Parses the TOML configuration format.
+///
+/// All errors found are reported together, each one with the line it was
+/// found on.
+pub(crate) fn parse(source: &str) -> Result<(KeyHandlers, Config)> {
+    let file: ConfigFile = toml::from_str(source)?;
+    let mut errors = vec![];
+    let report = |errors: &mut Vec<String>, span: (usize, usize), error: Error| {
+        let line = source[..span.0].matches('\n').count() + 1;
+        let causes: Vec<String> = error.iter().map(|e| e.to_string()).collect();
+        errors.push(format!("line {}: {}", line, causes.join(": ")));
+    };
+
+    let mut keys = vec![];
+    for (combo, command) in &file.keys {
+        let parsed = combo
+            .parse::<KeyCombo>()
+            .and_then(|combo| Ok((combo, cmd::parse(command.get_ref())?)));
+        match parsed {
+            Ok(binding) => keys.push(binding),
+            Err(error) => report(&mut errors, command.span(), error),
+        }
+    }
+
+    let mut layouts = layout::defaults();
+    if let Some(ref names) = file.layouts {
+        let stock = mem::take(&mut layouts);
+        for name in names {
+            match stock.iter().find(|layout| layout.name() == name.get_ref()) {
+                Some(layout) => layouts.push(layout.clone()),
+                None => {
+                    let error = format!("Unknown layout '{}'", name.get_ref()).into();
+                    report(&mut errors, name.span(), error);
+                }
+            }
+        }
+        if names.is_empty() {
+            errors.push("at least one layout must be configured".to_owned());
+        }
+    }
+
+    let mut workspaces_config = vec![];
+    for workspace in file.workspaces {
+        let mut workspace_config = WorkspaceConfig::new(workspace.name);
+        match workspace.layout {
+            Some(layout) => {
+                if layouts.iter().any(|l| l.name() == layout.get_ref()) {
+                    workspace_config.layout = layout.into_inner();
+                } else {
+                    let error = format!("Unknown layout '{}'", layout.get_ref()).into();
+                    report(&mut errors, layout.span(), error);
+                }
+            }
+            None => {
+                if let Some(layout) = layouts.first() {
+                    workspace_config.layout = layout.name().to_owned();
+                }
+            }
+        }
+        workspaces_config.push(workspace_config);
+    }
+    if workspaces_config.is_empty() {
+        errors.push("at least one workspace must be configured".to_owned());
+    }
+
+    let mut border = BorderConfig::default();
+    border.width = file.border.width.unwrap_or(border.width);
+    for (color, value) in &mut [
+        (&file.border.focused, &mut border.focused),
+        (&file.border.unfocused, &mut border.unfocused),
+    ] {
+        if let Some(color) = color {
+            match parse_color(color.get_ref()) {
+                Ok(pixel) => **value = pixel,
+                Err(error) => report(&mut errors, color.span(), error),
+            }
+        }
+    }
+
+    let mut tab_bar = TabBarConfig::default();
+    if let Some(ref font) = file.tab_bar.font {
+        tab_bar.font = font.clone();
+    }
+    for (color, value) in &mut [
+        (&file.tab_bar.focused, &mut tab_bar.focused),
+        (&file.tab_bar.unfocused, &mut tab_bar.unfocused),
+        (&file.tab_bar.urgent, &mut tab_bar.urgent),
+        (&file.tab_bar.text, &mut tab_bar.text),
+    ] {
+        if let Some(color) = color {
+            match parse_color(color.get_ref()) {
+                Ok(pixel) => **value = pixel,
+                Err(error) => report(&mut errors, color.span(), error),
+            }
+        }
+    }
+
+    let mut decorations = DecorationConfig::default();
+    let file_decorations = &file.decorations;
+    if let Some(ref windows) = file_decorations.windows {
+        match windows.get_ref().parse() {
+            Ok(windows) => decorations.windows = windows,
+            Err(error) => report(&mut errors, windows.span(), error),
+        }
+    }
+    if let Some(ref position) = file_decorations.position {
+        match position.get_ref().parse() {
+            Ok(position) => decorations.position = position,
+            Err(error) => report(&mut errors, position.span(), error),
+        }
+    }
+    decorations.height = file_decorations.height.unwrap_or(decorations.height);
+    for (names, buttons) in &mut [
+        (&file_decorations.left_buttons, &mut decorations.left_buttons),
+        (&file_decorations.right_buttons, &mut decorations.right_buttons),
+    ] {
+        if let Some(names) = names {
+            buttons.clear();
+            for name in names {
+                match name.get_ref().parse() {
+                    Ok(button) => buttons.push(button),
+                    Err(error) => report(&mut errors, name.span(), error),
+                }
+            }
+        }
+    }
+    if let Some(ref font) = file_decorations.font {
+        decorations.font = font.clone();
+    }
+    for (color, value) in &mut [
+        (&file_decorations.focused, &mut decorations.focused),
+        (&file_decorations.unfocused, &mut decorations.unfocused),
+        (&file_decorations.urgent, &mut decorations.urgent),
+        (&file_decorations.text, &mut decorations.text),
+    ] {
+        if let Some(color) = color {
+            match parse_color(color.get_ref()) {
+                Ok(pixel) => **value = pixel,
+                Err(error) => report(&mut errors, color.span(), error),
+            }
+        }
+    }
+
+    let mut autostart = AutostartConfig::default();
+    autostart.xdg = file.autostart.xdg.unwrap_or(autostart.xdg);
+    for command in &file.autostart.commands {
+        match cmd::parse(command.get_ref()) {
+            Ok(command) => autostart.commands.push(command),
+            Err(error) => report(&mut errors, command.span(), error),
+        }
+    }
+
+    let mut scratchpads: Vec<ScratchpadConfig> = vec![];
+    for scratchpad in file.scratchpads {
+        let name = scratchpad.name.get_ref();
+        if scratchpads.iter().any(|s| &s.name == name) {
+            let error = format!("Scratchpad '{}' is defined twice", name).into();
+            report(&mut errors, scratchpad.name.span(), error);
+            continue;
+        }
+        let mut scratchpad_config = ScratchpadConfig::new(scratchpad.name.into_inner());
+        scratchpad_config.spawn = scratchpad.command.map(Spawn::shell);
+        for (size, value) in &mut [
+            (&scratchpad.width, &mut scratchpad_config.width),
+            (&scratchpad.height, &mut scratchpad_config.height),
+        ] {
+            if let Some(size) = size {
+                if *size.get_ref() > 0.0 && *size.get_ref() <= 1.0 {
+                    **value = *size.get_ref();
+                } else {
+                    let error = format!("Invalid size {}, expected a fraction", size.get_ref());
+                    report(&mut errors, size.span(), error.into());
+                }
+            }
+        }
+        scratchpads.push(scratchpad_config);
+    }
+
+    let mut rules = vec![];
+    for rule in file.rules {
+        if let Some(ref name) = rule.scratchpad {
+            if !scratchpads.iter().any(|s| &s.name == name.get_ref()) {
+                let error = format!("Unknown scratchpad '{}'", name.get_ref()).into();
+                report(&mut errors, name.span(), error);
+            }
+        }
+        rules.push(Rule {
+            class: rule.class,
+            title: rule.title,
+            workspace: rule.workspace,
+            floating: rule.floating,
+            scratchpad: rule.scratchpad.map(Spanned::into_inner),
+            sticky: rule.sticky,
+        });
+    }
+
+    if !errors.is_empty() {
+        bail!(errors.join("\n"));
+    }
+
+    let config = Config {
+        workspaces_config,
+        gaps: file.gaps,
+        warp_pointer: file.warp_pointer,
+        dynamic_workspaces: file.dynamic_workspaces,
+        reparent: file.reparent,
+        border,
+        tab_bar,
+        decorations,
+        rules,
+        autostart,
+        scratchpads,
+        layouts,
+    };
+    Ok((KeyHandlers::from(keys), config))
+}
+
+/// Parses a `#rrggbb` colour into a pixel value.
+fn parse_color(color: &str) -> Result<u32> {
+    if color.len() == 7 && color.starts_with('#') {
+        if let Ok(pixel) = u32::from_str_radix(&color[1..], 16) {
+            return Ok(pixel);
+        }
+    }
+    bail!("Invalid colour '{}', expected #rrggbb", color)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    gaps: u32,
+    #[serde(default)]
+    warp_pointer: bool,
+    #[serde(default)]
+    dynamic_workspaces: bool,
+    #[serde(default)]
+    reparent: bool,
+    #[serde(default)]
+    border: BorderFile,
+    #[serde(default)]
+    tab_bar: TabBarFile,
+    #[serde(default)]
+    decorations: DecorationFile,
+    layouts: Option<Vec<Spanned<String>>>,
+    #[serde(default)]
+    workspaces: Vec<WorkspaceFile>,
+    #[serde(default)]
+    keys: BTreeMap<String, Spanned<String>>,
+    #[serde(default)]
+    rules: Vec<RuleFile>,
+    #[serde(default)]
+    autostart: AutostartFile,
+    #[serde(default)]
+    scratchpads: Vec<ScratchpadFile>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BorderFile {
+    width: Option<u32>,
+    focused: Option<Spanned<String>>,
+    unfocused: Option<Spanned<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TabBarFile {
+    font: Option<String>,
+    focused: Option<Spanned<String>>,
+    unfocused: Option<Spanned<String>>,
+    urgent: Option<Spanned<String>>,
+    text: Option<Spanned<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DecorationFile {
+    windows: Option<Spanned<String>>,
+    position: Option<Spanned<String>>,
+    height: Option<u32>,
+    left_buttons: Option<Vec<Spanned<String>>>,
+    right_buttons: Option<Vec<Spanned<String>>>,
+    font: Option<String>,
+    focused: Option<Spanned<String>>,
+    unfocused: Option<Spanned<String>>,
+    urgent: Option<Spanned<String>>,
+    text: Option<Spanned<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AutostartFile {
+    xdg: Option<bool>,
+    #[serde(default)]
+    commands: Vec<Spanned<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WorkspaceFile {
+    name: String,
+    layout: Option<Spanned<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    class: Option<String>,
+    title: Option<String>,
+    workspace: Option<String>,
+    #[serde(default)]
+    floating: bool,
+    scratchpad: Option<Spanned<String>>,
+    #[serde(default)]
+    sticky: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScratchpadFile {
+    name: Spanned<String>,
+    command: Option<String>,
+    width: Option<Spanned<f32>>,
+    height: Option<Spanned<f32>>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, Button, Decorated, TitlePosition, DEFAULT_CONFIG};
+
+    #[test]
+    fn test_parse_default_config() {
+        let (keys, config) = parse(DEFAULT_CONFIG).unwrap();
+        assert!(!keys.key_combos().is_empty());
+        assert!(!config.workspaces_config.is_empty());
+    }
+
+    #[test]
+    fn test_parse() {
+        let source = r##"
+            gaps = 5
+            warp_pointer = true
+            dynamic_workspaces = true
+            reparent = true
+            layouts = ["monocle", "bsp"]
+
+            [border]
+            width = 2
+            focused = "#ff0000"
+
+            [tab_bar]
+            font = "-misc-fixed-medium-r-normal--13-*-*-*-*-*-iso10646-1"
+            urgent = "#ffff00"
+
+            [decorations]
+            windows = "floating"
+            position = "bottom"
+            left_buttons = ["close"]
+            right_buttons = ["minimize", "maximize"]
+
+            [[workspaces]]
+            name = "web"
+
+            [keys]
+            "M-j" = "focus_next"
+
+            [[rules]]
+            class = "Firefox"
+            workspace = "web"
+            sticky = true
+
+            [[rules]]
+            class = "URxvt"
+            title = "scratchpad"
+            scratchpad = "term"
+
+            [[scratchpads]]
+            name = "term"
+            command = "urxvt -title scratchpad"
+            width = 0.8
+
+            [autostart]
+            xdg = false
+            commands = ["spawn nm-applet", "spawn_on web firefox"]
+        "##;
+        let (keys, config) = parse(source).unwrap();
+        assert_eq!(keys.key_combos().len(), 1);
+        assert_eq!(config.gaps, 5);
+        assert!(config.warp_pointer);
+        assert!(config.dynamic_workspaces);
+        assert!(config.reparent);
+        assert_eq!(config.border.width, 2);
+        assert_eq!(config.border.focused, 0xff0000);
+        assert!(config.tab_bar.font.ends_with("iso10646-1"));
+        assert_eq!(config.tab_bar.urgent, 0xffff00);
+        assert_eq!(config.decorations.windows, Decorated::Floating);
+        assert_eq!(config.decorations.position, TitlePosition::Bottom);
+        assert_eq!(config.decorations.left_buttons, vec![Button::Close]);
+        assert_eq!(
+            config.decorations.right_buttons,
+            vec![Button::Minimize, Button::Maximize]
+        );
+        assert_eq!(config.workspaces_config[0].name, "web");
+        assert_eq!(config.workspaces_config[0].layout, "monocle");
+        let layouts: Vec<_> = config.layouts.iter().map(|l| l.name()).collect();
+        assert_eq!(layouts, vec!["monocle", "bsp"]);
+        assert!(config.rules[0].matches(Some("Firefox"), None));
+        assert!(!config.rules[0].matches(Some("Chromium"), None));
+        assert!(config.rules[0].sticky);
+        assert!(!config.autostart.xdg);
+        assert_eq!(config.autostart.commands.len(), 2);
+        assert_eq!(config.rules[1].scratchpad.as_deref(), Some("term"));
+        assert_eq!(config.scratchpads[0].name, "term");
+        assert_eq!(config.scratchpads[0].width, 0.8);
+        assert_eq!(config.scratchpads[0].height, 0.6);
+        assert!(config.scratchpads[0].spawn.is_some());
+    }
+
+    #[test]
+    fn test_parse_errors_have_line_numbers() {
+        let source = "[[workspaces]]\nname = \"a\"\nlayout = \"nope\"\n\n[keys]\n\"M-Nope\" = \"focus_next\"\n\"M-j\" = \"nope\"\n\n[autostart]\ncommands = [\"nope\"]\n";
+        let error = parse(source).err().unwrap().to_string();
+        assert!(error.contains("line 3: Unknown layout 'nope'"), "{}", error);
+        assert!(
+            error.contains("line 6: Invalid key combination 'M-Nope': Unknown key 'Nope'"),
+            "{}",
+            error
+        );
+        assert!(error.contains("line 7: Unknown command 'nope'"), "{}", error);
+        assert!(error.contains("line 10: Unknown command 'nope'"), "{}", error);
+
+        let source = "[[workspaces]]\nname = \"a\"\n\n[[rules]]\nscratchpad = \"nope\"\n\n[[scratchpads]]\nname = \"calc\"\nwidth = 2.0\n";
+        let error = parse(source).err().unwrap().to_string();
+        assert!(error.contains("line 5: Unknown scratchpad 'nope'"), "{}", error);
+        assert!(error.contains("line 9: Invalid size 2, expected a fraction"), "{}", error);
+
+        let error = parse("layouts = [\"bsp\", \"nope\"]\n").err().unwrap().to_string();
+        assert!(error.contains("line 1: Unknown layout 'nope'"), "{}", error);
+
+        let error = parse("gaps = \"wide\"\n").err().unwrap().to_string();
+        assert!(error.contains("line 1"), "{}", error);
+
+        let source = "[decorations]\nwindows = \"some\"\nright_buttons = [\"shade\"]\n";
+        let error = parse(source).err().unwrap().to_string();
+        assert!(error.contains("line 2: Invalid windows 'some'"), "{}", error);
+        assert!(error.contains("line 3: Invalid button 'shade'"), "{}", error);
+    }
+}
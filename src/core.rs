@@ -1,19 +1,92 @@
-use log::{error, info, log};
+use std::collections::HashMap;
+use std::env;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use error_chain::{bail, ChainedError};
+use log::{debug, error, info, log, warn};
+use serde_json::json;
 
-use crate::config::*;
+use crate::autostart;
+use crate::config::{self, *};
+use crate::cmd::{self, Command};
+use crate::decorations::{self, Decorations};
 use crate::errors::*;
+use crate::ipc;
 use crate::keys::KeyCombo;
 use crate::keys::KeyHandlers;
+use crate::layout::Message;
+use crate::reactor::{self, Reactor, TimerId};
+use crate::spawn::Spawn;
+use crate::tab_bar::TabBars;
 use crate::utils::Rectangle;
-use crate::window_set::WindowSet;
-use crate::x::{Connection, Event, StrutPartial, WindowId, WindowType};
-use error_chain::ChainedError;
-use std::rc::Rc;
+use crate::window_set::{Screen, ScreenTarget, Status, WindowSet};
+use crate::x::{Connection, Event, WindowId, WindowState, WindowType};
 
 pub struct Lanta {
     connection: Rc<Connection>,
     keys: KeyHandlers,
+    config: Config,
+    /// Where the configuration was loaded from, if it came from a file.
+    config_path: Option<PathBuf>,
     window_set: WindowSet,
+    reactor: Reactor,
+    /// Cleared to leave the event loop.
+    running: bool,
+    /// Set to exec ourselves again once the event loop is left.
+    restarting: bool,
+    /// Set if we were restarted, in which case the autostart applications
+    /// are still running.
+    restarted: bool,
+    /// Processes we started that have not opened a window yet.
+    spawned: Vec<Spawned>,
+    /// Number of processes started, to make up startup notification ids.
+    spawn_count: u64,
+    /// IPC clients listening to our events.
+    subscribers: Vec<ipc::Subscriber>,
+    /// Events not yet sent to the subscribers.
+    events: Vec<ipc::Event>,
+    /// Where each shown tiled window was last placed, to skip placing it
+    /// again when it doesn't move.
+    geometry: HashMap<WindowId, Rectangle>,
+    tab_bars: TabBars,
+    decorations: Decorations,
+    /// The name of the focused workspace and the focused window when events
+    /// were last sent.
+    last_focus: (String, Option<WindowId>),
+    /// Timers started by the `every` and `idle` commands, by name.
+    named_timers: HashMap<String, NamedTimer>,
+}
+
+/// How long a spawned process has to open its first window for it to be
+/// placed on the workspace it was spawned from.
+const SPAWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A timer started by a command, which other commands reset or cancel by
+/// name.
+struct NamedTimer {
+    id: TimerId,
+    /// The delay and command of an idle timer, to start it again once it has
+    /// run.
+    idle: Option<(Duration, Command)>,
+}
+
+/// A process we started, waiting for its first window.
+#[derive(Debug)]
+struct Spawned {
+    pid: u32,
+    startup_id: String,
+    workspace: String,
+    /// Whether the workspace was asked for, rather than the one focused.
+    explicit: bool,
+    /// Name of the scratchpad that was toggled to start the process.
+    scratchpad: Option<String>,
+    deadline: Instant,
 }
 
 impl Lanta {
@@ -24,54 +97,398 @@ impl Lanta {
         let keys = keys.into();
         let connection = Rc::new(Connection::connect()?);
         connection.install_as_wm(&keys)?;
-        let screens = vec![]; // TODO
-        let window_set = WindowSet::new(config.workspaces_config, screens);
+        let screens = connection.get_screens();
+        let window_set = WindowSet::new(
+            config.workspaces_config.clone(),
+            config.layouts.clone(),
+            screens,
+        );
+        let reactor = Reactor::new(connection.clone())?;
 
         let mut wm = Lanta {
             connection: connection.clone(),
             keys,
+            config,
+            config_path: None,
             window_set,
+            reactor,
+            running: true,
+            restarting: false,
+            restarted: false,
+            spawned: vec![],
+            spawn_count: 0,
+            subscribers: vec![],
+            events: vec![],
+            geometry: HashMap::new(),
+            tab_bars: TabBars::new(connection.clone()),
+            decorations: Decorations::new(connection.clone()),
+            last_focus: (String::new(), None),
+            named_timers: HashMap::new(),
         };
 
-        // Learn about existing top-level windows.
-        let existing_windows = connection.top_level_windows()?;
+        // Learn about existing top-level windows. If we were restarted, put
+        // them back where they were, otherwise treat them as new windows.
+        let existing_windows: Vec<WindowId> = connection
+            .top_level_windows()?
+            .into_iter()
+            .filter(|window| connection.should_adopt(window))
+            .collect();
+        if let Some(state) = connection.take_saved_state() {
+            wm.restarted = true;
+            match wm
+                .window_set
+                .restore(&state, |window| existing_windows.contains(window))
+            {
+                Ok(restored) => {
+                    info!("Restored {} windows", restored.len());
+                    for window_id in restored {
+                        connection.enable_window_key_events(&window_id, &wm.keys);
+                        connection.enable_window_tracking(&window_id);
+                        let urgent = connection.is_window_urgent(&window_id);
+                        wm.window_set.set_urgent(&window_id, urgent);
+                    }
+                }
+                Err(error) => error!("{}", error.display_chain().to_string()),
+            }
+        }
         for window in existing_windows {
             wm.manage_window(window);
         }
 
-        let names = wm.window_set.get_workspace_names();
-        wm.connection
-            .update_ewmh_desktops(names, wm.window_set.focused_workspace());
+        wm.update_ewmh_desktops();
+        wm.perform_layout();
+        wm.last_focus = (
+            wm.window_set.workspace().name().to_owned(),
+            wm.window_set.focused_window(),
+        );
+        wm.events.clear();
 
         Ok(wm)
     }
 
-    // fn viewport(&self) -> Rectangle {
-    //     let (width, height) = self
-    //         .connection
-    //         .get_window_geometry(self.connection.root_window_id());
-    //     self.screen.viewport(width, height)
-    // }
+    /// Creates a new WM configured by a TOML configuration file.
+    ///
+    /// The file is read again when the configuration is reloaded.
+    pub fn from_config_file<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let (keys, config) = config::load(&path)?;
+        let mut wm = Lanta::new(keys, config)?;
+        wm.config_path = Some(path);
+        Ok(wm)
+    }
 
-    pub fn run(mut self) {
+    /// Handles events until `quit()` is called or a SIGTERM or SIGINT is
+    /// received, then releases the windows. A SIGHUP reloads the
+    /// configuration.
+    ///
+    /// Fails if the connection to the X server is lost.
+    pub fn run(mut self) -> Result<()> {
         info!("Started WM, entering event loop.");
-        let event_loop_connection = self.connection.clone();
-        let event_loop = event_loop_connection.get_event_loop();
-        for event in event_loop {
-            match event {
-                Event::MapRequest(window_id) => self.on_map_request(window_id),
-                Event::UnmapNotify(window_id) => self.on_unmap_notify(&window_id),
-                Event::DestroyNotify(window_id) => self.on_destroy_notify(&window_id),
-                Event::KeyPress(key) => self.on_key_press(key),
-                Event::EnterNotify(window_id) => self.on_enter_notify(&window_id),
+        for signal in &[libc::SIGTERM, libc::SIGINT, libc::SIGHUP] {
+            self.reactor.watch_signal(*signal)?;
+        }
+        match ipc::socket_path().and_then(|path| ipc::Server::bind(&path)) {
+            Ok(server) => {
+                // Let the processes we spawn find us.
+                env::set_var(ipc::SOCKET_ENV, server.path());
+                self.reactor.set_ipc_server(server);
+            }
+            Err(error) => {
+                error!("IPC is disabled: {}", error.display_chain().to_string());
+            }
+        };
+        if !self.restarted {
+            self.autostart();
+        }
+
+        while self.running {
+            match self.reactor.next_event()? {
+                reactor::Event::X(event) => self.on_x_event(event),
+                reactor::Event::IpcRequest(client) => self.on_ipc_request(client),
+                reactor::Event::Timer(command) => self.run_command(&command, "timer"),
+                reactor::Event::Signal(signal) => self.on_signal(signal),
+                reactor::Event::ChildExited(pid, status) => {
+                    info!("Process {} exited ({})", pid, status)
+                }
+                reactor::Event::Readable(fd, command) => {
+                    self.run_command(&command, &format!("file descriptor {}", fd))
+                }
             }
+            if self.config.dynamic_workspaces {
+                self.prune_workspaces();
+            }
+            self.send_events();
         }
         info!("Event loop exiting");
+        let state = if self.restarting {
+            Some(self.window_set.save()?)
+        } else {
+            None
+        };
+        self.release_windows();
+        match state {
+            Some(state) => self.exec(&state),
+            None => Ok(()),
+        }
+    }
+
+    /// Replaces this process with a new instance of the binary, which
+    /// restores the saved state.
+    fn exec(&self, state: &str) -> Result<()> {
+        self.connection.save_state(state);
+        self.connection.flush();
+
+        // Use the name we were started with rather than `current_exe()`, as
+        // the latter is the old, deleted file when the binary was rebuilt.
+        let mut args = env::args_os();
+        let program = match args.next() {
+            Some(program) => PathBuf::from(program),
+            None => env::current_exe()?,
+        };
+        info!("Restarting {}", program.display());
+        let error = process::Command::new(&program).args(args).exec();
+        Err(error).chain_err(|| format!("Could not restart {}", program.display()))
+    }
+
+    fn on_signal(&mut self, signal: libc::c_int) {
+        match signal {
+            libc::SIGTERM | libc::SIGINT => {
+                info!("Received signal {}", signal);
+                self.quit();
+            }
+            libc::SIGHUP => {
+                info!("Received SIGHUP");
+                if let Err(error) = self.reload_config() {
+                    error!(
+                        "Could not reload the configuration: {}",
+                        error.display_chain().to_string()
+                    );
+                }
+            }
+            _ => info!("Ignoring signal {}", signal),
+        }
+    }
+
+    /// Gives up every window, so that the next WM can manage them: they are
+    /// taken out of their frames and mapped again, lose their border, and we
+    /// stop listening to their events and keys.
+    fn release_windows(&mut self) {
+        info!("Releasing windows");
+        self.decorations.release_all();
+        let root = *self.connection.root_window_id();
+        self.connection.disable_window_key_events(&root);
+        for window_id in self.window_set.windows() {
+            self.connection.disable_window_tracking(&window_id);
+            self.connection.disable_window_key_events(&window_id);
+            self.connection.set_window_border(&window_id, 0, 0);
+            self.connection.map_window(&window_id);
+        }
+        if !self.restarting {
+            for window_id in self.window_set.minimized_windows() {
+                self.connection.set_window_iconic(&window_id, false);
+            }
+        }
+        self.connection.focus_nothing();
+        self.connection.flush();
+    }
+
+    fn on_x_event(&mut self, event: Event) {
+        match event {
+            Event::MapRequest(window_id) => self.on_map_request(window_id),
+            Event::UnmapNotify(window_id) => self.on_unmap_notify(&window_id),
+            Event::DestroyNotify(window_id) => self.on_destroy_notify(&window_id),
+            Event::KeyPress(key) => self.on_key_press(key),
+            Event::EnterNotify(window_id) => self.on_enter_notify(&window_id),
+            Event::TitleChanged(window_id) => self.on_title_changed(&window_id),
+            Event::UrgencyChanged(window_id) => self.on_urgency_changed(&window_id),
+            Event::MinimizeRequest(window_id) => self.minimize_window(&window_id),
+            Event::StickyRequest(window_id, sticky) => {
+                let sticky = sticky.unwrap_or_else(|| !self.window_set.is_sticky(&window_id));
+                self.set_sticky(&window_id, sticky);
+            }
+            Event::PointerMotion(x, y) => self.on_pointer_motion(x, y),
+            Event::ConfigureRequest(window_id) => self.on_configure_request(&window_id),
+            Event::ButtonPress(window_id, x, y) => self.on_button_press(&window_id, x, y),
+            Event::Expose(_) => self.draw_bars(),
+            Event::ScreensChanged => self.on_screens_changed(),
+        }
+    }
+
+    /// Runs a command, logging its error if it fails.
+    fn run_command(&mut self, command: &Command, source: &str) {
+        if let Err(error) = (command)(self) {
+            error!(
+                "Error running command for {}: {}",
+                source,
+                error.display_chain().to_string()
+            );
+        }
+    }
+
+    /// Runs `command` once, after `delay`.
+    pub fn after(&mut self, delay: Duration, command: Command) -> TimerId {
+        self.reactor.after(delay, command)
+    }
+
+    /// Runs `command` every `interval`, until the timer is cancelled.
+    pub fn every(&mut self, interval: Duration, command: Command) -> TimerId {
+        self.reactor.every(interval, command)
+    }
+
+    /// Restarts a timer, so that it expires a whole delay from now.
+    ///
+    /// Resetting a timer started with `after()` on every key press, for
+    /// instance, runs its command after that much inactivity. Returns false
+    /// if the timer already expired or was cancelled.
+    pub fn reset_timer(&mut self, id: TimerId) -> bool {
+        self.reactor.reset_timer(id)
+    }
+
+    /// Cancels a timer. Returns false if it already expired or was cancelled.
+    pub fn cancel_timer(&mut self, id: TimerId) -> bool {
+        self.reactor.cancel_timer(id)
+    }
+
+    /// Runs `command` every `interval` under a name, by which it can be
+    /// reset or cancelled. A timer already running under that name is
+    /// replaced.
+    pub fn every_named(&mut self, name: &str, interval: Duration, command: Command) {
+        let id = self.reactor.every(interval, command);
+        self.add_named_timer(name, NamedTimer { id, idle: None });
+    }
+
+    /// Runs `command` under a name once no key was pressed for `delay`, and
+    /// again after each later key press followed by that much inactivity.
+    pub fn idle_named(&mut self, name: &str, delay: Duration, command: Command) {
+        let id = self.reactor.after(delay, command.clone());
+        let idle = Some((delay, command));
+        self.add_named_timer(name, NamedTimer { id, idle });
+    }
+
+    fn add_named_timer(&mut self, name: &str, timer: NamedTimer) {
+        if let Some(old) = self.named_timers.insert(name.to_owned(), timer) {
+            self.reactor.cancel_timer(old.id);
+        }
+    }
+
+    /// Restarts the timer started under `name`, so that it expires a whole
+    /// delay from now. An idle timer that already ran is started again.
+    pub fn reset_named_timer(&mut self, name: &str) -> Result<()> {
+        match self.named_timers.get_mut(name) {
+            Some(timer) => {
+                restart_timer(&mut self.reactor, timer);
+                Ok(())
+            }
+            None => bail!("No timer is named '{}'", name),
+        }
+    }
+
+    /// Cancels the timer started under `name`.
+    pub fn cancel_named_timer(&mut self, name: &str) -> Result<()> {
+        match self.named_timers.remove(name) {
+            Some(timer) => {
+                self.reactor.cancel_timer(timer.id);
+                Ok(())
+            }
+            None => bail!("No timer is named '{}'", name),
+        }
+    }
+
+    /// Runs `command` whenever the file descriptor is readable, until
+    /// `unwatch_fd()` is called or the file descriptor is closed. The command
+    /// is expected to read from it.
+    pub fn watch_fd(&mut self, fd: RawFd, command: Command) {
+        self.reactor.watch_fd(fd, command)
+    }
+
+    pub fn unwatch_fd(&mut self, fd: RawFd) {
+        self.reactor.unwatch_fd(fd)
+    }
+
+    /// Makes `run()` release the windows and return once the current event is
+    /// handled.
+    pub fn quit(&mut self) {
+        info!("Quitting");
+        self.running = false;
+    }
+
+    /// Starts a process. Its first window goes to the workspace focused now,
+    /// unless the `Spawn` names another one.
+    pub fn spawn(&mut self, spawn: &Spawn) -> Result<()> {
+        self.start(spawn, None)
+    }
+
+    /// Starts a process, remembering it until it opens its first window,
+    /// which becomes the named scratchpad's if there is one.
+    fn start(&mut self, spawn: &Spawn, scratchpad: Option<&str>) -> Result<()> {
+        self.spawn_count += 1;
+        let startup_id = format!("lanta-{}-{}_TIME0", process::id(), self.spawn_count);
+        let pid = spawn.start(&startup_id)?;
+
+        let now = Instant::now();
+        self.spawned.retain(|spawned| spawned.deadline > now);
+        let workspace = spawn
+            .get_workspace()
+            .unwrap_or_else(|| self.window_set.workspace().name());
+        self.spawned.push(Spawned {
+            pid,
+            startup_id,
+            workspace: workspace.to_owned(),
+            explicit: spawn.get_workspace().is_some(),
+            scratchpad: scratchpad.map(str::to_owned),
+            deadline: now + SPAWN_TIMEOUT,
+        });
+        Ok(())
+    }
+
+    /// Finds the process that opened the window among those still waiting for
+    /// their first window, by startup notification id or pid.
+    fn take_spawned(&mut self, window_id: &WindowId) -> Option<Spawned> {
+        let now = Instant::now();
+        self.spawned.retain(|spawned| spawned.deadline > now);
+        if self.spawned.is_empty() {
+            return None;
+        }
+        let startup_id = self.connection.get_window_startup_id(window_id);
+        let pid = self.connection.get_window_pid(window_id);
+        let ix = self.spawned.iter().position(|spawned| {
+            startup_id.as_ref() == Some(&spawned.startup_id) || pid == Some(spawned.pid)
+        })?;
+        Some(self.spawned.remove(ix))
+    }
+
+    /// Starts the XDG autostart applications and runs the configured
+    /// startup commands.
+    fn autostart(&mut self) {
+        if self.config.autostart.xdg {
+            match autostart::entries() {
+                Ok(entries) => {
+                    for entry in entries {
+                        if let Err(error) = self.spawn(&entry) {
+                            error!("{}", error.display_chain().to_string());
+                        }
+                    }
+                }
+                Err(error) => error!(
+                    "Could not read the autostart entries: {}",
+                    error.display_chain().to_string()
+                ),
+            }
+        }
+        for command in self.config.autostart.commands.clone() {
+            self.run_command(&command, "autostart");
+        }
+    }
+
+    /// Restarts Lanta, e.g. after its binary was rebuilt. The windows are
+    /// kept on their workspaces and in their place in the layout.
+    pub fn restart(&mut self) {
+        self.restarting = true;
+        self.quit();
     }
 
     pub fn manage_window(&mut self, window_id: WindowId) {
         if !self.window_set.contains(&window_id) {
-            // TODO: Follow the management rules
             let window_types = self.connection.get_window_types(&window_id);
             let dock = window_types.contains(&WindowType::Dock);
 
@@ -79,22 +496,738 @@ impl Lanta {
                 .enable_window_key_events(&window_id, &self.keys);
 
             if dock {
-                // TODO: get_dock screen and associate to that screen
-                // let strut_partial = conn.get_strut_partial(&window_id);
-                // let screen_id = self.connection.get_screen(window_id);
-                // self.window_set.get_screen(screen_id).add_dock(window_id, strut_partial);
-                // self.window_set.update_rectangles(); and paint
+                let strut = self
+                    .connection
+                    .get_strut_partial(&window_id)
+                    .unwrap_or_default();
+                let screen = self
+                    .connection
+                    .get_window_rectangle(&window_id)
+                    .and_then(|r| self.window_set.screen_at(r.x, r.y))
+                    .unwrap_or_else(|| self.window_set.focused_screen());
+                info!("Managing dock {} on screen {}: {:?}", window_id, screen, strut);
+
+                self.connection.enable_window_tracking(&window_id);
+                self.window_set.add_dock(window_id, screen, strut);
                 self.connection.map_window(&window_id);
+                self.perform_layout();
             } else {
+                let spawned = self.take_spawned(&window_id);
+                let class = self.connection.get_window_class(&window_id);
+                let title = self.connection.get_window_title(&window_id);
+                let rule = self.config.rules.iter().find(|rule| {
+                    rule.matches(class.as_deref(), title.as_deref())
+                });
+                info!(
+                    "Managing window {} (class: {:?}, title: {:?}, rule: {:?}, spawned: {:?})",
+                    window_id, class, title, rule, spawned
+                );
+
+                let scratchpad = spawned
+                    .as_ref()
+                    .and_then(|spawned| spawned.scratchpad.clone())
+                    .or_else(|| rule.and_then(|rule| rule.scratchpad.clone()));
+                if let Some(name) = scratchpad {
+                    if self.window_set.add_scratchpad(window_id, &name) {
+                        info!("Window {} is scratchpad {}", window_id, name);
+                        // Show it straight away if it was toggled before it
+                        // had a window.
+                        let pending = self
+                            .spawned
+                            .iter()
+                            .position(|spawned| spawned.scratchpad.as_ref() == Some(&name));
+                        if let Some(ix) = pending {
+                            self.spawned.remove(ix);
+                        }
+                        let toggled = spawned.is_some_and(|s| s.scratchpad.is_some());
+                        if toggled || pending.is_some() {
+                            self.window_set.toggle_scratchpad(&name);
+                        }
+                        self.connection.enable_window_tracking(&window_id);
+                        self.perform_layout();
+                        return;
+                    }
+                }
+
+                // A workspace given when spawning wins over the rules, which
+                // win over the workspace the window was spawned from.
+                let rule_workspace = rule.and_then(|rule| rule.workspace.as_deref());
+                let workspace = match spawned {
+                    Some(ref spawned) if spawned.explicit => Some(spawned.workspace.as_str()),
+                    Some(ref spawned) => rule_workspace.or(Some(spawned.workspace.as_str())),
+                    None => rule_workspace,
+                };
+                let workspace = workspace.and_then(|name| self.window_set.workspace_index(name));
+                let floating = rule.is_some_and(|rule| rule.floating)
+                    || window_types.iter().any(|t| {
+                        matches!(
+                            t,
+                            WindowType::Dialog | WindowType::Utility | WindowType::Splash
+                        )
+                    });
+
+                if floating {
+                    self.center_window(&window_id);
+                }
                 self.connection.enable_window_tracking(&window_id);
-                self.window_set.add_window(window_id);
+                self.window_set.add_window(window_id, workspace, floating);
+                let urgent = self.connection.is_window_urgent(&window_id);
+                self.window_set.set_urgent(&window_id, urgent);
+                let sticky = rule.is_some_and(|rule| rule.sticky)
+                    || self
+                        .connection
+                        .get_window_states(&window_id)
+                        .contains(&WindowState::Sticky);
+                if sticky {
+                    self.set_sticky(&window_id, true);
+                }
+                self.perform_layout();
             }
         }
     }
 
     /// TODO
     fn unmanage_window(&mut self, window_id: &WindowId) {
-        self.window_set.remove_window(window_id)
+        if self.window_set.contains(window_id) {
+            self.window_set.remove_window(window_id);
+            self.tab_bars.forget_title(window_id);
+            self.events.push(ipc::Event::WindowClosed {
+                window: *window_id,
+            });
+            self.perform_layout();
+        }
+    }
+
+    /// Places a (floating) window in the middle of the focused screen,
+    /// keeping its size.
+    fn center_window(&self, window_id: &WindowId) {
+        let screen = &self.window_set.screens()[self.window_set.focused_screen()];
+        let viewport = screen.viewport();
+        let (width, height) = self.connection.get_window_geometry(window_id);
+        self.connection.configure_window(
+            window_id,
+            viewport.x + viewport.width.saturating_sub(width) / 2,
+            viewport.y + viewport.height.saturating_sub(height) / 2,
+            width,
+            height,
+        );
+    }
+
+    /// Shows a window without seeing the events it generates.
+    fn show_window(&self, window_id: &WindowId) {
+        if let Some(frame) = self.decorations.frame_of(window_id) {
+            self.connection.map_window(&frame);
+            return;
+        }
+        self.connection.disable_window_tracking(window_id);
+        self.connection.map_window(window_id);
+        self.connection.enable_window_tracking(window_id);
+    }
+
+    /// Hides a window, without us mistaking it for the window being withdrawn.
+    fn hide_window(&self, window_id: &WindowId) {
+        if let Some(frame) = self.decorations.frame_of(window_id) {
+            self.connection.unmap_window(&frame);
+            return;
+        }
+        self.connection.disable_window_tracking(window_id);
+        self.connection.unmap_window(window_id);
+        self.connection.enable_window_tracking(window_id);
+    }
+
+    /// Maps, places and decorates every window of the visible workspaces,
+    /// unmaps the rest, and gives the input focus to the focused window.
+    ///
+    /// Windows are put into frames, or taken out of them, as decorations and
+    /// the `reparent` option want. Tiled windows are only moved when the
+    /// layout changed their place.
+    pub fn perform_layout(&mut self) {
+        let mut geometry = HashMap::new();
+        let window_set = &self.window_set;
+        let border = &self.config.border;
+        let decoration = &self.config.decorations;
+        let reparent = self.config.reparent;
+        let focused = self.window_set.focused_window();
+        let color = |window_id: &WindowId| {
+            if Some(*window_id) == focused {
+                border.focused
+            } else {
+                border.unfocused
+            }
+        };
+
+        for (ix, workspace) in self.window_set.workspaces().iter().enumerate() {
+            if !self.window_set.is_visible(ix) {
+                for window_id in workspace.windows() {
+                    self.hide_window(&window_id);
+                }
+            }
+        }
+
+        for window_id in self.window_set.minimized_windows() {
+            self.hide_window(&window_id);
+        }
+        for window_id in self.window_set.hidden_scratchpads() {
+            self.hide_window(&window_id);
+        }
+
+        for (ix, screen) in self.window_set.screens().iter().enumerate() {
+            let workspace = &self.window_set.workspaces()[screen.workspace()];
+            for placement in workspace.placements(screen.viewport()) {
+                let window_id = placement.window_id;
+                if !placement.visible {
+                    self.hide_window(&window_id);
+                    continue;
+                }
+                let gaps = if placement.gaps { self.config.gaps } else { 0 };
+                let border_width = if placement.border { border.width } else { 0 };
+                let mut rectangle = placement.rectangle.shrink(gaps);
+                rectangle.width = rectangle.width.saturating_sub(2 * border_width).max(1);
+                rectangle.height = rectangle.height.saturating_sub(2 * border_width).max(1);
+                let title_bar = decorations::decorates(decoration, window_set.status(&window_id));
+                self.decorations
+                    .update(&window_id, reparent || title_bar, title_bar, decoration);
+                let outer = self.decorations.frame_of(&window_id).unwrap_or(window_id);
+                self.connection
+                    .set_window_border(&outer, border_width, color(&window_id));
+                if self.decorations.is_framed(&window_id) {
+                    self.decorations
+                        .place(&window_id, rectangle, border_width, decoration);
+                } else {
+                    if self.geometry.get(&window_id) != Some(&rectangle) {
+                        self.connection
+                            .configure_window_rectangle(&window_id, &rectangle);
+                    }
+                    geometry.insert(window_id, rectangle);
+                }
+                self.show_window(&window_id);
+            }
+            // Below the floating windows.
+            self.tab_bars.show(ix, workspace.tab_bar(screen.viewport()));
+            for window_id in workspace.floats() {
+                let title_bar = decorations::decorates(decoration, Some(Status::Floating));
+                self.decorations
+                    .update(window_id, reparent || title_bar, title_bar, decoration);
+                let rectangle = self.scratchpad_rectangle(window_id, screen);
+                if self.decorations.is_framed(window_id) {
+                    // Floating windows stay where they are, with a border.
+                    let rectangle = rectangle.or_else(|| self.decorations.rectangle_of(window_id));
+                    if let Some(rectangle) = rectangle {
+                        self.decorations
+                            .place(window_id, rectangle, border.width, decoration);
+                    }
+                } else if let Some(rectangle) = rectangle {
+                    self.connection
+                        .configure_window_rectangle(window_id, &rectangle);
+                }
+                let outer = self.decorations.frame_of(window_id).unwrap_or(*window_id);
+                self.connection
+                    .set_window_border(&outer, border.width, color(window_id));
+                self.show_window(window_id);
+                self.connection.raise_window(&outer);
+            }
+        }
+
+        match focused {
+            Some(window_id) => self.connection.focus_window(&window_id),
+            None => self.connection.focus_nothing(),
+        }
+        self.geometry = geometry;
+        self.draw_bars();
+    }
+
+    /// Draws the tab bars and the title bars.
+    fn draw_bars(&mut self) {
+        let window_set = &self.window_set;
+        self.tab_bars.draw(
+            &self.config.tab_bar,
+            window_set.focused_window(),
+            |window_id| window_set.is_urgent(window_id),
+        );
+        self.decorations.draw(
+            &self.config.decorations,
+            window_set.focused_window(),
+            |window_id| window_set.is_urgent(window_id),
+        );
+    }
+
+    /// Returns where a shown scratchpad goes: in the middle of the screen's
+    /// viewport, at the configured size.
+    fn scratchpad_rectangle(&self, window_id: &WindowId, screen: &Screen) -> Option<Rectangle> {
+        let name = self.window_set.scratchpad_name(window_id)?;
+        let config = self.config.scratchpads.iter().find(|s| s.name == name)?;
+        let viewport = screen.viewport();
+        let width = (viewport.width as f32 * config.width) as u32;
+        let height = (viewport.height as f32 * config.height) as u32;
+        let border = 2 * self.config.border.width;
+        Some(Rectangle {
+            x: viewport.x + (viewport.width - width) / 2,
+            y: viewport.y + (viewport.height - height) / 2,
+            width: width.saturating_sub(border).max(1),
+            height: height.saturating_sub(border).max(1),
+        })
+    }
+
+    fn update_ewmh_desktops(&self) {
+        let names = self.window_set.get_workspace_names();
+        self.connection
+            .update_ewmh_desktops(names, self.window_set.focused_workspace());
+    }
+
+    /// Closes the focused window.
+    pub fn close_focused_window(&mut self) {
+        if let Some(window_id) = self.window_set.focused_window() {
+            self.connection.close_window(&window_id);
+        }
+    }
+
+    /// Moves the focus to the next window of the focused workspace.
+    pub fn focus_next(&mut self) {
+        self.window_set.workspace_mut().focus_next();
+        self.perform_layout();
+    }
+
+    /// Moves the focus to the previous window of the focused workspace.
+    pub fn focus_previous(&mut self) {
+        self.window_set.workspace_mut().focus_previous();
+        self.perform_layout();
+    }
+
+    /// Swaps the focused window with the next one of the focused workspace.
+    pub fn shuffle_next(&mut self) {
+        self.window_set.workspace_mut().shuffle_next();
+        self.perform_layout();
+    }
+
+    /// Swaps the focused window with the previous one of the focused
+    /// workspace.
+    pub fn shuffle_previous(&mut self) {
+        self.window_set.workspace_mut().shuffle_previous();
+        self.perform_layout();
+    }
+
+    /// Shows the named workspace on the focused screen. If another screen
+    /// shows it, the two screens swap workspaces.
+    pub fn switch_workspace(&mut self, name: &str) -> Result<()> {
+        let workspace = self.find_or_add_workspace(name)?;
+        self.window_set.greedy_view(workspace);
+        self.update_ewmh_desktops();
+        self.perform_layout();
+        Ok(())
+    }
+
+    /// Shows the named workspace, focusing the screen showing it if there
+    /// is one.
+    pub fn view_workspace(&mut self, name: &str) -> Result<()> {
+        let workspace = self.find_or_add_workspace(name)?;
+        self.window_set.view(workspace);
+        self.update_ewmh_desktops();
+        self.perform_layout();
+        Ok(())
+    }
+
+    /// Swaps the workspaces of the focused screen and another one, by
+    /// default the next.
+    pub fn swap_screens(&mut self, other: Option<usize>) -> Result<()> {
+        let screens = self.window_set.screens().len();
+        let focused = self.window_set.focused_screen();
+        let other = other.unwrap_or((focused + 1) % screens);
+        if other >= screens {
+            bail!("No screen {}, there are {}", other, screens);
+        }
+        self.window_set.swap_screens(focused, other);
+        self.update_ewmh_desktops();
+        self.perform_layout();
+        Ok(())
+    }
+
+    /// Focuses a screen and the workspace it shows, moving the pointer to it
+    /// if `warp_pointer` is set.
+    pub fn focus_screen(&mut self, target: ScreenTarget) -> Result<()> {
+        let screen = self.find_screen(target)?;
+        self.window_set.focus_screen(screen);
+        if self.config.warp_pointer {
+            let (x, y) = self.window_set.screens()[screen].viewport().center();
+            self.connection.warp_pointer(x, y);
+        }
+        self.update_ewmh_desktops();
+        self.perform_layout();
+        Ok(())
+    }
+
+    /// Moves the pointer to the middle of the focused window, or of the
+    /// focused screen if it shows no window.
+    pub fn warp_pointer(&mut self) {
+        match self.window_set.focused_window() {
+            Some(window_id) => self.connection.warp_pointer_to_window(&window_id),
+            None => {
+                let screen = &self.window_set.screens()[self.window_set.focused_screen()];
+                let (x, y) = screen.viewport().center();
+                self.connection.warp_pointer(x, y);
+            }
+        }
+    }
+
+    /// Moves the focused window to the workspace shown on another screen.
+    pub fn move_focused_to_screen(&mut self, target: ScreenTarget) -> Result<()> {
+        let screen = self.find_screen(target)?;
+        let workspace = self.window_set.screens()[screen].workspace();
+        self.move_focused_to(workspace);
+        Ok(())
+    }
+
+    fn find_screen(&self, target: ScreenTarget) -> Result<usize> {
+        match self.window_set.find_screen(target) {
+            Some(screen) => Ok(screen),
+            None => bail!("No screen matches {:?}", target),
+        }
+    }
+
+    /// Moves the focused window to the named workspace.
+    pub fn move_focused_to_workspace(&mut self, name: &str) -> Result<()> {
+        let workspace = self.find_or_add_workspace(name)?;
+        self.move_focused_to(workspace);
+        Ok(())
+    }
+
+    fn move_focused_to(&mut self, workspace: usize) {
+        let was_sticky = self
+            .window_set
+            .focused_window()
+            .is_some_and(|w| self.window_set.is_sticky(&w));
+        if let Some(window_id) = self.window_set.move_focused_to_workspace(workspace) {
+            if was_sticky {
+                self.connection.set_window_sticky(&window_id, false, workspace);
+            }
+        }
+        self.perform_layout();
+    }
+
+    fn find_workspace(&self, name: &str) -> Result<usize> {
+        match self.window_set.workspace_index(name) {
+            Some(workspace) => Ok(workspace),
+            None => bail!("Unknown workspace '{}'", name),
+        }
+    }
+
+    /// Finds the named workspace, creating it if `dynamic_workspaces` is
+    /// set.
+    fn find_or_add_workspace(&mut self, name: &str) -> Result<usize> {
+        match self.window_set.workspace_index(name) {
+            Some(workspace) => Ok(workspace),
+            None if self.config.dynamic_workspaces => {
+                let workspace = self.window_set.add_workspace(WorkspaceConfig::new(name))?;
+                self.on_workspaces_changed();
+                Ok(workspace)
+            }
+            None => bail!("Unknown workspace '{}'", name),
+        }
+    }
+
+    /// Appends a new, empty workspace.
+    pub fn add_workspace(&mut self, name: &str) -> Result<()> {
+        if name.is_empty() {
+            bail!("Workspace names can't be empty");
+        }
+        self.window_set.add_workspace(WorkspaceConfig::new(name))?;
+        self.on_workspaces_changed();
+        Ok(())
+    }
+
+    /// Renames a workspace, or the focused one if `name` is `None`.
+    pub fn rename_workspace(&mut self, name: Option<&str>, new_name: &str) -> Result<()> {
+        if new_name.is_empty() {
+            bail!("Workspace names can't be empty");
+        }
+        let workspace = match name {
+            Some(name) => self.find_workspace(name)?,
+            None => self.window_set.focused_workspace(),
+        };
+        self.window_set.rename_workspace(workspace, new_name)?;
+        self.on_workspaces_changed();
+        Ok(())
+    }
+
+    /// Removes a workspace. If it has windows, they are moved to the
+    /// `merge_into` workspace, which must then be given.
+    pub fn remove_workspace(&mut self, name: &str, merge_into: Option<&str>) -> Result<()> {
+        let workspace = self.find_workspace(name)?;
+        let merge_into = match merge_into {
+            Some(name) => Some(self.find_workspace(name)?),
+            None => None,
+        };
+        self.window_set.remove_workspace(workspace, merge_into)?;
+        self.on_workspaces_changed();
+        self.perform_layout();
+        Ok(())
+    }
+
+    /// Moves a workspace to another position, counted from 0.
+    pub fn move_workspace(&mut self, name: &str, position: usize) -> Result<()> {
+        let workspace = self.find_workspace(name)?;
+        let count = self.window_set.workspaces().len();
+        if position >= count {
+            bail!("No position {}, there are {} workspaces", position, count);
+        }
+        self.window_set.move_workspace(workspace, position);
+        self.on_workspaces_changed();
+        Ok(())
+    }
+
+    /// Removes the workspaces that are not configured, once they are empty
+    /// and hidden and no spawned process is waiting to open a window on
+    /// them.
+    fn prune_workspaces(&mut self) {
+        let now = Instant::now();
+        let unused: Vec<usize> = self
+            .window_set
+            .workspaces()
+            .iter()
+            .enumerate()
+            .filter(|(ix, workspace)| {
+                workspace.is_empty()
+                    && workspace.minimized().is_empty()
+                    && !self.window_set.is_visible(*ix)
+                    && !self.is_configured(workspace.name())
+                    && !self
+                        .spawned
+                        .iter()
+                        .any(|s| s.workspace == workspace.name() && s.deadline > now)
+            })
+            .map(|(ix, _)| ix)
+            .collect();
+        if unused.is_empty() {
+            return;
+        }
+        for workspace in unused.into_iter().rev() {
+            info!("Removing empty workspace {}", self.window_set.workspaces()[workspace].name());
+            if let Err(error) = self.window_set.remove_workspace(workspace, None) {
+                error!("{}", error.display_chain().to_string());
+            }
+        }
+        self.on_workspaces_changed();
+    }
+
+    fn is_configured(&self, name: &str) -> bool {
+        self.config.workspaces_config.iter().any(|w| w.name == name)
+    }
+
+    fn on_workspaces_changed(&mut self) {
+        self.update_ewmh_desktops();
+        self.events.push(ipc::Event::WorkspacesChanged);
+    }
+
+    /// Switches the focused workspace to its next layout.
+    pub fn layout_next(&mut self) {
+        self.window_set.workspace_mut().next_layout();
+        self.on_layout_changed();
+        self.perform_layout();
+    }
+
+    /// Switches the focused workspace to the named layout.
+    pub fn set_layout(&mut self, name: &str) -> Result<()> {
+        if !self.window_set.workspace_mut().set_layout(name) {
+            bail!("Unknown layout '{}'", name);
+        }
+        self.on_layout_changed();
+        self.perform_layout();
+        Ok(())
+    }
+
+    fn on_layout_changed(&mut self) {
+        let workspace = self.window_set.workspace();
+        self.events.push(ipc::Event::LayoutChanged {
+            workspace: workspace.name().to_owned(),
+            layout: workspace.layout().name().to_owned(),
+        });
+    }
+
+    /// Sends a message to the layout of the focused workspace.
+    pub fn send_layout_message(&mut self, message: Message) {
+        let layout = self.window_set.workspace_mut().layout_mut();
+        if layout.handle_message(message) {
+            self.perform_layout();
+        } else {
+            debug!("Layout {} ignored {:?}", layout.name(), message);
+        }
+    }
+
+    /// Shows the focused window over the whole viewport of its screen, or
+    /// goes back to the tree.
+    pub fn toggle_maximize(&mut self) {
+        self.window_set.workspace_mut().toggle_maximized();
+        self.perform_layout();
+    }
+
+    /// Makes the focused window float, or tiles it again.
+    pub fn toggle_floating(&mut self) {
+        if let Some(window_id) = self.window_set.focused_window() {
+            let floating = self.window_set.status(&window_id) != Some(Status::Floating);
+            self.set_floating(&window_id, floating);
+        }
+    }
+
+    /// Makes a window float where it is, or tiles it again.
+    pub fn set_floating(&mut self, window_id: &WindowId, floating: bool) {
+        let was_sticky = self.window_set.is_sticky(window_id);
+        if self.window_set.set_floating(window_id, floating) {
+            info!("Window {} is floating: {}", window_id, floating);
+            self.unstick_dropped(window_id, was_sticky);
+            self.perform_layout();
+        }
+    }
+
+    /// Clears the X sticky state of a window the window set stopped
+    /// keeping sticky, as it does when the window is tiled or minimized.
+    fn unstick_dropped(&self, window_id: &WindowId, was_sticky: bool) {
+        if was_sticky && !self.window_set.is_sticky(window_id) {
+            let desktop = self.window_set.workspace_of(window_id).unwrap_or(0);
+            self.connection.set_window_sticky(window_id, false, desktop);
+        }
+    }
+
+    /// Makes the focused window sticky, or not.
+    pub fn toggle_sticky(&mut self) {
+        if let Some(window_id) = self.window_set.focused_window() {
+            let sticky = !self.window_set.is_sticky(&window_id);
+            self.set_sticky(&window_id, sticky);
+        }
+    }
+
+    /// Makes a window sticky, floating on every workspace of its screen, or
+    /// leaves it floating on the workspace it's on.
+    pub fn set_sticky(&mut self, window_id: &WindowId, sticky: bool) {
+        let was_floating = self.window_set.status(window_id) == Some(Status::Floating);
+        if !self.window_set.set_sticky(window_id, sticky) {
+            return;
+        }
+        info!("Window {} is sticky: {}", window_id, sticky);
+        let desktop = self.window_set.workspace_of(window_id).unwrap_or(0);
+        self.connection.set_window_sticky(window_id, sticky, desktop);
+        if !was_floating {
+            self.center_window(window_id);
+        }
+        self.perform_layout();
+    }
+
+    /// Minimizes the focused window.
+    pub fn minimize_focused_window(&mut self) {
+        if let Some(window_id) = self.window_set.focused_window() {
+            self.minimize_window(&window_id);
+        }
+    }
+
+    /// Minimizes a window: it is unmapped and left out of the layout until
+    /// it is restored.
+    pub fn minimize_window(&mut self, window_id: &WindowId) {
+        let was_sticky = self.window_set.is_sticky(window_id);
+        if self.window_set.minimize(window_id) {
+            info!("Minimizing window {}", window_id);
+            self.unstick_dropped(window_id, was_sticky);
+            self.connection.set_window_iconic(window_id, true);
+            self.perform_layout();
+        }
+    }
+
+    /// Restores the most recently minimized window, preferring those of the
+    /// focused workspace.
+    pub fn restore_last_window(&mut self) -> Result<()> {
+        match self.window_set.minimized_windows().first() {
+            Some(window_id) => {
+                self.restore_window(window_id);
+                Ok(())
+            }
+            None => bail!("No window is minimized"),
+        }
+    }
+
+    /// Restores a minimized window given its id, as listed by the
+    /// `get_minimized` query, or part of its title.
+    pub fn restore_named_window(&mut self, name: &str) -> Result<()> {
+        let window_id = self
+            .window_set
+            .minimized_windows()
+            .into_iter()
+            .find(|window_id| {
+                window_id.to_string() == name
+                    || self
+                        .connection
+                        .get_window_title(window_id)
+                        .is_some_and(|title| title.contains(name))
+            });
+        match window_id {
+            Some(window_id) => {
+                self.restore_window(&window_id);
+                Ok(())
+            }
+            None => bail!("No minimized window matches '{}'", name),
+        }
+    }
+
+    /// Puts a minimized window back into the tree of the focused workspace.
+    fn restore_window(&mut self, window_id: &WindowId) {
+        if self.window_set.unminimize(window_id) {
+            info!("Restoring window {}", window_id);
+            self.connection.set_window_iconic(window_id, false);
+            self.perform_layout();
+        }
+    }
+
+    /// Shows the named scratchpad on the focused workspace, or hides it if
+    /// it is shown there. Its command is started if it has no window yet.
+    pub fn toggle_scratchpad(&mut self, name: &str) -> Result<()> {
+        let spawn = match self.config.scratchpads.iter().find(|s| s.name == name) {
+            Some(config) => config.spawn.clone(),
+            None => bail!("Unknown scratchpad '{}'", name),
+        };
+        if self.window_set.toggle_scratchpad(name).is_some() {
+            self.perform_layout();
+            return Ok(());
+        }
+        let now = Instant::now();
+        let pending = self.spawned.iter().any(|spawned| {
+            spawned.scratchpad.as_deref() == Some(name) && spawned.deadline > now
+        });
+        if pending {
+            info!("Still waiting for scratchpad {} to open a window", name);
+            return Ok(());
+        }
+        match spawn {
+            Some(spawn) => self.start(&spawn, Some(name)),
+            None => bail!("Scratchpad '{}' has no window and no command", name),
+        }
+    }
+
+    /// Reads the configuration file again and applies it.
+    ///
+    /// If the file is invalid, the error is returned and the current
+    /// configuration is kept.
+    pub fn reload_config(&mut self) -> Result<()> {
+        let path = match self.config_path {
+            Some(ref path) => path.clone(),
+            None => bail!("Lanta was not started from a configuration file"),
+        };
+        let (keys, config) = config::load(&path)?;
+        info!("Reloading configuration from {}", path.display());
+
+        let root = *self.connection.root_window_id();
+        let windows = self.window_set.windows();
+        for window_id in windows.iter().chain(Some(&root)) {
+            self.connection.disable_window_key_events(window_id);
+            self.connection.enable_window_key_events(window_id, &keys);
+        }
+        self.keys = keys;
+
+        self.window_set.set_layouts(config.layouts.clone());
+        self.window_set
+            .set_workspaces_config(config.workspaces_config.clone());
+        self.config = config;
+        // Frame the windows again, with the new title bars.
+        self.decorations.release_all();
+
+        self.update_ewmh_desktops();
+        self.perform_layout();
+        Ok(())
     }
 
     fn on_map_request(&mut self, window_id: WindowId) {
@@ -105,15 +1238,48 @@ impl Lanta {
         // We only receive an unmap notify event when the window is actually
         // unmapped by its application. When our layouts unmap windows, they
         // (should) do it by disabling event tracking first.
+        self.decorations.release(window_id);
         self.unmanage_window(window_id);
     }
 
     fn on_destroy_notify(&mut self, window_id: &WindowId) {
+        self.decorations.forget(window_id);
         self.unmanage_window(window_id);
     }
 
+    /// Lays windows out on the screens now available.
+    fn on_screens_changed(&mut self) {
+        let screens = self.connection.get_screens();
+        let (added, removed) = self.window_set.set_screens(screens);
+        for screen in removed {
+            info!("Screen {} was removed", screen);
+            self.events.push(ipc::Event::ScreenRemoved { screen });
+        }
+        for screen in added {
+            info!("Screen {} was added", screen);
+            self.events.push(ipc::Event::ScreenAdded { screen });
+        }
+        self.tab_bars.retain_screens(self.window_set.screens().len());
+        self.update_ewmh_desktops();
+        self.perform_layout();
+    }
+
+    fn on_configure_request(&mut self, window_id: &WindowId) {
+        // Its place is no longer the one we gave it.
+        self.geometry.remove(window_id);
+        // A floating window may resize its frame, others stay in place.
+        let floating = self.window_set.status(window_id) == Some(Status::Floating);
+        self.decorations
+            .fit(window_id, !floating, &self.config.decorations);
+    }
+
     fn on_key_press(&mut self, key: KeyCombo) {
-        self.keys.get(&key).map(move |handler| {
+        for timer in self.named_timers.values_mut() {
+            if timer.idle.is_some() {
+                restart_timer(&mut self.reactor, timer);
+            }
+        }
+        if let Some(handler) = self.keys.get(&key) {
             if let Err(error) = (handler)(self) {
                 error!(
                     "Error running command for key command {:?}: {}",
@@ -121,10 +1287,314 @@ impl Lanta {
                     error.display_chain().to_string()
                 );
             }
-        });
+        }
+    }
+
+    fn on_ipc_request(&mut self, client: ipc::Client) {
+        let result = match client.request() {
+            Ok(ref request) if request.command == "subscribe" => {
+                match ipc::check_events(&request.args) {
+                    Ok(()) => return self.add_subscriber(client, request.args.clone()),
+                    Err(error) => Err(error),
+                }
+            }
+            Ok(ref request) => self.run_ipc_request(request),
+            Err(error) => Err(error),
+        };
+        let response = match result {
+            Ok(data) => ipc::Response::success(data),
+            Err(error) => {
+                error!("Error running IPC request: {}", error.display_chain().to_string());
+                ipc::Response::error(&error)
+            }
+        };
+        client.reply(&response);
+    }
+
+    /// Keeps the client to send it our events, starting with the current
+    /// workspace list.
+    fn add_subscriber(&mut self, client: ipc::Client, events: Vec<String>) {
+        let data = json!({ "workspaces": self.get_workspaces() });
+        match client.subscribe(events, &ipc::Response::success(Some(data))) {
+            Ok(subscriber) => self.subscribers.push(subscriber),
+            Err(error) => warn!("Could not subscribe IPC client: {}", error),
+        }
+    }
+
+    /// Sends the events caused by the last handled event to the subscribers.
+    ///
+    /// Focus changes are found by comparing with the focus when events were
+    /// last sent, the other events are queued as they happen.
+    fn send_events(&mut self) {
+        let focus = (
+            self.window_set.workspace().name().to_owned(),
+            self.window_set.focused_window(),
+        );
+        if focus.0 != self.last_focus.0 {
+            let workspace = focus.0.clone();
+            self.events.push(ipc::Event::WorkspaceFocused { workspace });
+        }
+        if focus.1 != self.last_focus.1 {
+            let title = focus
+                .1
+                .and_then(|window_id| self.connection.get_window_title(&window_id));
+            self.events.push(ipc::Event::WindowFocused {
+                window: focus.1,
+                title,
+            });
+        }
+        self.last_focus = focus;
+
+        let events = mem::take(&mut self.events);
+        if events.is_empty() || self.subscribers.is_empty() {
+            return;
+        }
+        let workspaces = self.get_workspaces();
+        for event in events {
+            let line = match event.encode(&workspaces) {
+                Ok(line) => line,
+                Err(error) => {
+                    error!("{}", error.display_chain().to_string());
+                    continue;
+                }
+            };
+            self.subscribers.retain_mut(|subscriber| {
+                if !subscriber.wants(&event) {
+                    return true;
+                }
+                match subscriber.send(&line) {
+                    Ok(()) => true,
+                    Err(error) => {
+                        info!("Dropping IPC subscriber: {}", error);
+                        false
+                    }
+                }
+            });
+        }
+    }
+
+    /// Runs a request, returning the data to send back for queries.
+    fn run_ipc_request(&mut self, request: &ipc::Request) -> Result<Option<serde_json::Value>> {
+        info!("IPC request: {:?}", request);
+        match request.command.as_str() {
+            "get_workspaces" => Ok(Some(self.get_workspaces())),
+            "get_tree" => Ok(Some(self.get_tree())),
+            "get_minimized" => Ok(Some(self.get_minimized())),
+            name => {
+                let command = cmd::from_args(name, &request.args)?;
+                (command)(self)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Describes the workspaces, in order.
+    pub fn get_workspaces(&self) -> serde_json::Value {
+        let focused = self.window_set.focused_workspace();
+        let workspaces: Vec<_> = self
+            .window_set
+            .workspaces()
+            .iter()
+            .enumerate()
+            .map(|(ix, workspace)| {
+                json!({
+                    "name": workspace.name(),
+                    "focused": ix == focused,
+                    "visible": self.window_set.is_visible(ix),
+                    "windows": workspace.windows().len(),
+                    "layout": workspace.layout().name(),
+                    "urgent": workspace.windows().iter().any(|w| self.window_set.is_urgent(w)),
+                })
+            })
+            .collect();
+        json!(workspaces)
+    }
+
+    /// Lists the minimized windows, in the order `restore_last_window` would
+    /// restore them.
+    pub fn get_minimized(&self) -> serde_json::Value {
+        let workspaces = self.window_set.workspaces();
+        let windows: Vec<_> = self
+            .window_set
+            .minimized_windows()
+            .into_iter()
+            .map(|window_id| {
+                let workspace = workspaces
+                    .iter()
+                    .find(|workspace| workspace.minimized().contains(&window_id))
+                    .map(|workspace| workspace.name());
+                json!({
+                    "window": window_id,
+                    "workspace": workspace,
+                    "class": self.connection.get_window_class(&window_id),
+                    "title": self.connection.get_window_title(&window_id),
+                })
+            })
+            .collect();
+        json!(windows)
+    }
+
+    /// Describes the whole window set: screens and their docks, workspaces
+    /// and their trees of tiled windows, every managed window, and what has
+    /// the focus.
+    pub fn get_tree(&self) -> serde_json::Value {
+        let window_set = &self.window_set;
+        let workspace_names = window_set.get_workspace_names();
+
+        let screens: Vec<_> = window_set
+            .screens()
+            .iter()
+            .map(|screen| {
+                let docks: Vec<_> = screen
+                    .docks()
+                    .iter()
+                    .map(|dock| json!({ "window": dock.window_id(), "reserved": dock.reserved() }))
+                    .collect();
+                json!({
+                    "geometry": screen.info(),
+                    "viewport": screen.viewport(),
+                    "workspace": workspace_names[screen.workspace()],
+                    "docks": docks,
+                })
+            })
+            .collect();
+
+        let workspaces: Vec<_> = window_set
+            .workspaces()
+            .iter()
+            .enumerate()
+            .map(|(ix, workspace)| {
+                json!({
+                    "name": workspace.name(),
+                    "visible": window_set.is_visible(ix),
+                    "focused": workspace.focused(),
+                    "layout": workspace.layout().name(),
+                    "tree": workspace.tree(),
+                    "floating": workspace.floats(),
+                    "minimized": workspace.minimized(),
+                    "maximized": workspace.maximized(),
+                })
+            })
+            .collect();
+
+        let windows: serde_json::Map<_, _> = window_set
+            .windows()
+            .into_iter()
+            .map(|window_id| {
+                let workspace = window_set
+                    .workspace_of(&window_id)
+                    .map(|ix| workspace_names[ix]);
+                let window = json!({
+                    "status": window_set.status(&window_id),
+                    "workspace": workspace,
+                    "class": self.connection.get_window_class(&window_id),
+                    "title": self.connection.get_window_title(&window_id),
+                    "geometry": self.connection.get_window_rectangle(&window_id),
+                    "urgent": window_set.is_urgent(&window_id),
+                    "sticky": window_set.is_sticky(&window_id),
+                });
+                (window_id.to_string(), window)
+            })
+            .collect();
+
+        json!({
+            "focused": {
+                "screen": window_set.focused_screen(),
+                "workspace": window_set.workspace().name(),
+                "window": window_set.focused_window(),
+            },
+            "screens": screens,
+            "workspaces": workspaces,
+            "windows": windows,
+        })
+    }
+
+    fn on_title_changed(&mut self, window_id: &WindowId) {
+        if self.window_set.contains(window_id) {
+            let title = self.connection.get_window_title(window_id);
+            self.events.push(ipc::Event::WindowTitle {
+                window: *window_id,
+                title,
+            });
+            self.tab_bars.forget_title(window_id);
+            self.decorations.update_title(window_id);
+            self.draw_bars();
+        }
+    }
+
+    fn on_urgency_changed(&mut self, window_id: &WindowId) {
+        if self.window_set.contains(window_id) {
+            let urgent = self.connection.is_window_urgent(window_id);
+            if self.window_set.set_urgent(window_id, urgent) {
+                self.events.push(ipc::Event::Urgent {
+                    window: *window_id,
+                    urgent,
+                });
+                self.draw_bars();
+            }
+        }
+    }
+
+    /// Focuses the screen under the pointer when it crosses onto another
+    /// one.
+    fn on_pointer_motion(&mut self, x: u32, y: u32) {
+        match self.window_set.screen_at(x, y) {
+            Some(screen) if screen != self.window_set.focused_screen() => {
+                self.window_set.focus_screen(screen);
+                self.update_ewmh_desktops();
+                self.perform_layout();
+            }
+            _ => {}
+        }
+    }
+
+    /// Focuses the window whose tab or title bar was clicked, if any, and
+    /// runs the title bar button that was clicked.
+    fn on_button_press(&mut self, window_id: &WindowId, x: u32, y: u32) {
+        if let Some(window_id) = self.tab_bars.window_at(window_id, x, y) {
+            self.window_set.focus(&window_id);
+            self.perform_layout();
+            return;
+        }
+        let (window_id, button) =
+            match self.decorations.button_at(window_id, x, y, &self.config.decorations) {
+                Some(clicked) => clicked,
+                None => return,
+            };
+        self.window_set.focus(&window_id);
+        let status = self.window_set.status(&window_id);
+        match button {
+            Some(Button::Close) => self.connection.close_window(&window_id),
+            Some(Button::Float) => {
+                self.set_floating(&window_id, status != Some(Status::Floating));
+            }
+            Some(Button::Maximize) => {
+                // Only tiled windows are maximized.
+                if status == Some(Status::Floating) {
+                    self.set_floating(&window_id, false);
+                }
+                self.toggle_maximize();
+            }
+            Some(Button::Minimize) => self.minimize_window(&window_id),
+            None => {}
+        }
+        self.perform_layout();
     }
 
     fn on_enter_notify(&mut self, window_id: &WindowId) {
-        //self.group_mut().focus(window_id);
+        let window_id = &self.decorations.window_in(window_id).unwrap_or(*window_id);
+        if self.window_set.contains(window_id) {
+            self.window_set.focus(window_id);
+            self.perform_layout();
+        }
     }
-}
\ No newline at end of file
+}
+
+/// Restarts a named timer, starting an idle timer that already ran again.
+fn restart_timer(reactor: &mut Reactor, timer: &mut NamedTimer) {
+    if !reactor.reset_timer(timer.id) {
+        if let Some((delay, command)) = &timer.idle {
+            timer.id = reactor.after(*delay, command.clone());
+        }
+    }
+}
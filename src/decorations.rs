@@ -0,0 +1,435 @@
+//! The frames windows are put into, with the title bars and their buttons
+//! that windows may be decorated with.
+//!
+//! A framed window is reparented into a frame window of ours: the title bar,
+//! if any, takes the top or the bottom of the frame and the window the rest
+//! of it. We then place, map and raise the frame instead of the window, and
+//! tell the window where it is on the root window.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::config::{Button, Decorated, DecorationConfig, TitlePosition};
+use crate::utils::Rectangle;
+use crate::window_set::Status;
+use crate::x::{Connection, Tab, WindowId};
+
+/// The frames of the framed windows.
+pub struct Decorations {
+    connection: Rc<Connection>,
+    /// The frame of each framed window.
+    frames: HashMap<WindowId, Frame>,
+}
+
+struct Frame {
+    window_id: WindowId,
+    /// Where the frame is on the root window, without its border.
+    rectangle: Rectangle,
+    border: u32,
+    /// Height of the title bar, 0 if it has none.
+    bar_height: u32,
+    title: String,
+}
+
+impl Frame {
+    fn parts(&self, config: &DecorationConfig) -> Parts {
+        parts(config, self.bar_height, self.rectangle.width, self.rectangle.height)
+    }
+
+    /// Returns where the window in the frame is on the root window.
+    fn window_rectangle(&self, config: &DecorationConfig) -> Rectangle {
+        let window = self.parts(config).window;
+        Rectangle {
+            x: self.rectangle.x + self.border + window.x,
+            y: self.rectangle.y + self.border + window.y,
+            ..window
+        }
+    }
+}
+
+/// Where the parts of a frame go, relative to it.
+#[derive(Debug, PartialEq)]
+struct Parts {
+    window: Rectangle,
+    title: Rectangle,
+    buttons: Vec<(Button, Rectangle)>,
+}
+
+/// Returns whether the configuration decorates windows with a status.
+pub fn decorates(config: &DecorationConfig, status: Option<Status>) -> bool {
+    matches!(
+        (config.windows, status),
+        (Decorated::All, Some(Status::Tiled))
+            | (Decorated::All, Some(Status::Maximized))
+            | (Decorated::All, Some(Status::Floating))
+            | (Decorated::Floating, Some(Status::Floating))
+    )
+}
+
+impl Decorations {
+    pub fn new(connection: Rc<Connection>) -> Decorations {
+        Decorations {
+            connection,
+            frames: HashMap::new(),
+        }
+    }
+
+    pub fn is_framed(&self, window_id: &WindowId) -> bool {
+        self.frames.contains_key(window_id)
+    }
+
+    /// Returns the frame of a framed window.
+    pub fn frame_of(&self, window_id: &WindowId) -> Option<WindowId> {
+        self.frames.get(window_id).map(|frame| frame.window_id)
+    }
+
+    /// Returns where the frame of a framed window is, without its border.
+    pub fn rectangle_of(&self, window_id: &WindowId) -> Option<Rectangle> {
+        self.frames.get(window_id).map(|frame| frame.rectangle)
+    }
+
+    /// Returns the window in a frame.
+    pub fn window_in(&self, frame: &WindowId) -> Option<WindowId> {
+        self.frames
+            .iter()
+            .find(|(_, f)| f.window_id == *frame)
+            .map(|(window_id, _)| *window_id)
+    }
+
+    /// Puts a window into a frame, with a title bar or not, or gives it up,
+    /// as the window should be framed or not.
+    ///
+    /// XEmbed clients are left alone, as the application embedding them
+    /// reparents them.
+    pub fn update(
+        &mut self,
+        window_id: &WindowId,
+        framed: bool,
+        title_bar: bool,
+        config: &DecorationConfig,
+    ) {
+        let bar_height = if title_bar { config.height } else { 0 };
+        match self.frames.get(window_id) {
+            Some(frame) if framed && frame.bar_height == bar_height => {}
+            Some(_) => {
+                self.release(window_id);
+                if framed {
+                    self.frame(window_id, bar_height, config);
+                }
+            }
+            None if framed && !self.connection.is_window_embedded(window_id) => {
+                self.frame(window_id, bar_height, config);
+            }
+            None => {}
+        }
+    }
+
+    /// Puts a window into a new frame, which keeps the window where it is
+    /// and adds the title bar above or below it. The frame is left unmapped.
+    fn frame(&mut self, window_id: &WindowId, bar_height: u32, config: &DecorationConfig) {
+        let window = match self.connection.get_window_rectangle(window_id) {
+            Some(rectangle) => rectangle,
+            None => return,
+        };
+        let rectangle = Rectangle {
+            y: match config.position {
+                TitlePosition::Top => window.y.saturating_sub(bar_height),
+                TitlePosition::Bottom => window.y,
+            },
+            height: window.height + bar_height,
+            ..window
+        };
+        let frame = self.connection.create_frame_window();
+        self.connection.configure_window_rectangle(&frame, &rectangle);
+        let parts = parts(config, bar_height, rectangle.width, rectangle.height);
+
+        // The window is unmapped when put into the frame.
+        self.connection.disable_window_tracking(window_id);
+        self.connection.set_window_border(window_id, 0, 0);
+        self.connection
+            .reparent_window(window_id, &frame, parts.window.x, parts.window.y);
+        self.connection.map_window(window_id);
+        self.connection.enable_window_tracking(window_id);
+
+        let title = self.connection.get_window_title(window_id);
+        self.frames.insert(
+            *window_id,
+            Frame {
+                window_id: frame,
+                rectangle,
+                border: 0,
+                bar_height,
+                title: title.unwrap_or_default(),
+            },
+        );
+    }
+
+    /// Places the frame of a framed window, with a border, along with the
+    /// window in it.
+    pub fn place(
+        &mut self,
+        window_id: &WindowId,
+        rectangle: Rectangle,
+        border: u32,
+        config: &DecorationConfig,
+    ) {
+        let frame = match self.frames.get_mut(window_id) {
+            Some(frame) => frame,
+            None => return,
+        };
+        if frame.rectangle == rectangle && frame.border == border {
+            return;
+        }
+        frame.rectangle = rectangle;
+        frame.border = border;
+        let connection = &self.connection;
+        connection.configure_window_rectangle(&frame.window_id, &rectangle);
+        connection.configure_window_rectangle(window_id, &frame.parts(config).window);
+        connection.send_configure_notify(window_id, &frame.window_rectangle(config));
+        let (top, bottom) = match config.position {
+            TitlePosition::Top => (border + frame.bar_height, border),
+            TitlePosition::Bottom => (border, border + frame.bar_height),
+        };
+        connection.set_frame_extents(window_id, [border, border, top, bottom]);
+    }
+
+    /// Fits the frame of a framed window around it after the window resized
+    /// itself, or, if `keep` is set, puts the window back in place.
+    pub fn fit(&mut self, window_id: &WindowId, keep: bool, config: &DecorationConfig) {
+        let frame = match self.frames.get(window_id) {
+            Some(frame) => frame,
+            None => return,
+        };
+        if keep {
+            let connection = &self.connection;
+            connection.configure_window_rectangle(window_id, &frame.parts(config).window);
+            // The window may not have moved, in which case the X server
+            // doesn't tell it that its request was refused.
+            connection.send_configure_notify(window_id, &frame.window_rectangle(config));
+        } else if let Some(window) = self.connection.get_window_rectangle(window_id) {
+            let rectangle = Rectangle {
+                width: window.width,
+                height: window.height + frame.bar_height,
+                ..frame.rectangle
+            };
+            let border = frame.border;
+            // Force placing it again.
+            self.frames.get_mut(window_id).unwrap().rectangle = Rectangle::default();
+            self.place(window_id, rectangle, border, config);
+        }
+    }
+
+    /// Puts a framed window back into the root window where it is, and
+    /// destroys its frame.
+    pub fn release(&mut self, window_id: &WindowId) {
+        if let Some(frame) = self.frames.remove(window_id) {
+            let window = self
+                .connection
+                .get_window_rectangle(window_id)
+                .unwrap_or_default();
+            let root = *self.connection.root_window_id();
+            self.connection.disable_window_tracking(window_id);
+            self.connection.reparent_window(
+                window_id,
+                &root,
+                frame.rectangle.x + frame.border + window.x,
+                frame.rectangle.y + frame.border + window.y,
+            );
+            self.connection.enable_window_tracking(window_id);
+            self.connection.set_frame_extents(window_id, [0; 4]);
+            self.connection.destroy_window(&frame.window_id);
+        }
+    }
+
+    /// Gives up every framed window.
+    pub fn release_all(&mut self) {
+        let windows: Vec<WindowId> = self.frames.keys().cloned().collect();
+        for window_id in windows {
+            self.release(&window_id);
+        }
+    }
+
+    /// Destroys the frame of a window that was destroyed.
+    pub fn forget(&mut self, window_id: &WindowId) {
+        if let Some(frame) = self.frames.remove(window_id) {
+            self.connection.destroy_window(&frame.window_id);
+        }
+    }
+
+    /// Reads the title of a framed window again.
+    pub fn update_title(&mut self, window_id: &WindowId) {
+        if let Some(frame) = self.frames.get_mut(window_id) {
+            let title = self.connection.get_window_title(window_id);
+            frame.title = title.unwrap_or_default();
+        }
+    }
+
+    /// Draws every title bar, colouring those of the focused window and of
+    /// the windows asking for attention.
+    pub fn draw<F>(&self, config: &DecorationConfig, focused: Option<WindowId>, is_urgent: F)
+    where
+        F: Fn(&WindowId) -> bool,
+    {
+        for (window_id, frame) in &self.frames {
+            if frame.bar_height == 0 {
+                continue;
+            }
+            let background = if Some(*window_id) == focused {
+                config.focused
+            } else if is_urgent(window_id) {
+                config.urgent
+            } else {
+                config.unfocused
+            };
+            let parts = frame.parts(config);
+            let title = Tab {
+                rectangle: parts.title,
+                title: &frame.title,
+                background,
+                foreground: config.text,
+                centered: false,
+            };
+            let buttons = parts.buttons.iter().map(|(button, rectangle)| Tab {
+                rectangle: *rectangle,
+                title: label(*button),
+                background,
+                foreground: config.text,
+                centered: true,
+            });
+            let tabs: Vec<Tab> = Some(title).into_iter().chain(buttons).collect();
+            self.connection
+                .draw_tabs(&frame.window_id, &config.font, &tabs);
+        }
+    }
+
+    /// Returns the window in a frame, and the button at a position in the
+    /// frame, if any.
+    pub fn button_at(
+        &self,
+        frame: &WindowId,
+        x: u32,
+        y: u32,
+        config: &DecorationConfig,
+    ) -> Option<(WindowId, Option<Button>)> {
+        let window_id = self.window_in(frame)?;
+        let button = self.frames[&window_id]
+            .parts(config)
+            .buttons
+            .into_iter()
+            .find(|(_, rectangle)| rectangle.contains(x, y))
+            .map(|(button, _)| button);
+        Some((window_id, button))
+    }
+}
+
+fn label(button: Button) -> &'static str {
+    match button {
+        Button::Close => "x",
+        Button::Float => "^",
+        Button::Maximize => "+",
+        Button::Minimize => "_",
+    }
+}
+
+/// Splits a frame between the window, the title and the buttons, which are
+/// as wide as the title bar is high.
+fn parts(config: &DecorationConfig, bar_height: u32, width: u32, height: u32) -> Parts {
+    // Always leave some room for the window.
+    let size = cmp::min(bar_height, height.saturating_sub(1));
+    let (bar_y, window_y) = match config.position {
+        TitlePosition::Top => (0, size),
+        TitlePosition::Bottom => (height - size, 0),
+    };
+    let button = |x| Rectangle {
+        x,
+        y: bar_y,
+        width: size,
+        height: size,
+    };
+
+    let mut buttons = Vec::new();
+    let mut left = 0;
+    for b in &config.left_buttons {
+        buttons.push((*b, button(left)));
+        left += size;
+    }
+    let right_width = size * config.right_buttons.len() as u32;
+    let mut right = cmp::max(left, width.saturating_sub(right_width));
+    let title = Rectangle {
+        x: left,
+        y: bar_y,
+        width: right - left,
+        height: size,
+    };
+    for b in &config.right_buttons {
+        buttons.push((*b, button(right)));
+        right += size;
+    }
+
+    Parts {
+        window: Rectangle {
+            x: 0,
+            y: window_y,
+            width,
+            height: height - size,
+        },
+        title,
+        buttons,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decorates, parts};
+    use crate::config::{Button, Decorated, DecorationConfig, TitlePosition};
+    use crate::layout::test::rectangle;
+    use crate::window_set::Status;
+
+    #[test]
+    fn test_parts() {
+        let mut config = DecorationConfig {
+            left_buttons: vec![Button::Close],
+            right_buttons: vec![Button::Minimize, Button::Maximize],
+            ..DecorationConfig::default()
+        };
+        let frame = parts(&config, 20, 100, 70);
+        assert_eq!(frame.window, rectangle(0, 20, 100, 50));
+        assert_eq!(frame.title, rectangle(20, 0, 40, 20));
+        assert_eq!(
+            frame.buttons,
+            vec![
+                (Button::Close, rectangle(0, 0, 20, 20)),
+                (Button::Minimize, rectangle(60, 0, 20, 20)),
+                (Button::Maximize, rectangle(80, 0, 20, 20)),
+            ]
+        );
+
+        config.position = TitlePosition::Bottom;
+        let frame = parts(&config, 20, 100, 70);
+        assert_eq!(frame.window, rectangle(0, 0, 100, 50));
+        assert_eq!(frame.title, rectangle(20, 50, 40, 20));
+
+        // The buttons don't overlap in a narrow frame.
+        let frame = parts(&config, 20, 30, 70);
+        assert_eq!(frame.title.width, 0);
+        assert_eq!(frame.buttons[1].1, rectangle(20, 50, 20, 20));
+
+        // Without a title bar, the window takes the whole frame.
+        let frame = parts(&config, 0, 100, 70);
+        assert_eq!(frame.window, rectangle(0, 0, 100, 70));
+    }
+
+    #[test]
+    fn test_decorates() {
+        let mut config = DecorationConfig::default();
+        assert!(!decorates(&config, Some(Status::Floating)));
+        config.windows = Decorated::Floating;
+        assert!(decorates(&config, Some(Status::Floating)));
+        assert!(!decorates(&config, Some(Status::Tiled)));
+        config.windows = Decorated::All;
+        assert!(decorates(&config, Some(Status::Maximized)));
+        assert!(!decorates(&config, Some(Status::Minimized)));
+        assert!(!decorates(&config, None));
+    }
+}
@@ -1,9 +1,14 @@
+// error_chain 0.12.0 implements the deprecated `Error::cause()` and
+// `Error::description()`.
+#![allow(deprecated)]
+
 use error_chain::*;
 
 error_chain!{
     foreign_links {
         Io(::std::io::Error);
         Log(::log::SetLoggerError);
+        Toml(::toml::de::Error);
         Xcb(::xcb::GenericError);
         Xdg(::xdg::BaseDirectoriesError);
     }
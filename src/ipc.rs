@@ -0,0 +1,578 @@
+//! Control of Lanta from other processes, over a Unix domain socket.
+//!
+//! Each connection carries a single request, written as one line, and gets a
+//! single JSON response line back. A request is either a textual command, as
+//! used in the configuration file (e.g. `switch_workspace web`), or a JSON
+//! object such as `{"command": "switch_workspace", "args": ["web"]}`.
+//!
+//! A `subscribe` request keeps the connection open instead: after the
+//! response, which carries the current workspace list, the client receives one
+//! JSON line per event (see `Event`), until it disconnects. The names of the
+//! events wanted can be given as arguments, otherwise all are sent.
+//!
+//! The `lanta-msg` binary is a command-line client for this socket.
+
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use error_chain::bail;
+use log::{info, log, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::*;
+use crate::x::WindowId;
+
+/// Environment variable holding the socket path. Lanta sets it for the
+/// processes it spawns.
+pub const SOCKET_ENV: &str = "LANTA_SOCKET";
+
+/// Requests longer than this are rejected.
+const MAX_REQUEST_LENGTH: usize = 64 * 1024;
+
+/// How long we wait for a client to accept our response.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Returns the path of the control socket.
+///
+/// This is `$LANTA_SOCKET` if set, otherwise a socket named after the X
+/// display under `$XDG_RUNTIME_DIR/lanta/`.
+pub fn socket_path() -> Result<PathBuf> {
+    if let Some(path) = env::var_os(SOCKET_ENV) {
+        return Ok(PathBuf::from(path));
+    }
+    let display = env::var("DISPLAY").unwrap_or_default();
+    let xdg_dirs = xdg::BaseDirectories::with_prefix("lanta")?;
+    let path = xdg_dirs
+        .place_runtime_file(format!("ipc{}.sock", display))
+        .chain_err(|| "Could not create the runtime directory for the IPC socket")?;
+    Ok(path)
+}
+
+/// A request received from a client.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Request {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl Request {
+    /// Parses a request line, either textual or JSON.
+    pub fn parse(line: &str) -> Result<Request> {
+        let line = line.trim();
+        if line.starts_with('{') {
+            return serde_json::from_str(line).chain_err(|| "Invalid JSON request");
+        }
+        let mut words = line.split_whitespace().map(str::to_owned);
+        match words.next() {
+            Some(command) => Ok(Request {
+                command,
+                args: words.collect(),
+            }),
+            None => bail!("Empty request"),
+        }
+    }
+}
+
+/// The response sent back for each request.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Response {
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl Response {
+    pub fn success(data: Option<serde_json::Value>) -> Response {
+        Response {
+            success: true,
+            error: None,
+            data,
+        }
+    }
+
+    pub fn error(error: &Error) -> Response {
+        let causes: Vec<String> = error.iter().map(|e| e.to_string()).collect();
+        Response {
+            success: false,
+            error: Some(causes.join(": ")),
+            data: None,
+        }
+    }
+}
+
+/// Names of the events clients can subscribe to, as found in the `event`
+/// field of each event line.
+///
+/// There is no `mode_changed` event: Lanta has no modes, such as i3's binding
+/// modes, that could change.
+pub const EVENTS: &[&str] = &[
+    "workspace_focused",
+    "window_focused",
+    "window_title",
+    "window_closed",
+    "urgent",
+    "workspaces_changed",
+    "layout_changed",
+    "screen_added",
+    "screen_removed",
+];
+
+/// Something that happened in the WM, sent to subscribed clients.
+///
+/// On the wire, each event also has a `workspaces` field with the same list
+/// as the `get_workspaces` query returns.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    WorkspaceFocused { workspace: String },
+    WindowFocused {
+        window: Option<WindowId>,
+        title: Option<String>,
+    },
+    WindowTitle {
+        window: WindowId,
+        title: Option<String>,
+    },
+    WindowClosed { window: WindowId },
+    Urgent { window: WindowId, urgent: bool },
+    /// Workspaces were added, renamed, removed or reordered.
+    WorkspacesChanged,
+    /// The focused workspace switched to another layout.
+    LayoutChanged { workspace: String, layout: String },
+    /// A monitor was plugged in, named after its RandR output.
+    ScreenAdded { screen: String },
+    /// A monitor was unplugged or disabled.
+    ScreenRemoved { screen: String },
+}
+
+impl Event {
+    /// Returns the name of the event, one of `EVENTS`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Event::WorkspaceFocused { .. } => "workspace_focused",
+            Event::WindowFocused { .. } => "window_focused",
+            Event::WindowTitle { .. } => "window_title",
+            Event::WindowClosed { .. } => "window_closed",
+            Event::Urgent { .. } => "urgent",
+            Event::WorkspacesChanged => "workspaces_changed",
+            Event::LayoutChanged { .. } => "layout_changed",
+            Event::ScreenAdded { .. } => "screen_added",
+            Event::ScreenRemoved { .. } => "screen_removed",
+        }
+    }
+
+    /// Encodes the event as a line, along with the workspace list.
+    pub fn encode(&self, workspaces: &serde_json::Value) -> Result<Vec<u8>> {
+        #[derive(Serialize)]
+        struct Message<'a> {
+            #[serde(flatten)]
+            event: &'a Event,
+            workspaces: &'a serde_json::Value,
+        }
+
+        let message = Message {
+            event: self,
+            workspaces,
+        };
+        let mut line = serde_json::to_vec(&message).chain_err(|| "Could not encode event")?;
+        line.push(b'\n');
+        Ok(line)
+    }
+}
+
+/// Checks the event names given to a `subscribe` request.
+pub fn check_events<S: AsRef<str>>(events: &[S]) -> Result<()> {
+    for event in events {
+        if event.as_ref() == "mode_changed" {
+            bail!("Lanta has no modes, so there is no 'mode_changed' event");
+        }
+        if !EVENTS.contains(&event.as_ref()) {
+            bail!(
+                "Unknown event '{}', expected one of: {}",
+                event.as_ref(),
+                EVENTS.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The listening end of the control socket.
+///
+/// The socket file is removed when the server is dropped.
+pub struct Server {
+    listener: UnixListener,
+    path: PathBuf,
+    clients: Vec<Client>,
+}
+
+impl Server {
+    /// Listens on the socket at `path`, replacing any stale socket left by a
+    /// previous instance.
+    pub fn bind(path: &Path) -> Result<Server> {
+        if UnixStream::connect(path).is_ok() {
+            bail!("Another instance is already listening on {}", path.display());
+        }
+        match fs::remove_file(path) {
+            Err(ref error) if error.kind() != ErrorKind::NotFound => {
+                warn!("Could not remove stale socket {}: {}", path.display(), error);
+            }
+            _ => {}
+        }
+
+        let listener = UnixListener::bind(path)
+            .chain_err(|| format!("Could not listen on {}", path.display()))?;
+        listener.set_nonblocking(true)?;
+        info!("Listening for IPC requests on {}", path.display());
+
+        Ok(Server {
+            listener,
+            path: path.to_owned(),
+            clients: vec![],
+        })
+    }
+
+    /// Returns the path of the socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the file descriptors to wait on: the listener followed by the
+    /// connected clients, in the order expected by `Server::ready()`.
+    pub fn fds(&self) -> Vec<RawFd> {
+        Some(self.listener.as_raw_fd())
+            .into_iter()
+            .chain(self.clients.iter().map(|c| c.stream.as_raw_fd()))
+            .collect()
+    }
+
+    /// Handles the file descriptors from `Server::fds()` that became
+    /// readable, returning the clients whose request is complete.
+    pub fn ready(&mut self, readable: &[bool]) -> Vec<Client> {
+        let mut complete = vec![];
+        let mut clients = vec![];
+        for (client, readable) in self.clients.drain(..).zip(&readable[1..]) {
+            if !readable {
+                clients.push(client);
+                continue;
+            }
+            let mut client = client;
+            match client.read() {
+                Ok(true) => complete.push(client),
+                Ok(false) => clients.push(client),
+                Err(error) => warn!("Dropping IPC client: {}", error),
+            }
+        }
+        self.clients = clients;
+
+        if readable[0] {
+            self.accept();
+        }
+        complete
+    }
+
+    fn accept(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => match stream.set_nonblocking(true) {
+                    Ok(()) => self.clients.push(Client {
+                        stream,
+                        buffer: vec![],
+                    }),
+                    Err(error) => warn!("Could not set up IPC client: {}", error),
+                },
+                Err(ref error) if error.kind() == ErrorKind::WouldBlock => return,
+                Err(error) => {
+                    warn!("Could not accept IPC client: {}", error);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A connected client, which has sent a request once `Server::ready()`
+/// returns it.
+pub struct Client {
+    stream: UnixStream,
+    buffer: Vec<u8>,
+}
+
+impl Client {
+    /// Reads what is available, returning whether the request line is
+    /// complete.
+    fn read(&mut self) -> io::Result<bool> {
+        let mut chunk = [0; 4096];
+        loop {
+            match self.stream.read(&mut chunk) {
+                // The client may close its end without a trailing newline.
+                Ok(0) if self.buffer.is_empty() => {
+                    return Err(io::Error::new(ErrorKind::UnexpectedEof, "closed"));
+                }
+                Ok(0) => return Ok(true),
+                Ok(read) => {
+                    self.buffer.extend_from_slice(&chunk[..read]);
+                    if self.buffer.contains(&b'\n') {
+                        return Ok(true);
+                    }
+                    if self.buffer.len() > MAX_REQUEST_LENGTH {
+                        return Err(io::Error::new(ErrorKind::InvalidData, "request too long"));
+                    }
+                }
+                Err(ref error) if error.kind() == ErrorKind::WouldBlock => return Ok(false),
+                Err(ref error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Returns the request sent by the client.
+    pub fn request(&self) -> Result<Request> {
+        let line = self.buffer.split(|b| *b == b'\n').next().unwrap_or(&[]);
+        let line = std::str::from_utf8(line).chain_err(|| "Request is not valid UTF-8")?;
+        Request::parse(line)
+    }
+
+    /// Sends the response and closes the connection.
+    pub fn reply(self, response: &Response) {
+        if let Err(error) = self.send(response) {
+            warn!("Could not reply to IPC client: {}", error);
+        }
+    }
+
+    /// Sends the response and keeps the connection open to send events.
+    ///
+    /// `events` are the names of the events wanted, or empty for all of them.
+    pub fn subscribe(self, events: Vec<String>, response: &Response) -> Result<Subscriber> {
+        self.send(response)?;
+        // From now on, a client too slow to read its events is dropped rather
+        // than blocking us.
+        self.stream.set_nonblocking(true)?;
+        Ok(Subscriber {
+            stream: self.stream,
+            events,
+        })
+    }
+
+    fn send(&self, response: &Response) -> Result<()> {
+        self.stream.set_nonblocking(false)?;
+        self.stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let mut line = serde_json::to_vec(response).chain_err(|| "Could not encode response")?;
+        line.push(b'\n');
+        (&self.stream).write_all(&line)?;
+        Ok(())
+    }
+}
+
+/// A client that subscribed to events.
+pub struct Subscriber {
+    stream: UnixStream,
+    events: Vec<String>,
+}
+
+impl Subscriber {
+    /// Returns whether the client wants to receive the event.
+    pub fn wants(&self, event: &Event) -> bool {
+        self.events.is_empty() || self.events.iter().any(|e| e == event.name())
+    }
+
+    /// Sends an encoded event. On error, the subscriber should be dropped.
+    pub fn send(&mut self, line: &[u8]) -> io::Result<()> {
+        self.stream.write_all(line)
+    }
+}
+
+/// Sends a request to the running instance and waits for its response.
+pub fn send_request(path: &Path, command: &str, args: &[String]) -> Result<Response> {
+    let mut reader = connect(path, command, args)?;
+    read_response(&mut reader)
+}
+
+/// Subscribes to events, returning the response to the subscription and the
+/// events, as JSON values, as they arrive.
+pub fn subscribe(
+    path: &Path,
+    events: &[String],
+) -> Result<(Response, impl Iterator<Item = Result<serde_json::Value>>)> {
+    let mut reader = connect(path, "subscribe", events)?;
+    let response = read_response(&mut reader)?;
+    let events = reader.lines().map(|line| {
+        let line = line?;
+        serde_json::from_str(&line).chain_err(|| "Invalid event")
+    });
+    Ok((response, events))
+}
+
+fn connect(path: &Path, command: &str, args: &[String]) -> Result<BufReader<UnixStream>> {
+    let request = serde_json::json!({ "command": command, "args": args });
+    let mut stream = UnixStream::connect(path)
+        .chain_err(|| format!("Could not connect to {}", path.display()))?;
+    let mut line = request.to_string();
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    Ok(BufReader::new(stream))
+}
+
+fn read_response(reader: &mut BufReader<UnixStream>) -> Result<Response> {
+    let mut response = String::new();
+    reader.read_line(&mut response)?;
+    serde_json::from_str(&response).chain_err(|| "Invalid response")
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::process;
+    use std::thread;
+
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    use super::{check_events, send_request, Event, Request, Response, Server, EVENTS};
+
+    fn request(command: &str, args: &[&str]) -> Request {
+        Request {
+            command: command.to_owned(),
+            args: args.iter().map(|a| (*a).to_owned()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_text_request() {
+        assert_eq!(Request::parse("focus_next\n").unwrap(), request("focus_next", &[]));
+        assert_eq!(
+            Request::parse("spawn urxvt -e htop").unwrap(),
+            request("spawn", &["urxvt", "-e", "htop"])
+        );
+        assert!(Request::parse("  \n").is_err());
+    }
+
+    #[test]
+    fn test_parse_json_request() {
+        assert_eq!(
+            Request::parse(r#"{"command": "spawn", "args": ["sh", "-c", "a b"]}"#).unwrap(),
+            request("spawn", &["sh", "-c", "a b"])
+        );
+        assert_eq!(
+            Request::parse(r#"{"command": "quit"}"#).unwrap(),
+            request("quit", &[])
+        );
+        assert!(Request::parse(r#"{"args": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_encode_event() {
+        let event = Event::WorkspaceFocused {
+            workspace: "web".to_owned(),
+        };
+        let workspaces = serde_json::json!([{ "name": "web" }]);
+        let line = event.encode(&workspaces).unwrap();
+        assert_eq!(line.last(), Some(&b'\n'));
+        let value: serde_json::Value = serde_json::from_slice(&line).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "event": "workspace_focused",
+                "workspace": "web",
+                "workspaces": [{ "name": "web" }],
+            })
+        );
+        assert_eq!(value["event"], event.name());
+
+        let event = Event::LayoutChanged {
+            workspace: "web".to_owned(),
+            layout: "tall".to_owned(),
+        };
+        let value: serde_json::Value =
+            serde_json::from_slice(&event.encode(&workspaces).unwrap()).unwrap();
+        assert_eq!(value["event"], "layout_changed");
+        assert_eq!(value["layout"], "tall");
+        assert!(EVENTS.contains(&event.name()));
+    }
+
+    #[test]
+    fn test_check_events() {
+        assert!(check_events::<&str>(&[]).is_ok());
+        assert!(check_events(&["urgent", "window_closed"]).is_ok());
+        assert!(check_events(&["urgent", "nope"]).is_err());
+        assert_eq!(
+            check_events(&["mode_changed"]).err().unwrap().to_string(),
+            "Lanta has no modes, so there is no 'mode_changed' event"
+        );
+    }
+
+    #[test]
+    fn test_server_round_trip() {
+        let path = env::temp_dir().join(format!("lanta-test-{}.sock", process::id()));
+        let mut server = Server::bind(&path).unwrap();
+
+        let client_path = path.clone();
+        let client = thread::spawn(move || {
+            send_request(&client_path, "switch_workspace", &["web".to_owned()]).unwrap()
+        });
+
+        let mut complete = vec![];
+        while complete.is_empty() {
+            let readable = vec![true; server.fds().len()];
+            complete = server.ready(&readable);
+        }
+        let request = complete[0].request().unwrap();
+        assert_eq!(request.command, "switch_workspace");
+        assert_eq!(request.args, vec!["web".to_owned()]);
+        complete.remove(0).reply(&Response::success(None));
+
+        assert!(client.join().unwrap().success);
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_subscriber_receives_events() {
+        let path = env::temp_dir().join(format!("lanta-test-sub-{}.sock", process::id()));
+        let mut server = Server::bind(&path).unwrap();
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream.write_all(b"subscribe urgent\n").unwrap();
+
+        let mut complete = vec![];
+        while complete.is_empty() {
+            let readable = vec![true; server.fds().len()];
+            complete = server.ready(&readable);
+        }
+        let request = complete[0].request().unwrap();
+        assert_eq!(request.command, "subscribe");
+        let mut subscriber = complete
+            .remove(0)
+            .subscribe(request.args, &Response::success(None))
+            .unwrap();
+
+        let focused = Event::WorkspaceFocused {
+            workspace: "web".to_owned(),
+        };
+        assert!(!subscriber.wants(&focused));
+        let workspaces = serde_json::json!([]);
+        subscriber.send(&focused.encode(&workspaces).unwrap()).unwrap();
+
+        let mut lines = BufReader::new(stream).lines();
+        let response: Response = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert!(response.success);
+        let event: serde_json::Value =
+            serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(event["event"], "workspace_focused");
+    }
+}
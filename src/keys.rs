@@ -1,9 +1,13 @@
 use std::collections::HashMap;
+use std::ffi::CString;
 use std::os::raw::c_uint;
+use std::str::FromStr;
 
-use xcb;
+use error_chain::bail;
+use x11::xlib;
 
 use crate::cmd::Command;
+use crate::errors::*;
 
 
 /// Represents a modifier key.
@@ -42,10 +46,45 @@ impl ModKey {
     }
 }
 
+impl FromStr for ModKey {
+    type Err = Error;
+
+    /// Parses a modifier name, as used in textual key combinations.
+    ///
+    /// Accepts the X names (`Shift`, `Lock`, `Control`, `Mod1`...`Mod5`), the
+    /// common aliases `Ctrl`, `Alt` and `Super`, and the short forms `S`
+    /// (Shift), `C` (Control), `A` (Mod1) and `M` (Mod4).
+    fn from_str(s: &str) -> Result<ModKey> {
+        let mod_key = match s {
+            "S" | "Shift" => ModKey::Shift,
+            "Lock" => ModKey::Lock,
+            "C" | "Ctrl" | "Control" => ModKey::Control,
+            "A" | "Alt" | "Mod1" => ModKey::Mod1,
+            "Mod2" => ModKey::Mod2,
+            "Mod3" => ModKey::Mod3,
+            "M" | "Super" | "Mod4" => ModKey::Mod4,
+            "Mod5" => ModKey::Mod5,
+            _ => bail!("Unknown modifier '{}'", s),
+        };
+        Ok(mod_key)
+    }
+}
+
 
 /// A single key, of the same type as the `x11::keysym` constants.
 type Key = c_uint;
 
+/// Looks up a keysym by its name (e.g. `Return`, `j`, `XF86AudioMute`),
+/// using Xlib's keysym name table.
+fn keysym_from_name(name: &str) -> Result<Key> {
+    let c_name = CString::new(name).chain_err(|| format!("Invalid key name '{}'", name))?;
+    let keysym = unsafe { xlib::XStringToKeysym(c_name.as_ptr()) };
+    if keysym == 0 {
+        bail!("Unknown key '{}'", name);
+    }
+    Ok(keysym as Key)
+}
+
 
 /// A combination of zero or more mods and a key.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -59,8 +98,36 @@ impl KeyCombo {
         let mask = mods.iter().fold(0, |mask, mod_key| mask | mod_key.mask());
         KeyCombo {
             mod_mask: mask,
-            keysym: keysym,
+            keysym,
+        }
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = Error;
+
+    /// Parses a textual key combination, such as `Mod4-Shift-Return` or
+    /// `M-S-j`.
+    ///
+    /// The combination is a list of modifiers followed by a key name, all
+    /// separated by `-`. See `ModKey::from_str()` for the accepted modifier
+    /// names. The key name is anything understood by `XStringToKeysym()`.
+    fn from_str(s: &str) -> Result<KeyCombo> {
+        let mut parts: Vec<&str> = s.split('-').collect();
+        let key = parts.pop().unwrap_or("");
+        if key.is_empty() {
+            bail!("Missing key in key combination '{}'", s);
         }
+
+        let mods = parts
+            .into_iter()
+            .map(ModKey::from_str)
+            .collect::<Result<Vec<_>>>()
+            .chain_err(|| format!("Invalid key combination '{}'", s))?;
+        let keysym =
+            keysym_from_name(key).chain_err(|| format!("Invalid key combination '{}'", s))?;
+
+        Ok(KeyCombo::new(&mods, keysym))
     }
 }
 
@@ -88,3 +155,61 @@ impl From<Vec<(Vec<ModKey>, Key, Command)>> for KeyHandlers {
         KeyHandlers { hashmap }
     }
 }
+
+
+impl From<Vec<(KeyCombo, Command)>> for KeyHandlers {
+    fn from(handlers: Vec<(KeyCombo, Command)>) -> KeyHandlers {
+        KeyHandlers {
+            hashmap: handlers.into_iter().collect(),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use x11::keysym::*;
+
+    use super::{KeyCombo, ModKey};
+
+    #[test]
+    fn test_parse_mod_key() {
+        assert_eq!("Shift".parse::<ModKey>().unwrap(), ModKey::Shift);
+        assert_eq!("S".parse::<ModKey>().unwrap(), ModKey::Shift);
+        assert_eq!("Ctrl".parse::<ModKey>().unwrap(), ModKey::Control);
+        assert_eq!("Alt".parse::<ModKey>().unwrap(), ModKey::Mod1);
+        assert_eq!("M".parse::<ModKey>().unwrap(), ModKey::Mod4);
+        assert_eq!("Mod4".parse::<ModKey>().unwrap(), ModKey::Mod4);
+        assert!("Hyper".parse::<ModKey>().is_err());
+    }
+
+    #[test]
+    fn test_parse_key_combo() {
+        let combo: KeyCombo = "Mod4-Shift-Return".parse().unwrap();
+        assert_eq!(combo.mod_mask, xcb::MOD_MASK_4 | xcb::MOD_MASK_SHIFT);
+        assert_eq!(combo.keysym, XK_Return);
+
+        let combo: KeyCombo = "M-S-j".parse().unwrap();
+        assert_eq!(combo.mod_mask, xcb::MOD_MASK_4 | xcb::MOD_MASK_SHIFT);
+        assert_eq!(combo.keysym, XK_j);
+    }
+
+    #[test]
+    fn test_parse_key_combo_without_mods() {
+        let combo: KeyCombo = "XF86AudioMute".parse().unwrap();
+        assert_eq!(combo.mod_mask, 0);
+        assert_eq!(combo.keysym, XF86XK_AudioMute);
+    }
+
+    #[test]
+    fn test_parse_key_combo_errors() {
+        let error = "Hyper-j".parse::<KeyCombo>().unwrap_err();
+        assert!(error.iter().any(|e| e.to_string() == "Unknown modifier 'Hyper'"));
+
+        let error = "M-NotAKey".parse::<KeyCombo>().unwrap_err();
+        assert!(error.iter().any(|e| e.to_string() == "Unknown key 'NotAKey'"));
+
+        assert!("M-".parse::<KeyCombo>().is_err());
+        assert!("".parse::<KeyCombo>().is_err());
+    }
+}
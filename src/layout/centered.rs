@@ -0,0 +1,125 @@
+use std::cmp;
+
+use crate::layout::{self, Layout, Message, Placement, Rectangle, Tiled};
+
+/// Puts the master windows in the middle of the viewport, between two
+/// stacks sharing the other windows, like XMonad's `ThreeColMid`. With a
+/// single window left over, the master area goes on the left.
+///
+/// Like `MasterStackLayout`, it handles `Message::IncMaster`,
+/// `Message::Expand` and `Message::Shrink`.
+#[derive(Clone)]
+pub struct CenteredMasterLayout {
+    name: String,
+    padding: u32,
+    masters: usize,
+    /// Fraction of the viewport's width taken by the master area.
+    ratio: f32,
+}
+
+impl CenteredMasterLayout {
+    pub fn new<S: Into<String>>(name: S, padding: u32) -> CenteredMasterLayout {
+        CenteredMasterLayout {
+            name: name.into(),
+            padding,
+            masters: 1,
+            ratio: 0.5,
+        }
+    }
+
+    /// Sets the number of master windows.
+    pub fn masters(mut self, masters: usize) -> Self {
+        self.masters = masters;
+        self
+    }
+
+    /// Sets the fraction of the viewport's width taken by the master area.
+    pub fn ratio(mut self, ratio: f32) -> Self {
+        self.ratio = ratio.clamp(0.1, 0.9);
+        self
+    }
+}
+
+impl Layout for CenteredMasterLayout {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn layout(&self, viewport: Rectangle, tiled: &Tiled) -> Vec<Placement> {
+        let windows = tiled.windows();
+        let viewport = viewport.shrink(self.padding);
+        let masters = cmp::min(self.masters, windows.len());
+        let stacked = windows.len() - masters;
+
+        let rectangles = if masters == 0 || stacked == 0 {
+            layout::rows(viewport, windows.len(), self.padding)
+        } else if stacked == 1 {
+            let (master, stack) = layout::split_columns(viewport, self.ratio, self.padding);
+            let mut rectangles = layout::rows(master, masters, self.padding);
+            rectangles.push(stack);
+            rectangles
+        } else {
+            // The stacks share the width left by the master area.
+            let side = (1.0 - self.ratio) / 2.0;
+            let (left, rest) = layout::split_columns(viewport, side, self.padding);
+            let (master, right) =
+                layout::split_columns(rest, self.ratio / (1.0 - side), self.padding);
+            let mut rectangles = layout::rows(master, masters, self.padding);
+            // Stacked windows go right, then left, then right...
+            let right = layout::rows(right, stacked.div_ceil(2), self.padding);
+            let left = layout::rows(left, stacked / 2, self.padding);
+            for i in 0..stacked {
+                rectangles.push(if i % 2 == 0 { right[i / 2] } else { left[i / 2] });
+            }
+            rectangles
+        };
+        windows
+            .iter()
+            .zip(rectangles)
+            .map(|(window_id, rectangle)| Placement::shown(*window_id, rectangle))
+            .collect()
+    }
+
+    fn handle_message(&mut self, message: Message) -> bool {
+        match message {
+            Message::IncMaster(delta) => {
+                let masters = cmp::max(self.masters as i64 + i64::from(delta), 0) as usize;
+                let changed = masters != self.masters;
+                self.masters = masters;
+                changed
+            }
+            Message::Expand => {
+                self.ratio = (self.ratio + 0.05).min(0.9);
+                true
+            }
+            Message::Shrink => {
+                self.ratio = (self.ratio - 0.05).max(0.1);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CenteredMasterLayout;
+    use crate::layout::test::{rectangle, rectangles};
+
+    #[test]
+    fn test_centered_master() {
+        let layout = CenteredMasterLayout::new("centered", 0);
+        let viewport = rectangle(0, 0, 100, 60);
+        let placed = |count| rectangles(&layout, viewport, count);
+        assert_eq!(placed(1), vec![viewport]);
+        assert_eq!(placed(2), vec![rectangle(0, 0, 50, 60), rectangle(50, 0, 50, 60)]);
+        assert_eq!(
+            placed(4),
+            vec![
+                rectangle(25, 0, 50, 60),
+                rectangle(75, 0, 25, 30),
+                rectangle(0, 0, 25, 60),
+                rectangle(75, 30, 25, 30),
+            ]
+        );
+    }
+}
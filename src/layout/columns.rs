@@ -0,0 +1,33 @@
+use crate::layout::{self, Layout, Placement, Rectangle, Tiled};
+
+/// Puts the windows side by side, at equal widths.
+#[derive(Clone)]
+pub struct ColumnsLayout {
+    name: String,
+    padding: u32,
+}
+
+impl ColumnsLayout {
+    pub fn new<S: Into<String>>(name: S, padding: u32) -> ColumnsLayout {
+        ColumnsLayout {
+            name: name.into(),
+            padding,
+        }
+    }
+}
+
+impl Layout for ColumnsLayout {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn layout(&self, viewport: Rectangle, tiled: &Tiled) -> Vec<Placement> {
+        let windows = tiled.windows();
+        let columns = layout::columns(viewport.shrink(self.padding), windows.len(), self.padding);
+        windows
+            .iter()
+            .zip(columns)
+            .map(|(window_id, rectangle)| Placement::shown(*window_id, rectangle))
+            .collect()
+    }
+}
@@ -0,0 +1,88 @@
+use crate::layout::{self, Layout, Placement, Rectangle, Tiled};
+
+/// Arranges the windows in a grid with about as many rows as columns. The
+/// last row takes the windows left over, which get wider.
+#[derive(Clone)]
+pub struct GridLayout {
+    name: String,
+    padding: u32,
+}
+
+impl GridLayout {
+    pub fn new<S: Into<String>>(name: S, padding: u32) -> GridLayout {
+        GridLayout {
+            name: name.into(),
+            padding,
+        }
+    }
+}
+
+/// Returns the number of columns of a grid of `count` windows.
+fn columns_for(count: usize) -> usize {
+    let mut columns = 0;
+    while columns * columns < count {
+        columns += 1;
+    }
+    columns
+}
+
+impl Layout for GridLayout {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn layout(&self, viewport: Rectangle, tiled: &Tiled) -> Vec<Placement> {
+        let windows = tiled.windows();
+        let columns = columns_for(windows.len());
+        if columns == 0 {
+            return vec![];
+        }
+        let rows = windows.len().div_ceil(columns);
+        let rectangles = layout::rows(viewport.shrink(self.padding), rows, self.padding)
+            .into_iter()
+            .enumerate()
+            .flat_map(|(row, area)| {
+                let count = (windows.len() - row * columns).min(columns);
+                layout::columns(area, count, self.padding)
+            });
+        windows
+            .iter()
+            .zip(rectangles)
+            .map(|(window_id, rectangle)| Placement::shown(*window_id, rectangle))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::GridLayout;
+    use crate::layout::test::{rectangle, rectangles, shown, tree};
+    use crate::layout::{Layout, Tiled};
+
+    #[test]
+    fn test_grid() {
+        let layout = GridLayout::new("grid", 0);
+        let viewport = rectangle(0, 0, 90, 60);
+        let placed = |count| rectangles(&layout, viewport, count);
+        assert!(placed(0).is_empty());
+        assert_eq!(placed(1), vec![viewport]);
+        assert_eq!(
+            placed(3),
+            vec![
+                rectangle(0, 0, 45, 30),
+                rectangle(45, 0, 45, 30),
+                rectangle(0, 30, 90, 30),
+            ]
+        );
+        let rectangles = placed(7);
+        assert_eq!(rectangles.len(), 7);
+        assert_eq!(rectangles[0], rectangle(0, 0, 30, 20));
+        assert_eq!(rectangles[6], rectangle(0, 40, 90, 20));
+
+        let padded = GridLayout::new("grid", 5);
+        let tree = tree(2);
+        let rectangles = shown(padded.layout(viewport, &Tiled::new(&tree, None)));
+        assert_eq!(rectangles[0].1, rectangle(5, 5, 38, 50));
+        assert_eq!(rectangles[1].1, rectangle(48, 5, 37, 50));
+    }
+}
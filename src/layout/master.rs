@@ -0,0 +1,231 @@
+use std::cmp;
+
+use crate::layout::{self, Layout, Message, Placement, Rectangle, Tiled};
+
+/// Side of the viewport taken by the master area.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MasterSide {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// Puts the first windows in a master area on one side of the viewport and
+/// stacks the rest beside it, like XMonad's `Tall`.
+///
+/// The number of master windows changes with `Message::IncMaster` and the
+/// size of the master area with `Message::Expand` and `Message::Shrink`.
+#[derive(Clone)]
+pub struct MasterStackLayout {
+    name: String,
+    padding: u32,
+    side: MasterSide,
+    masters: usize,
+    /// Fraction of the viewport taken by the master area.
+    ratio: f32,
+    /// How much `Expand` and `Shrink` change the ratio.
+    delta: f32,
+}
+
+impl MasterStackLayout {
+    /// Creates a layout with one master window on the left, taking half the
+    /// viewport.
+    pub fn new<S: Into<String>>(name: S, padding: u32) -> MasterStackLayout {
+        MasterStackLayout {
+            name: name.into(),
+            padding,
+            side: MasterSide::Left,
+            masters: 1,
+            ratio: 0.5,
+            delta: 0.05,
+        }
+    }
+
+    /// Puts the master area on another side: `Top` makes a wide layout and
+    /// `Right` mirrors the default one.
+    pub fn side(mut self, side: MasterSide) -> Self {
+        self.side = side;
+        self
+    }
+
+    /// Sets the number of master windows.
+    pub fn masters(mut self, masters: usize) -> Self {
+        self.masters = masters;
+        self
+    }
+
+    /// Sets the fraction of the viewport taken by the master area, and how
+    /// much resizing it changes it.
+    pub fn ratio(mut self, ratio: f32, delta: f32) -> Self {
+        self.ratio = clamp_ratio(ratio);
+        self.delta = delta;
+        self
+    }
+
+    /// Changes the ratio, within bounds. Returns whether that changed it.
+    fn set_ratio(&mut self, ratio: f32) -> bool {
+        let ratio = clamp_ratio(ratio);
+        let changed = ratio != self.ratio;
+        self.ratio = ratio;
+        changed
+    }
+}
+
+/// Keeps both areas visible.
+fn clamp_ratio(ratio: f32) -> f32 {
+    ratio.clamp(0.05, 0.95)
+}
+
+impl Layout for MasterStackLayout {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn layout(&self, viewport: Rectangle, tiled: &Tiled) -> Vec<Placement> {
+        let windows = tiled.windows();
+        if windows.is_empty() {
+            return vec![];
+        }
+        let viewport = viewport.shrink(self.padding);
+        let masters = cmp::min(self.masters, windows.len());
+        let stacked = windows.len() - masters;
+
+        let horizontal = self.side == MasterSide::Left || self.side == MasterSide::Right;
+        let (master_area, stack_area) = if masters == 0 || stacked == 0 {
+            (viewport, viewport)
+        } else {
+            // The first area is on the left or at the top.
+            let ratio = match self.side {
+                MasterSide::Left | MasterSide::Top => self.ratio,
+                MasterSide::Right | MasterSide::Bottom => 1.0 - self.ratio,
+            };
+            let areas = if horizontal {
+                layout::split_columns(viewport, ratio, self.padding)
+            } else {
+                layout::split_rows(viewport, ratio, self.padding)
+            };
+            match self.side {
+                MasterSide::Left | MasterSide::Top => areas,
+                MasterSide::Right | MasterSide::Bottom => (areas.1, areas.0),
+            }
+        };
+
+        // Windows are stacked across the way the areas are split.
+        let split = |area, count| {
+            if horizontal {
+                layout::rows(area, count, self.padding)
+            } else {
+                layout::columns(area, count, self.padding)
+            }
+        };
+        let rectangles = split(master_area, masters)
+            .into_iter()
+            .chain(split(stack_area, stacked));
+        windows
+            .iter()
+            .zip(rectangles)
+            .map(|(window_id, rectangle)| Placement::shown(*window_id, rectangle))
+            .collect()
+    }
+
+    fn handle_message(&mut self, message: Message) -> bool {
+        match message {
+            Message::IncMaster(delta) => {
+                let masters = cmp::max(self.masters as i64 + i64::from(delta), 0) as usize;
+                let changed = masters != self.masters;
+                self.masters = masters;
+                changed
+            }
+            Message::Expand => self.set_ratio(self.ratio + self.delta),
+            Message::Shrink => self.set_ratio(self.ratio - self.delta),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MasterSide, MasterStackLayout};
+    use crate::layout::test::{rectangle, shown, tree};
+    use crate::layout::{Layout, Message, Tiled};
+
+    #[test]
+    fn test_tall() {
+        let mut layout = MasterStackLayout::new("tall", 0);
+        let viewport = rectangle(0, 0, 100, 60);
+        let tree = tree(3);
+        let tiled = Tiled::new(&tree, None);
+        let w = tiled.windows();
+        assert_eq!(
+            shown(layout.layout(viewport, &tiled)),
+            vec![
+                (w[0], rectangle(0, 0, 50, 60)),
+                (w[1], rectangle(50, 0, 50, 30)),
+                (w[2], rectangle(50, 30, 50, 30)),
+            ]
+        );
+
+        assert!(layout.handle_message(Message::IncMaster(1)));
+        assert!(layout.handle_message(Message::Expand));
+        assert_eq!(
+            shown(layout.layout(viewport, &tiled)),
+            vec![
+                (w[0], rectangle(0, 0, 55, 30)),
+                (w[1], rectangle(0, 30, 55, 30)),
+                (w[2], rectangle(55, 0, 45, 60)),
+            ]
+        );
+
+        // Without stacked windows, the masters take the whole viewport.
+        assert!(layout.handle_message(Message::IncMaster(5)));
+        let rectangles = shown(layout.layout(viewport, &tiled));
+        assert_eq!(rectangles[2], (w[2], rectangle(0, 40, 100, 20)));
+        assert!(layout.handle_message(Message::IncMaster(-10)));
+        assert!(!layout.handle_message(Message::IncMaster(-1)));
+        let rectangles = shown(layout.layout(viewport, &tiled));
+        assert_eq!(rectangles[0], (w[0], rectangle(0, 0, 100, 20)));
+    }
+
+    #[test]
+    fn test_ratio_bounds() {
+        let mut layout = MasterStackLayout::new("tall", 0).ratio(0.9, 0.1);
+        assert!(layout.handle_message(Message::Expand));
+        assert!(!layout.handle_message(Message::Expand));
+        assert!(layout.handle_message(Message::Shrink));
+
+        let mut layout = MasterStackLayout::new("tall", 0).ratio(0.1, 0.1);
+        assert!(layout.handle_message(Message::Shrink));
+        assert!(!layout.handle_message(Message::Shrink));
+        assert!(layout.handle_message(Message::Expand));
+    }
+
+    #[test]
+    fn test_sides_and_padding() {
+        let viewport = rectangle(0, 0, 100, 100);
+        let tree = tree(3);
+        let tiled = Tiled::new(&tree, None);
+        let w = tiled.windows();
+
+        let wide = MasterStackLayout::new("wide", 0).side(MasterSide::Top);
+        assert_eq!(
+            shown(wide.layout(viewport, &tiled)),
+            vec![
+                (w[0], rectangle(0, 0, 100, 50)),
+                (w[1], rectangle(0, 50, 50, 50)),
+                (w[2], rectangle(50, 50, 50, 50)),
+            ]
+        );
+
+        let mirrored = MasterStackLayout::new("mirrored", 10)
+            .side(MasterSide::Right)
+            .ratio(0.6, 0.1);
+        assert_eq!(
+            shown(mirrored.layout(viewport, &tiled)),
+            vec![
+                (w[0], rectangle(48, 10, 42, 80)),
+                (w[1], rectangle(10, 10, 28, 35)),
+                (w[2], rectangle(10, 55, 28, 35)),
+            ]
+        );
+    }
+}
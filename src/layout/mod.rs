@@ -1,43 +1,355 @@
+//! Layouts place the tiled windows of a workspace.
+//!
+//! Each workspace has its own copy of the configured layouts and uses one of
+//! them at a time. Switching layouts keeps the workspace's windows, and its
+//! tree, as they are.
+//!
+//! Layouts only compute where windows go: Lanta's core then maps, unmaps and
+//! moves the windows, so layouts are easy to test.
+//!
+//! Modifiers such as `Mirror` wrap another layout and change where it puts
+//! the windows.
+
+use std::cmp;
 use std::fmt;
 
-use crate::stack::Stack;
-use crate::Viewport;
-use crate::x::{Connection, WindowId};
+use crate::bsplayout::BSPLayout;
 
+mod centered;
+mod columns;
+mod grid;
+mod master;
+mod modifiers;
+mod spiral;
 mod stack;
+mod tabbed;
 mod tiled;
+mod tree;
 
+pub use self::centered::CenteredMasterLayout;
+pub use self::columns::ColumnsLayout;
+pub use self::grid::GridLayout;
+pub use self::master::{MasterSide, MasterStackLayout};
+pub use self::modifiers::{LimitWindows, Mirror, Reflect, SmartBorders, SmartGaps};
+pub use self::spiral::SpiralLayout;
 pub use self::stack::StackLayout;
+pub use self::tabbed::TabbedLayout;
 pub use self::tiled::TiledLayout;
+pub use self::tree::TreeLayout;
+pub use crate::utils::Rectangle;
+pub use crate::x::WindowId;
+
+/// Returns the stock layouts, in the order `layout_next` goes through them.
+pub fn defaults() -> Vec<Box<dyn Layout>> {
+    vec![
+        Box::new(TreeLayout::new("bsp")),
+        Box::new(MasterStackLayout::new("tall", 0)),
+        Box::new(MasterStackLayout::new("wide", 0).side(MasterSide::Top)),
+        Box::new(StackLayout::new("monocle", 0)),
+        Box::new(TiledLayout::new("tiled", 0)),
+        Box::new(GridLayout::new("grid", 0)),
+        Box::new(ColumnsLayout::new("columns", 0)),
+        Box::new(SpiralLayout::new("spiral", 0)),
+        Box::new(CenteredMasterLayout::new("centered", 0)),
+        Box::new(TabbedLayout::new("tabbed", 0)),
+        Box::new(TabbedLayout::new("stacked", 0).stacked()),
+    ]
+}
+
+/// A request for the layout in use, sent by commands such as `inc_master`.
+/// Layouts ignore the messages they don't understand.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Message {
+    /// Adds to the number of windows in the master area, which can be
+    /// negative.
+    IncMaster(i32),
+    /// Makes the master area bigger.
+    Expand,
+    /// Makes the master area smaller.
+    Shrink,
+}
+
+/// Where a layout puts a window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placement {
+    pub window_id: WindowId,
+    pub rectangle: Rectangle,
+    /// Cleared for windows hidden by the layout, e.g. behind a monocle's
+    /// window. Their rectangle is then meaningless.
+    pub visible: bool,
+    /// Cleared to leave out the configured gaps around the window.
+    pub gaps: bool,
+    /// Cleared to leave out the window's border.
+    pub border: bool,
+}
+
+impl Placement {
+    pub fn shown(window_id: WindowId, rectangle: Rectangle) -> Self {
+        Placement {
+            window_id,
+            rectangle,
+            visible: true,
+            gaps: true,
+            border: true,
+        }
+    }
+
+    pub fn hidden(window_id: WindowId) -> Self {
+        Placement {
+            window_id,
+            rectangle: Rectangle::default(),
+            visible: false,
+            gaps: true,
+            border: true,
+        }
+    }
+}
+
+/// A bar of tabs, one for each window, that Lanta draws for a layout such
+/// as `TabbedLayout`. Clicking a tab focuses its window.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TabBar {
+    pub rectangle: Rectangle,
+    /// Each window's tab, inside the bar.
+    pub tabs: Vec<(WindowId, Rectangle)>,
+}
+
+/// The tiled windows of a workspace, as given to `Layout::layout()`.
+pub struct Tiled<'a> {
+    windows: Vec<WindowId>,
+    focused: Option<WindowId>,
+    tree: &'a BSPLayout<WindowId>,
+}
+
+impl<'a> Tiled<'a> {
+    pub(crate) fn new(tree: &'a BSPLayout<WindowId>, focused: Option<WindowId>) -> Self {
+        Tiled {
+            windows: tree.leaves().into_iter().map(|(_, w)| *w).collect(),
+            focused: focused.filter(|w| tree.find_leaf(w).is_some()),
+            tree,
+        }
+    }
+
+    /// Returns the windows in order.
+    pub fn windows(&self) -> &[WindowId] {
+        &self.windows
+    }
+
+    /// Returns the focused window, if it's one of the tiled windows.
+    pub fn focused(&self) -> Option<WindowId> {
+        self.focused
+    }
 
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    pub(crate) fn tree(&self) -> &BSPLayout<WindowId> {
+        self.tree
+    }
+}
 
 pub trait LayoutClone {
-    fn clone_box(&self) -> Box<Layout>;
+    fn clone_box(&self) -> Box<dyn Layout>;
 }
 
 impl<T> LayoutClone for T
 where
     T: 'static + Layout + Clone,
 {
-    fn clone_box(&self) -> Box<Layout> {
+    fn clone_box(&self) -> Box<dyn Layout> {
         Box::new(self.clone())
     }
 }
 
-
 pub trait Layout: LayoutClone {
     fn name(&self) -> &str;
-    fn layout(&self, connection: &Connection, viewport: &Viewport, stack: &Stack<WindowId>);
+
+    /// Returns where each window goes in the viewport. Windows left out are
+    /// hidden.
+    fn layout(&self, viewport: Rectangle, tiled: &Tiled) -> Vec<Placement>;
+
+    /// Returns the tab bar to draw with the windows, if any.
+    fn tab_bar(&self, _viewport: Rectangle, _tiled: &Tiled) -> Option<TabBar> {
+        None
+    }
+
+    /// Handles a message, returning whether the layout changed. Layouts
+    /// that keep state, like the number of master windows, change it here.
+    fn handle_message(&mut self, _message: Message) -> bool {
+        false
+    }
 }
 
-impl Clone for Box<Layout> {
-    fn clone(&self) -> Box<Layout> {
+impl Clone for Box<dyn Layout> {
+    fn clone(&self) -> Box<dyn Layout> {
         self.clone_box()
     }
 }
 
-impl fmt::Debug for Layout {
+impl fmt::Debug for dyn Layout {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Layout {{ \"{}\" }}", self.name())
     }
 }
+
+/// Splits a rectangle into `count` columns, `gap` apart, sharing out the
+/// width as evenly as possible. The gaps shrink when there is not enough
+/// space for them.
+pub fn columns(rectangle: Rectangle, count: usize, gap: u32) -> Vec<Rectangle> {
+    split(rectangle.width, count, gap)
+        .into_iter()
+        .map(|(offset, width)| Rectangle {
+            x: rectangle.x + offset,
+            width,
+            ..rectangle
+        })
+        .collect()
+}
+
+/// Splits a rectangle into `count` rows, like `columns()`.
+pub fn rows(rectangle: Rectangle, count: usize, gap: u32) -> Vec<Rectangle> {
+    split(rectangle.height, count, gap)
+        .into_iter()
+        .map(|(offset, height)| Rectangle {
+            y: rectangle.y + offset,
+            height,
+            ..rectangle
+        })
+        .collect()
+}
+
+/// Splits a rectangle into two columns, `gap` apart, the first one taking
+/// `ratio` of the width.
+pub fn split_columns(rectangle: Rectangle, ratio: f32, gap: u32) -> (Rectangle, Rectangle) {
+    let (first, second) = split_in_two(rectangle.width, ratio, gap);
+    (
+        Rectangle {
+            width: first.1,
+            ..rectangle
+        },
+        Rectangle {
+            x: rectangle.x + second.0,
+            width: second.1,
+            ..rectangle
+        },
+    )
+}
+
+/// Splits a rectangle into two rows, like `split_columns()`.
+pub fn split_rows(rectangle: Rectangle, ratio: f32, gap: u32) -> (Rectangle, Rectangle) {
+    let (first, second) = split_in_two(rectangle.height, ratio, gap);
+    (
+        Rectangle {
+            height: first.1,
+            ..rectangle
+        },
+        Rectangle {
+            y: rectangle.y + second.0,
+            height: second.1,
+            ..rectangle
+        },
+    )
+}
+
+/// Splits a length in two pieces at a ratio, like `split()`.
+fn split_in_two(length: u32, ratio: f32, gap: u32) -> ((u32, u32), (u32, u32)) {
+    if length < 2 {
+        return ((0, 1), (0, 1));
+    }
+    let gap = cmp::min(gap, length - 2);
+    let usable = length - gap;
+    let first = cmp::min(cmp::max((usable as f32 * ratio).round() as u32, 1), usable - 1);
+    ((0, first), (first + gap, usable - first))
+}
+
+/// Splits a length into `count` pieces, returning the offset and length of
+/// each. Pieces are at least 1 long, even when that makes them overlap.
+fn split(length: u32, count: usize, gap: u32) -> Vec<(u32, u32)> {
+    if count == 0 {
+        return vec![];
+    }
+    let count = count as u32;
+    let gap = cmp::min(gap, length.saturating_sub(count) / cmp::max(count - 1, 1));
+    let usable = length - gap * (count - 1);
+    let mut offset = 0;
+    (0..count)
+        .map(|i| {
+            let piece = usable / count + if i < usable % count { 1 } else { 0 };
+            let start = cmp::min(offset, length.saturating_sub(1));
+            offset += piece + gap;
+            (start, cmp::max(piece, 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::{columns, rows, BSPLayout, Layout, Placement, Rectangle, Tiled, WindowId};
+
+    pub fn window(id: u32) -> WindowId {
+        serde_json::from_value(serde_json::json!(id)).unwrap()
+    }
+
+    /// Returns a tree holding windows 1 to `count`.
+    pub fn tree(count: u32) -> BSPLayout<WindowId> {
+        let mut tree = BSPLayout::empty();
+        for id in 1..=count {
+            tree.push(window(id));
+        }
+        tree
+    }
+
+    /// Returns the shown windows with their rectangle.
+    pub fn shown(placements: Vec<Placement>) -> Vec<(WindowId, Rectangle)> {
+        placements
+            .into_iter()
+            .filter(|placement| placement.visible)
+            .map(|placement| (placement.window_id, placement.rectangle))
+            .collect()
+    }
+
+    /// Returns where a layout puts windows 1 to `count`, in order.
+    pub fn rectangles(layout: &dyn Layout, viewport: Rectangle, count: u32) -> Vec<Rectangle> {
+        let tree = tree(count);
+        layout
+            .layout(viewport, &Tiled::new(&tree, None))
+            .into_iter()
+            .map(|placement| placement.rectangle)
+            .collect()
+    }
+
+    pub fn rectangle(x: u32, y: u32, width: u32, height: u32) -> Rectangle {
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn test_split() {
+        let viewport = rectangle(10, 20, 100, 50);
+        assert_eq!(
+            columns(viewport, 3, 5),
+            vec![
+                rectangle(10, 20, 30, 50),
+                rectangle(45, 20, 30, 50),
+                rectangle(80, 20, 30, 50),
+            ]
+        );
+        assert_eq!(
+            rows(viewport, 3, 0),
+            vec![
+                rectangle(10, 20, 100, 17),
+                rectangle(10, 37, 100, 17),
+                rectangle(10, 54, 100, 16),
+            ]
+        );
+        assert!(columns(viewport, 0, 5).is_empty());
+        // Gaps give way to the windows, which are never empty.
+        assert_eq!(rows(rectangle(0, 0, 10, 4), 2, 10)[1], rectangle(0, 3, 10, 1));
+        assert_eq!(rows(rectangle(0, 0, 10, 2), 3, 1)[2], rectangle(0, 1, 10, 1));
+    }
+}
@@ -0,0 +1,374 @@
+use crate::bsplayout::BSPLayout;
+use crate::layout::{Layout, Message, Placement, Rectangle, TabBar, Tiled, WindowId};
+
+/// Rotates another layout by 90 degrees, like XMonad's `Mirror`: a tall
+/// layout becomes a wide one.
+///
+/// Modifiers take the name of the layout they wrap, so give that one the
+/// name the modified layout should go by.
+#[derive(Clone)]
+pub struct Mirror {
+    layout: Box<dyn Layout>,
+}
+
+impl Mirror {
+    pub fn new(layout: Box<dyn Layout>) -> Mirror {
+        Mirror { layout }
+    }
+}
+
+/// Swaps the horizontal and vertical coordinates.
+fn transpose(rectangle: Rectangle) -> Rectangle {
+    Rectangle {
+        x: rectangle.y,
+        y: rectangle.x,
+        width: rectangle.height,
+        height: rectangle.width,
+    }
+}
+
+impl Layout for Mirror {
+    fn name(&self) -> &str {
+        self.layout.name()
+    }
+
+    fn layout(&self, viewport: Rectangle, tiled: &Tiled) -> Vec<Placement> {
+        map_shown(self.layout.layout(transpose(viewport), tiled), transpose)
+    }
+
+    fn tab_bar(&self, viewport: Rectangle, tiled: &Tiled) -> Option<TabBar> {
+        map_tab_bar(self.layout.tab_bar(transpose(viewport), tiled), transpose)
+    }
+
+    fn handle_message(&mut self, message: Message) -> bool {
+        self.layout.handle_message(message)
+    }
+}
+
+/// Flips another layout from left to right, or from top to bottom.
+#[derive(Clone)]
+pub struct Reflect {
+    layout: Box<dyn Layout>,
+    horizontal: bool,
+}
+
+impl Reflect {
+    /// Flips the layout from left to right.
+    pub fn horizontal(layout: Box<dyn Layout>) -> Reflect {
+        Reflect {
+            layout,
+            horizontal: true,
+        }
+    }
+
+    /// Flips the layout from top to bottom.
+    pub fn vertical(layout: Box<dyn Layout>) -> Reflect {
+        Reflect {
+            layout,
+            horizontal: false,
+        }
+    }
+
+    fn reflect(&self, viewport: Rectangle, rectangle: Rectangle) -> Rectangle {
+        if self.horizontal {
+            let end = 2 * viewport.x + viewport.width;
+            Rectangle {
+                x: end.saturating_sub(rectangle.x + rectangle.width),
+                ..rectangle
+            }
+        } else {
+            let end = 2 * viewport.y + viewport.height;
+            Rectangle {
+                y: end.saturating_sub(rectangle.y + rectangle.height),
+                ..rectangle
+            }
+        }
+    }
+}
+
+impl Layout for Reflect {
+    fn name(&self) -> &str {
+        self.layout.name()
+    }
+
+    fn layout(&self, viewport: Rectangle, tiled: &Tiled) -> Vec<Placement> {
+        let placements = self.layout.layout(viewport, tiled);
+        map_shown(placements, |rectangle| self.reflect(viewport, rectangle))
+    }
+
+    fn tab_bar(&self, viewport: Rectangle, tiled: &Tiled) -> Option<TabBar> {
+        let tab_bar = self.layout.tab_bar(viewport, tiled);
+        map_tab_bar(tab_bar, |rectangle| self.reflect(viewport, rectangle))
+    }
+
+    fn handle_message(&mut self, message: Message) -> bool {
+        self.layout.handle_message(message)
+    }
+}
+
+/// Leaves out the gaps when another layout shows a single window.
+#[derive(Clone)]
+pub struct SmartGaps {
+    layout: Box<dyn Layout>,
+}
+
+impl SmartGaps {
+    pub fn new(layout: Box<dyn Layout>) -> SmartGaps {
+        SmartGaps { layout }
+    }
+}
+
+impl Layout for SmartGaps {
+    fn name(&self) -> &str {
+        self.layout.name()
+    }
+
+    fn layout(&self, viewport: Rectangle, tiled: &Tiled) -> Vec<Placement> {
+        let mut placements = self.layout.layout(viewport, tiled);
+        if shown_count(&placements) == 1 {
+            for placement in &mut placements {
+                placement.gaps = false;
+            }
+        }
+        placements
+    }
+
+    fn tab_bar(&self, viewport: Rectangle, tiled: &Tiled) -> Option<TabBar> {
+        self.layout.tab_bar(viewport, tiled)
+    }
+
+    fn handle_message(&mut self, message: Message) -> bool {
+        self.layout.handle_message(message)
+    }
+}
+
+/// Leaves out the border when another layout shows a single window.
+#[derive(Clone)]
+pub struct SmartBorders {
+    layout: Box<dyn Layout>,
+}
+
+impl SmartBorders {
+    pub fn new(layout: Box<dyn Layout>) -> SmartBorders {
+        SmartBorders { layout }
+    }
+}
+
+impl Layout for SmartBorders {
+    fn name(&self) -> &str {
+        self.layout.name()
+    }
+
+    fn layout(&self, viewport: Rectangle, tiled: &Tiled) -> Vec<Placement> {
+        let mut placements = self.layout.layout(viewport, tiled);
+        if shown_count(&placements) == 1 {
+            for placement in &mut placements {
+                placement.border = false;
+            }
+        }
+        placements
+    }
+
+    fn tab_bar(&self, viewport: Rectangle, tiled: &Tiled) -> Option<TabBar> {
+        self.layout.tab_bar(viewport, tiled)
+    }
+
+    fn handle_message(&mut self, message: Message) -> bool {
+        self.layout.handle_message(message)
+    }
+}
+
+/// Gives another layout at most `limit` windows and hides the rest.
+///
+/// The first windows are shown, but the focused window always is: it takes
+/// the place of the last one shown.
+#[derive(Clone)]
+pub struct LimitWindows {
+    layout: Box<dyn Layout>,
+    limit: usize,
+}
+
+impl LimitWindows {
+    pub fn new(layout: Box<dyn Layout>, limit: usize) -> LimitWindows {
+        LimitWindows { layout, limit }
+    }
+
+    /// Returns the tree without the hidden windows, which layouts following
+    /// the tree get, or `None` if no window is hidden.
+    fn limit(&self, tiled: &Tiled) -> Option<BSPLayout<WindowId>> {
+        let windows = tiled.windows();
+        if windows.len() <= self.limit {
+            return None;
+        }
+        let mut shown = windows[..self.limit].to_vec();
+        if let Some(focused) = tiled.focused() {
+            if !shown.contains(&focused) {
+                if let Some(last) = shown.last_mut() {
+                    *last = focused;
+                }
+            }
+        }
+
+        let mut tree = tiled.tree().clone();
+        for window_id in windows.iter().filter(|w| !shown.contains(w)) {
+            if let Some(ix) = tree.find_leaf(window_id) {
+                tree.remove(ix);
+            }
+        }
+        Some(tree)
+    }
+}
+
+impl Layout for LimitWindows {
+    fn name(&self) -> &str {
+        self.layout.name()
+    }
+
+    fn layout(&self, viewport: Rectangle, tiled: &Tiled) -> Vec<Placement> {
+        match self.limit(tiled) {
+            Some(tree) => {
+                let limited = Tiled::new(&tree, tiled.focused());
+                self.layout.layout(viewport, &limited)
+            }
+            None => self.layout.layout(viewport, tiled),
+        }
+    }
+
+    fn tab_bar(&self, viewport: Rectangle, tiled: &Tiled) -> Option<TabBar> {
+        match self.limit(tiled) {
+            Some(tree) => {
+                let limited = Tiled::new(&tree, tiled.focused());
+                self.layout.tab_bar(viewport, &limited)
+            }
+            None => self.layout.tab_bar(viewport, tiled),
+        }
+    }
+
+    fn handle_message(&mut self, message: Message) -> bool {
+        self.layout.handle_message(message)
+    }
+}
+
+fn shown_count(placements: &[Placement]) -> usize {
+    placements.iter().filter(|placement| placement.visible).count()
+}
+
+/// Changes the rectangle of the shown windows.
+fn map_shown<F>(placements: Vec<Placement>, f: F) -> Vec<Placement>
+where
+    F: Fn(Rectangle) -> Rectangle,
+{
+    placements
+        .into_iter()
+        .map(|placement| {
+            if placement.visible {
+                Placement {
+                    rectangle: f(placement.rectangle),
+                    ..placement
+                }
+            } else {
+                placement
+            }
+        })
+        .collect()
+}
+
+/// Changes the rectangles of a tab bar and of its tabs.
+fn map_tab_bar<F>(tab_bar: Option<TabBar>, f: F) -> Option<TabBar>
+where
+    F: Fn(Rectangle) -> Rectangle,
+{
+    tab_bar.map(|tab_bar| TabBar {
+        rectangle: f(tab_bar.rectangle),
+        tabs: tab_bar
+            .tabs
+            .into_iter()
+            .map(|(window_id, rectangle)| (window_id, f(rectangle)))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{LimitWindows, Mirror, Reflect, SmartBorders, SmartGaps};
+    use crate::layout::test::{rectangle, shown, tree};
+    use crate::layout::{Layout, MasterStackLayout, Message, StackLayout, Tiled};
+
+    fn tall() -> Box<dyn Layout> {
+        Box::new(MasterStackLayout::new("tall", 0))
+    }
+
+    #[test]
+    fn test_mirror_and_reflect() {
+        let tree = tree(3);
+        let tiled = Tiled::new(&tree, None);
+        let w = tiled.windows();
+
+        let mut mirror = Mirror::new(tall());
+        assert_eq!(mirror.name(), "tall");
+        assert_eq!(
+            shown(mirror.layout(rectangle(0, 0, 100, 60), &tiled)),
+            vec![
+                (w[0], rectangle(0, 0, 100, 30)),
+                (w[1], rectangle(0, 30, 50, 30)),
+                (w[2], rectangle(50, 30, 50, 30)),
+            ]
+        );
+        assert!(mirror.handle_message(Message::IncMaster(1)));
+
+        let reflect = Reflect::horizontal(tall());
+        assert_eq!(
+            shown(reflect.layout(rectangle(10, 0, 100, 60), &tiled)),
+            vec![
+                (w[0], rectangle(60, 0, 50, 60)),
+                (w[1], rectangle(10, 0, 50, 30)),
+                (w[2], rectangle(10, 30, 50, 30)),
+            ]
+        );
+        let reflect = Reflect::vertical(tall());
+        assert_eq!(
+            shown(reflect.layout(rectangle(0, 10, 100, 60), &tiled))[1],
+            (w[1], rectangle(50, 40, 50, 30))
+        );
+    }
+
+    #[test]
+    fn test_smart_gaps_and_borders() {
+        let layout = SmartBorders::new(Box::new(SmartGaps::new(tall())));
+        let viewport = rectangle(0, 0, 100, 60);
+        let single = tree(1);
+        let placements = layout.layout(viewport, &Tiled::new(&single, None));
+        assert!(placements.iter().all(|p| !p.gaps && !p.border));
+        let several = tree(2);
+        let placements = layout.layout(viewport, &Tiled::new(&several, None));
+        assert!(placements.iter().all(|p| p.gaps && p.border));
+
+        // A monocle shows a single window, however many there are.
+        let monocle = SmartBorders::new(Box::new(StackLayout::new("monocle", 0)));
+        let placements = monocle.layout(viewport, &Tiled::new(&several, None));
+        assert!(placements.iter().all(|p| !p.border));
+    }
+
+    #[test]
+    fn test_limit_windows() {
+        let layout = LimitWindows::new(tall(), 2);
+        let viewport = rectangle(0, 0, 100, 60);
+        let tree = tree(4);
+        let tiled = Tiled::new(&tree, None);
+        let w = tiled.windows().to_vec();
+        assert_eq!(
+            shown(layout.layout(viewport, &tiled)),
+            vec![
+                (w[0], rectangle(0, 0, 50, 60)),
+                (w[1], rectangle(50, 0, 50, 60)),
+            ]
+        );
+
+        let tiled = Tiled::new(&tree, Some(w[3]));
+        let windows: Vec<_> = shown(layout.layout(viewport, &tiled))
+            .into_iter()
+            .map(|(window_id, _)| window_id)
+            .collect();
+        assert_eq!(windows, vec![w[0], w[3]]);
+    }
+}
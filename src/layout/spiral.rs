@@ -0,0 +1,92 @@
+use crate::layout::{self, Layout, Message, Placement, Rectangle, Tiled};
+
+/// Gives each window `ratio` of the space left by the previous ones, turning
+/// clockwise: the first window goes on the left, the second at the top of
+/// what's left, the third on the right and so on, like a Fibonacci spiral.
+#[derive(Clone)]
+pub struct SpiralLayout {
+    name: String,
+    padding: u32,
+    ratio: f32,
+}
+
+impl SpiralLayout {
+    pub fn new<S: Into<String>>(name: S, padding: u32) -> SpiralLayout {
+        SpiralLayout {
+            name: name.into(),
+            padding,
+            ratio: 0.5,
+        }
+    }
+
+    /// Sets the fraction of the space left taken by each window.
+    pub fn ratio(mut self, ratio: f32) -> Self {
+        self.ratio = ratio.clamp(0.1, 0.9);
+        self
+    }
+}
+
+impl Layout for SpiralLayout {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn layout(&self, viewport: Rectangle, tiled: &Tiled) -> Vec<Placement> {
+        let windows = tiled.windows();
+        let mut left = viewport.shrink(self.padding);
+        let mut placed = vec![];
+        for (i, window_id) in windows.iter().enumerate() {
+            if i + 1 == windows.len() {
+                placed.push(Placement::shown(*window_id, left));
+                break;
+            }
+            let (rectangle, rest) = match i % 4 {
+                0 => layout::split_columns(left, self.ratio, self.padding),
+                1 => layout::split_rows(left, self.ratio, self.padding),
+                2 => {
+                    let (rest, rectangle) =
+                        layout::split_columns(left, 1.0 - self.ratio, self.padding);
+                    (rectangle, rest)
+                }
+                _ => {
+                    let (rest, rectangle) =
+                        layout::split_rows(left, 1.0 - self.ratio, self.padding);
+                    (rectangle, rest)
+                }
+            };
+            placed.push(Placement::shown(*window_id, rectangle));
+            left = rest;
+        }
+        placed
+    }
+
+    fn handle_message(&mut self, message: Message) -> bool {
+        match message {
+            Message::Expand => self.ratio = (self.ratio + 0.05).min(0.9),
+            Message::Shrink => self.ratio = (self.ratio - 0.05).max(0.1),
+            Message::IncMaster(_) => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SpiralLayout;
+    use crate::layout::test::{rectangle, rectangles};
+
+    #[test]
+    fn test_spiral() {
+        let layout = SpiralLayout::new("spiral", 0);
+        assert_eq!(
+            rectangles(&layout, rectangle(0, 0, 160, 80), 5),
+            vec![
+                rectangle(0, 0, 80, 80),
+                rectangle(80, 0, 80, 40),
+                rectangle(120, 40, 40, 40),
+                rectangle(80, 60, 40, 20),
+                rectangle(80, 40, 40, 20),
+            ]
+        );
+    }
+}
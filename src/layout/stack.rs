@@ -1,9 +1,7 @@
-use crate::layout::Layout;
-use crate::stack::Stack;
-use crate::Viewport;
-use crate::x::{Connection, WindowId};
+use crate::layout::{Layout, Placement, Rectangle, Tiled};
 
 
+/// Shows only the focused window, over the whole viewport.
 #[derive(Clone)]
 pub struct StackLayout {
     name: String,
@@ -24,32 +22,19 @@ impl Layout for StackLayout {
         &self.name
     }
 
-    fn layout(&self, connection: &Connection, viewport: &Viewport, stack: &Stack<WindowId>) {
-        if stack.is_empty() {
-            return;
-        }
-
-        // A non-empty `Stack` is guaranteed to have something focused.
-        let focused_id = stack.focused().unwrap();
-
-        for window_id in stack.iter() {
-            if focused_id == window_id {
-                continue;
-            }
-            connection.disable_window_tracking(window_id);
-            connection.unmap_window(window_id);
-            connection.enable_window_tracking(window_id);
-        }
-
-        connection.disable_window_tracking(focused_id);
-        connection.map_window(focused_id);
-        connection.configure_window(
-            focused_id,
-            viewport.x + self.padding,
-            viewport.y + self.padding,
-            viewport.width - (self.padding * 2),
-            viewport.height - (self.padding * 2),
-        );
-        connection.enable_window_tracking(focused_id);
+    fn layout(&self, viewport: Rectangle, tiled: &Tiled) -> Vec<Placement> {
+        // When a floating window is focused, keep showing the first one.
+        let shown = tiled.focused().or_else(|| tiled.windows().first().cloned());
+        tiled
+            .windows()
+            .iter()
+            .map(|window_id| {
+                if Some(*window_id) == shown {
+                    Placement::shown(*window_id, viewport.shrink(self.padding))
+                } else {
+                    Placement::hidden(*window_id)
+                }
+            })
+            .collect()
     }
 }
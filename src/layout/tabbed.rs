@@ -0,0 +1,154 @@
+use std::cmp;
+
+use crate::layout::{self, Layout, Placement, Rectangle, TabBar, Tiled};
+
+/// Shows the focused window below a bar with a tab for each window, or with
+/// a row for each window when stacked, like i3's tabbed and stacked
+/// containers.
+///
+/// The layout leaves out the configured gaps, which would come between the
+/// bar and the window, and only keeps its padding.
+#[derive(Clone)]
+pub struct TabbedLayout {
+    name: String,
+    padding: u32,
+    /// Height of a tab.
+    bar_height: u32,
+    stacked: bool,
+}
+
+impl TabbedLayout {
+    pub fn new<S: Into<String>>(name: S, padding: u32) -> TabbedLayout {
+        TabbedLayout {
+            name: name.into(),
+            padding,
+            bar_height: 20,
+            stacked: false,
+        }
+    }
+
+    /// Sets the height of a tab, which should fit the tab bar's font.
+    pub fn bar_height(mut self, bar_height: u32) -> Self {
+        self.bar_height = bar_height;
+        self
+    }
+
+    /// Puts each tab on its own row instead of side by side.
+    pub fn stacked(mut self) -> Self {
+        self.stacked = true;
+        self
+    }
+
+    /// Returns the rectangles of the bar and of the shown window.
+    fn areas(&self, viewport: Rectangle, count: usize) -> (Rectangle, Rectangle) {
+        let viewport = viewport.shrink(self.padding);
+        let rows = if self.stacked { count as u32 } else { 1 };
+        // Always leave some room for the window.
+        let height = cmp::min(self.bar_height * rows, viewport.height.saturating_sub(1));
+        (
+            Rectangle { height, ..viewport },
+            Rectangle {
+                y: viewport.y + height,
+                height: viewport.height - height,
+                ..viewport
+            },
+        )
+    }
+}
+
+impl Layout for TabbedLayout {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn layout(&self, viewport: Rectangle, tiled: &Tiled) -> Vec<Placement> {
+        let windows = tiled.windows();
+        let (_, area) = self.areas(viewport, windows.len());
+        // When a floating window is focused, keep showing the first one.
+        let shown = tiled.focused().or_else(|| windows.first().cloned());
+        windows
+            .iter()
+            .map(|window_id| {
+                if Some(*window_id) == shown {
+                    Placement {
+                        gaps: false,
+                        ..Placement::shown(*window_id, area)
+                    }
+                } else {
+                    Placement::hidden(*window_id)
+                }
+            })
+            .collect()
+    }
+
+    fn tab_bar(&self, viewport: Rectangle, tiled: &Tiled) -> Option<TabBar> {
+        let windows = tiled.windows();
+        let (bar, _) = self.areas(viewport, windows.len());
+        if windows.is_empty() || bar.height == 0 {
+            return None;
+        }
+        let tabs = if self.stacked {
+            layout::rows(bar, windows.len(), 0)
+        } else {
+            layout::columns(bar, windows.len(), 0)
+        };
+        Some(TabBar {
+            rectangle: bar,
+            tabs: windows.iter().cloned().zip(tabs).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TabbedLayout;
+    use crate::layout::test::{rectangle, shown, tree};
+    use crate::layout::{Layout, TabBar, Tiled};
+
+    #[test]
+    fn test_tabbed() {
+        let layout = TabbedLayout::new("tabbed", 0);
+        let viewport = rectangle(0, 0, 90, 60);
+        let empty = tree(0);
+        assert_eq!(layout.tab_bar(viewport, &Tiled::new(&empty, None)), None);
+        let tree = tree(3);
+        let tiled = Tiled::new(&tree, None);
+        let w = tiled.windows().to_vec();
+        let placements = layout.layout(viewport, &tiled);
+        assert_eq!(shown(placements.clone()), vec![(w[0], rectangle(0, 20, 90, 40))]);
+        assert!(!placements[0].gaps);
+        assert_eq!(
+            layout.tab_bar(viewport, &tiled),
+            Some(TabBar {
+                rectangle: rectangle(0, 0, 90, 20),
+                tabs: vec![
+                    (w[0], rectangle(0, 0, 30, 20)),
+                    (w[1], rectangle(30, 0, 30, 20)),
+                    (w[2], rectangle(60, 0, 30, 20)),
+                ],
+            })
+        );
+
+        let tiled = Tiled::new(&tree, Some(w[2]));
+        assert_eq!(
+            shown(layout.layout(viewport, &tiled)),
+            vec![(w[2], rectangle(0, 20, 90, 40))]
+        );
+    }
+
+    #[test]
+    fn test_stacked() {
+        let layout = TabbedLayout::new("stacked", 5).bar_height(10).stacked();
+        let viewport = rectangle(0, 0, 90, 60);
+        let tree = tree(2);
+        let tiled = Tiled::new(&tree, None);
+        let w = tiled.windows().to_vec();
+        assert_eq!(
+            shown(layout.layout(viewport, &tiled)),
+            vec![(w[0], rectangle(5, 25, 80, 30))]
+        );
+        let tab_bar = layout.tab_bar(viewport, &tiled).unwrap();
+        assert_eq!(tab_bar.rectangle, rectangle(5, 5, 80, 20));
+        assert_eq!(tab_bar.tabs[1], (w[1], rectangle(5, 15, 80, 10)));
+    }
+}
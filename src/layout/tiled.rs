@@ -1,9 +1,7 @@
-use crate::layout::Layout;
-use crate::stack::Stack;
-use crate::Viewport;
-use crate::x::{Connection, WindowId};
+use crate::layout::{self, Layout, Placement, Rectangle, Tiled};
 
 
+/// Stacks the windows from top to bottom, at equal heights.
 #[derive(Clone)]
 pub struct TiledLayout {
     name: String,
@@ -24,24 +22,13 @@ impl Layout for TiledLayout {
         &self.name
     }
 
-    fn layout(&self, connection: &Connection, viewport: &Viewport, stack: &Stack<WindowId>) {
-        if stack.is_empty() {
-            return;
-        }
-
-        let tile_height = ((viewport.height - self.padding) / stack.len() as u32) - self.padding;
-
-        for (i, window_id) in stack.iter().enumerate() {
-            connection.disable_window_tracking(window_id);
-            connection.map_window(window_id);
-            connection.configure_window(
-                window_id,
-                viewport.x + self.padding,
-                viewport.y + self.padding + (i as u32 * (tile_height + self.padding)),
-                viewport.width - (self.padding * 2),
-                tile_height,
-            );
-            connection.enable_window_tracking(window_id);
-        }
+    fn layout(&self, viewport: Rectangle, tiled: &Tiled) -> Vec<Placement> {
+        let windows = tiled.windows();
+        let rows = layout::rows(viewport.shrink(self.padding), windows.len(), self.padding);
+        windows
+            .iter()
+            .zip(rows)
+            .map(|(window_id, rectangle)| Placement::shown(*window_id, rectangle))
+            .collect()
     }
 }
@@ -0,0 +1,29 @@
+use crate::layout::{Layout, Placement, Rectangle, Tiled};
+
+/// Follows the workspace's tree, in which each new window splits the space
+/// of another one in two.
+#[derive(Clone)]
+pub struct TreeLayout {
+    name: String,
+}
+
+impl TreeLayout {
+    pub fn new<S: Into<String>>(name: S) -> TreeLayout {
+        TreeLayout { name: name.into() }
+    }
+}
+
+impl Layout for TreeLayout {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn layout(&self, viewport: Rectangle, tiled: &Tiled) -> Vec<Placement> {
+        let tree = tiled.tree();
+        let squares = tree.build_squares(viewport);
+        tree.leaves()
+            .into_iter()
+            .filter_map(|(ix, w)| squares[ix].map(|square| Placement::shown(*w, square)))
+            .collect()
+    }
+}
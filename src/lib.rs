@@ -1,33 +1,40 @@
 #![allow(unknown_lints)]
 
-use std::cell::RefCell;
-use std::cmp;
-use std::rc::Rc;
-
-use error_chain::ChainedError;
-use log::{debug, error, info, log};
-
+mod autostart;
 mod bsplayout;
 pub mod cmd;
 pub mod config;
 pub mod core;
+mod decorations;
 pub mod errors;
+pub mod ipc;
 mod keys;
+pub mod layout;
+pub mod reactor;
+pub mod spawn;
+mod tab_bar;
 mod utils;
 mod window_set;
 mod x;
 
 use errors::*;
-use keys::{KeyCombo, KeyHandlers};
-use window_set::WindowSet;
-use x::{Connection, Event, StrutPartial, WindowId, WindowType};
 
-pub use keys::ModKey;
+pub use keys::{KeyCombo, ModKey};
 
 pub mod keysym {
     pub use x11::keysym::*;
 }
 
+/// Returns the path of the log file, `$XDG_DATA_HOME/lanta/lanta.log`.
+///
+/// The output of the processes Lanta spawns also goes there.
+pub fn log_path() -> Result<std::path::PathBuf> {
+    let xdg_dirs = xdg::BaseDirectories::with_prefix("lanta")?;
+    xdg_dirs
+        .place_data_file("lanta.log")
+        .chain_err(|| "Could not create log file")
+}
+
 /// Initializes a logger using the default configuration.
 ///
 /// Outputs to stdout and `$XDG_DATA/lanta/lanta.log` by default.
@@ -35,10 +42,7 @@ pub mod keysym {
 pub fn intiailize_logger() -> Result<()> {
     log_panics::init();
 
-    let xdg_dirs = xdg::BaseDirectories::with_prefix("lanta")?;
-    let log_path = xdg_dirs
-        .place_data_file("lanta.log")
-        .chain_err(|| "Could not create log file")?;
+    let log_path = log_path()?;
 
     fern::Dispatch::new()
         .format(|out, message, record| {
@@ -99,7 +103,7 @@ macro_rules! layouts {
     [$( $layout:expr ),+ $(,)*] => (
         vec![
             $(
-                Box::new($layout) as Box<$crate::layout::Layout>
+                Box::new($layout) as Box<dyn $crate::layout::Layout>
             ),+
         ]
     )
@@ -0,0 +1,410 @@
+//! The event loop: waits on the X connection along with the IPC socket,
+//! timers, signals, child processes and file descriptors registered by the
+//! user, and yields whatever happens on any of them as an `Event`.
+//!
+//! Nothing here blocks except `Reactor::next_event()`, which sleeps in `poll(2)`
+//! until there is something to do.
+
+use std::collections::VecDeque;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::{Duration, Instant};
+
+use error_chain::bail;
+use log::{debug, log, warn};
+
+use crate::cmd::Command;
+use crate::errors::*;
+use crate::ipc;
+use crate::x::{self, Connection};
+
+/// Write end of the pipe the signal handler reports signals through. There
+/// is only one, as signal handlers are per process.
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+/// Identifies a timer, to reset or cancel it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimerId(u64);
+
+/// Something the WM should react to.
+pub enum Event {
+    X(x::Event),
+    IpcRequest(ipc::Client),
+    /// A timer expired, and this is its command.
+    Timer(Command),
+    /// A watched signal was received.
+    Signal(libc::c_int),
+    /// A child process exited and was reaped.
+    ChildExited(libc::pid_t, ExitStatus),
+    /// A watched file descriptor is readable, or was closed.
+    Readable(RawFd, Command),
+}
+
+pub struct Reactor {
+    x: x::EventLoop,
+    ipc: Option<ipc::Server>,
+    timers: Timers,
+    /// Read end of the signal pipe.
+    signals: RawFd,
+    fds: Vec<(RawFd, Command)>,
+    /// Events received while waiting, not yet yielded.
+    pending: VecDeque<Event>,
+}
+
+impl Reactor {
+    pub fn new(connection: Rc<Connection>) -> Result<Reactor> {
+        let mut pipe = [-1; 2];
+        if unsafe { libc::pipe2(pipe.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) } < 0 {
+            return Err(io::Error::last_os_error()).chain_err(|| "Could not create signal pipe");
+        }
+        SIGNAL_PIPE.store(pipe[1], Ordering::SeqCst);
+
+        let reactor = Reactor {
+            x: x::EventLoop::new(connection),
+            ipc: None,
+            timers: Timers::default(),
+            signals: pipe[0],
+            fds: vec![],
+            pending: VecDeque::new(),
+        };
+        // Children are always reaped, so that they don't become zombies.
+        reactor.watch_signal(libc::SIGCHLD)?;
+        Ok(reactor)
+    }
+
+    /// Starts yielding the requests received by the IPC server.
+    pub fn set_ipc_server(&mut self, server: ipc::Server) {
+        self.ipc = Some(server);
+    }
+
+    /// Runs `command` once, after `delay`.
+    pub fn after(&mut self, delay: Duration, command: Command) -> TimerId {
+        self.timers.add(Instant::now(), delay, false, command)
+    }
+
+    /// Runs `command` every `interval`, until the timer is cancelled.
+    pub fn every(&mut self, interval: Duration, command: Command) -> TimerId {
+        self.timers.add(Instant::now(), interval, true, command)
+    }
+
+    /// Restarts a timer, so that it expires a whole delay from now. Returns
+    /// false if the timer already expired or was cancelled.
+    pub fn reset_timer(&mut self, id: TimerId) -> bool {
+        self.timers.reset(Instant::now(), id)
+    }
+
+    /// Cancels a timer. Returns false if it already expired or was cancelled.
+    pub fn cancel_timer(&mut self, id: TimerId) -> bool {
+        self.timers.cancel(id)
+    }
+
+    /// Yields `Event::Signal` when the signal is received.
+    pub fn watch_signal(&self, signal: libc::c_int) -> Result<()> {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(signal, &action, std::ptr::null_mut()) < 0 {
+                return Err(io::Error::last_os_error())
+                    .chain_err(|| format!("Could not handle signal {}", signal));
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `command` whenever the file descriptor is readable. It stays
+    /// watched until `unwatch_fd()` is called, or it is closed.
+    pub fn watch_fd(&mut self, fd: RawFd, command: Command) {
+        self.unwatch_fd(fd);
+        self.fds.push((fd, command));
+    }
+
+    pub fn unwatch_fd(&mut self, fd: RawFd) {
+        self.fds.retain(|(watched, _)| *watched != fd);
+    }
+
+    /// Waits for the next event.
+    ///
+    /// Fails if the connection to the X server was lost.
+    pub fn next_event(&mut self) -> Result<Event> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(event);
+            }
+            if let Some(command) = self.timers.pop_expired(Instant::now()) {
+                return Ok(Event::Timer(command));
+            }
+            if let Some(event) = self.x.poll()? {
+                return Ok(Event::X(event));
+            }
+            self.wait()?;
+        }
+    }
+
+    /// Blocks until any file descriptor is readable or the next timer
+    /// expires, queueing the events found.
+    fn wait(&mut self) -> Result<()> {
+        let ipc_fds = self.ipc.as_ref().map(ipc::Server::fds).unwrap_or_default();
+        let mut fds: Vec<libc::pollfd> = vec![self.x.fd(), self.signals]
+            .into_iter()
+            .chain(self.fds.iter().map(|(fd, _)| *fd))
+            .chain(ipc_fds)
+            .map(|fd| libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect();
+
+        let timeout = match self.timers.next_deadline() {
+            Some(deadline) => {
+                // Round up, so that we don't wake up just before the deadline.
+                let left = deadline.saturating_duration_since(Instant::now());
+                (left.as_micros() as i64 + 999) / 1000
+            }
+            None => -1,
+        };
+        let timeout = timeout.min(i64::from(libc::c_int::MAX)) as libc::c_int;
+
+        let result = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+        if result < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() == io::ErrorKind::Interrupted {
+                return Ok(());
+            }
+            return Err(error).chain_err(|| "Error waiting for events");
+        }
+
+        if fds[0].revents & (libc::POLLERR | libc::POLLHUP) != 0 {
+            bail!("Lost the connection to the X server");
+        }
+        if fds[1].revents != 0 {
+            self.read_signals();
+        }
+
+        let (user_fds, ipc_fds) = fds[2..].split_at(self.fds.len());
+        let mut closed = vec![];
+        for (fd, (watched, command)) in user_fds.iter().zip(&self.fds) {
+            if fd.revents & libc::POLLNVAL != 0 {
+                warn!("Watched file descriptor {} was closed, no longer watching it", watched);
+                closed.push(*watched);
+            } else if fd.revents != 0 {
+                self.pending.push_back(Event::Readable(*watched, command.clone()));
+            }
+        }
+        for fd in closed {
+            self.unwatch_fd(fd);
+        }
+
+        if let Some(ref mut ipc) = self.ipc {
+            let readable: Vec<bool> = ipc_fds.iter().map(|fd| fd.revents != 0).collect();
+            let requests = ipc.ready(&readable);
+            self.pending
+                .extend(requests.into_iter().map(Event::IpcRequest));
+        }
+        Ok(())
+    }
+
+    fn read_signals(&mut self) {
+        let mut signals = [0u8; 64];
+        loop {
+            let read = unsafe {
+                libc::read(
+                    self.signals,
+                    signals.as_mut_ptr() as *mut libc::c_void,
+                    signals.len(),
+                )
+            };
+            if read <= 0 {
+                return;
+            }
+            for signal in &signals[..read as usize] {
+                let signal = libc::c_int::from(*signal);
+                debug!("Received signal {}", signal);
+                if signal == libc::SIGCHLD {
+                    self.reap_children();
+                } else {
+                    self.pending.push_back(Event::Signal(signal));
+                }
+            }
+        }
+    }
+
+    fn reap_children(&mut self) {
+        loop {
+            let mut status = 0;
+            let pid = unsafe { libc::waitpid(-1, &mut status, libc::WNOHANG) };
+            if pid <= 0 {
+                return;
+            }
+            self.pending
+                .push_back(Event::ChildExited(pid, ExitStatus::from_raw(status)));
+        }
+    }
+}
+
+impl Drop for Reactor {
+    fn drop(&mut self) {
+        let write_end = SIGNAL_PIPE.swap(-1, Ordering::SeqCst);
+        unsafe {
+            if write_end >= 0 {
+                libc::close(write_end);
+            }
+            libc::close(self.signals);
+        }
+    }
+}
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    let fd = SIGNAL_PIPE.load(Ordering::SeqCst);
+    if fd >= 0 {
+        // Only async-signal-safe calls here: write(2) is one, but it may
+        // change errno, which the interrupted code could be looking at.
+        unsafe {
+            let errno = *libc::__errno_location();
+            let byte = signal as u8;
+            libc::write(fd, &byte as *const u8 as *const libc::c_void, 1);
+            *libc::__errno_location() = errno;
+        }
+    }
+}
+
+struct Timer {
+    id: TimerId,
+    deadline: Instant,
+    delay: Duration,
+    repeat: bool,
+    command: Command,
+}
+
+/// Pending timers, in no particular order as there are only ever a few.
+#[derive(Default)]
+struct Timers {
+    timers: Vec<Timer>,
+    next_id: u64,
+}
+
+impl Timers {
+    fn add(&mut self, now: Instant, delay: Duration, repeat: bool, command: Command) -> TimerId {
+        let id = TimerId(self.next_id);
+        self.next_id += 1;
+        self.timers.push(Timer {
+            id,
+            deadline: now + delay,
+            delay,
+            repeat,
+            command,
+        });
+        id
+    }
+
+    fn reset(&mut self, now: Instant, id: TimerId) -> bool {
+        match self.timers.iter_mut().find(|timer| timer.id == id) {
+            Some(timer) => {
+                timer.deadline = now + timer.delay;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn cancel(&mut self, id: TimerId) -> bool {
+        let len = self.timers.len();
+        self.timers.retain(|timer| timer.id != id);
+        self.timers.len() != len
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.timers.iter().map(|timer| timer.deadline).min()
+    }
+
+    /// Returns the command of the earliest expired timer, removing the timer
+    /// or scheduling its next run if it repeats.
+    fn pop_expired(&mut self, now: Instant) -> Option<Command> {
+        let ix = self
+            .timers
+            .iter()
+            .enumerate()
+            .filter(|(_, timer)| timer.deadline <= now)
+            .min_by_key(|(_, timer)| timer.deadline)
+            .map(|(ix, _)| ix)?;
+        if self.timers[ix].repeat {
+            let timer = &mut self.timers[ix];
+            // Skip the runs we missed rather than running them all at once.
+            while timer.deadline <= now {
+                timer.deadline += timer.delay.max(Duration::from_millis(1));
+            }
+            Some(timer.command.clone())
+        } else {
+            Some(self.timers.remove(ix).command)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
+
+    use super::Timers;
+    use crate::cmd::Command;
+
+    fn command() -> Command {
+        Rc::new(|_| Ok(()))
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_timers_expire_in_order() {
+        let now = Instant::now();
+        let mut timers = Timers::default();
+        let late = command();
+        let early = command();
+        timers.add(now, ms(20), false, late.clone());
+        timers.add(now, ms(10), false, early.clone());
+
+        assert_eq!(timers.next_deadline(), Some(now + ms(10)));
+        assert!(timers.pop_expired(now + ms(5)).is_none());
+        let expired = timers.pop_expired(now + ms(30)).unwrap();
+        assert!(Rc::ptr_eq(&expired, &early));
+        let expired = timers.pop_expired(now + ms(30)).unwrap();
+        assert!(Rc::ptr_eq(&expired, &late));
+        assert!(timers.pop_expired(now + ms(30)).is_none());
+        assert_eq!(timers.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_repeating_timer() {
+        let now = Instant::now();
+        let mut timers = Timers::default();
+        let id = timers.add(now, ms(10), true, command());
+
+        assert!(timers.pop_expired(now + ms(25)).is_some());
+        assert_eq!(timers.next_deadline(), Some(now + ms(30)));
+        assert!(timers.pop_expired(now + ms(25)).is_none());
+        assert!(timers.cancel(id));
+        assert!(!timers.cancel(id));
+        assert_eq!(timers.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_reset_timer() {
+        let now = Instant::now();
+        let mut timers = Timers::default();
+        let id = timers.add(now, ms(10), false, command());
+
+        assert!(timers.reset(now + ms(5), id));
+        assert!(timers.pop_expired(now + ms(10)).is_none());
+        assert!(timers.pop_expired(now + ms(15)).is_some());
+        assert!(!timers.reset(now + ms(20), id));
+    }
+}
@@ -0,0 +1,199 @@
+//! Starting processes from the WM.
+
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{self, Stdio};
+
+use error_chain::bail;
+use log::{info, log, warn};
+
+use crate::errors::*;
+
+/// Environment variable through which the startup notification id is given to
+/// the process. Toolkits copy it to their windows' `_NET_STARTUP_ID`.
+const STARTUP_ID_ENV: &str = "DESKTOP_STARTUP_ID";
+
+/// What to run.
+#[derive(Clone, Debug, PartialEq)]
+enum Program {
+    /// A program and its arguments.
+    Argv(Vec<OsString>),
+    /// A command line, run with `sh -c`.
+    Shell(String),
+}
+
+/// A process to start, built with `Spawn::argv()` or `Spawn::shell()`.
+///
+/// Processes are started in their own session, so that they survive the WM
+/// being restarted, and their output goes to the Lanta log file.
+///
+/// The first window the process opens is placed on the workspace that was
+/// focused when it was started, or the one given to `Spawn::workspace()`.
+#[derive(Clone, Debug)]
+pub struct Spawn {
+    program: Program,
+    env: Vec<(OsString, OsString)>,
+    current_dir: Option<PathBuf>,
+    workspace: Option<String>,
+}
+
+impl Spawn {
+    /// Runs a program with arguments, e.g. `["urxvt", "-e", "htop"]`.
+    pub fn argv<I, S>(argv: I) -> Spawn
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        Spawn::new(Program::Argv(argv.into_iter().map(Into::into).collect()))
+    }
+
+    /// Runs a command line through `sh -c`, e.g. `"cd ~/src && urxvt"`.
+    pub fn shell<S: Into<String>>(command: S) -> Spawn {
+        Spawn::new(Program::Shell(command.into()))
+    }
+
+    fn new(program: Program) -> Spawn {
+        Spawn {
+            program,
+            env: vec![],
+            current_dir: None,
+            workspace: None,
+        }
+    }
+
+    /// Sets an environment variable for the process.
+    pub fn env<K: Into<OsString>, V: Into<OsString>>(mut self, key: K, value: V) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Sets the working directory of the process.
+    pub fn current_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Places the first window of the process on the named workspace, over
+    /// what the rules say.
+    pub fn workspace<S: Into<String>>(mut self, name: S) -> Self {
+        self.workspace = Some(name.into());
+        self
+    }
+
+    /// Returns the workspace given to `Spawn::workspace()`.
+    pub fn get_workspace(&self) -> Option<&str> {
+        self.workspace.as_deref()
+    }
+
+    /// Starts the process, returning its pid.
+    ///
+    /// `startup_id` is passed on as the startup notification id.
+    pub fn start(&self, startup_id: &str) -> Result<u32> {
+        if self.program == Program::Argv(vec![]) {
+            bail!("Nothing to spawn");
+        }
+        let mut command = self.command();
+        command.env(STARTUP_ID_ENV, startup_id);
+        if let Some(log) = open_log() {
+            command.stdout(log.try_clone()?).stderr(log);
+        }
+        unsafe {
+            // Only async-signal-safe calls are allowed here.
+            command.pre_exec(|| {
+                libc::setsid();
+                Ok(())
+            });
+        }
+
+        info!("Spawning: {:?}", command);
+        let child = command
+            .spawn()
+            .chain_err(|| format!("Could not spawn {}", self))?;
+        // The child is reaped by the `Reactor` when it exits.
+        Ok(child.id())
+    }
+
+    fn command(&self) -> process::Command {
+        let mut command = match self.program {
+            Program::Argv(ref argv) => {
+                let mut command = process::Command::new(&argv[0]);
+                command.args(&argv[1..]);
+                command
+            }
+            Program::Shell(ref line) => {
+                let mut command = process::Command::new("sh");
+                command.arg("-c").arg(line);
+                command
+            }
+        };
+        command.envs(self.env.iter().map(|(k, v)| (k, v)));
+        if let Some(ref dir) = self.current_dir {
+            command.current_dir(dir);
+        }
+        command.stdin(Stdio::null());
+        command
+    }
+}
+
+impl std::fmt::Display for Spawn {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.program {
+            Program::Argv(ref argv) => {
+                let argv: Vec<_> = argv.iter().map(|arg| arg.to_string_lossy()).collect();
+                write!(f, "{}", argv.join(" "))
+            }
+            Program::Shell(ref line) => write!(f, "sh -c '{}'", line),
+        }
+    }
+}
+
+/// Opens the log file for a child's output. Failing that, the child shares
+/// our output.
+fn open_log() -> Option<File> {
+    let file = crate::log_path().and_then(|path| {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(file)
+    });
+    match file {
+        Ok(file) => Some(file),
+        Err(error) => {
+            warn!("Could not open the log file for a child's output: {}", error);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::OsStr;
+    use std::path::Path;
+
+    use super::Spawn;
+
+    #[test]
+    fn test_argv() {
+        let spawn = Spawn::argv(vec!["urxvt", "-e", "htop"]).env("TERM", "xterm");
+        let command = spawn.command();
+        assert_eq!(command.get_program(), "urxvt");
+        let args: Vec<&OsStr> = command.get_args().collect();
+        assert_eq!(args, vec!["-e", "htop"]);
+        let envs: Vec<_> = command.get_envs().collect();
+        assert_eq!(envs, vec![(OsStr::new("TERM"), Some(OsStr::new("xterm")))]);
+        assert_eq!(spawn.to_string(), "urxvt -e htop");
+    }
+
+    #[test]
+    fn test_shell() {
+        let spawn = Spawn::shell("make && ./run")
+            .current_dir("/tmp")
+            .workspace("code");
+        let command = spawn.command();
+        assert_eq!(command.get_program(), "sh");
+        let args: Vec<&OsStr> = command.get_args().collect();
+        assert_eq!(args, vec!["-c", "make && ./run"]);
+        assert_eq!(command.get_current_dir(), Some(Path::new("/tmp")));
+        assert_eq!(spawn.get_workspace(), Some("code"));
+    }
+}
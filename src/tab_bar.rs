@@ -0,0 +1,176 @@
+//! The tab bars drawn for layouts such as `TabbedLayout`.
+//!
+//! Each screen whose layout asks for a tab bar gets an override-redirect
+//! window of ours, in which a tab is drawn for each window with its title.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::config::TabBarConfig;
+use crate::layout::TabBar;
+use crate::utils::Rectangle;
+use crate::x::{Connection, Tab, WindowId};
+
+/// The tab bars of the screens.
+pub struct TabBars {
+    connection: Rc<Connection>,
+    /// The bar of each screen showing one, by screen index.
+    bars: HashMap<usize, Bar>,
+    /// The titles of the windows with a tab, read when first drawn.
+    titles: HashMap<WindowId, String>,
+}
+
+struct Bar {
+    window_id: WindowId,
+    tab_bar: TabBar,
+}
+
+impl TabBars {
+    pub fn new(connection: Rc<Connection>) -> TabBars {
+        TabBars {
+            connection,
+            bars: HashMap::new(),
+            titles: HashMap::new(),
+        }
+    }
+
+    /// Shows a screen's tab bar where the layout put it, or removes it if
+    /// the layout has none. It is drawn by `draw()`.
+    pub fn show(&mut self, screen: usize, tab_bar: Option<TabBar>) {
+        let tab_bar = match tab_bar {
+            Some(tab_bar) => tab_bar,
+            None => {
+                if let Some(bar) = self.bars.remove(&screen) {
+                    self.connection.destroy_window(&bar.window_id);
+                }
+                return;
+            }
+        };
+        let connection = &self.connection;
+        let moved = match self.bars.get(&screen) {
+            Some(bar) => bar.tab_bar.rectangle != tab_bar.rectangle,
+            None => true,
+        };
+        let bar = self.bars.entry(screen).or_insert_with(|| Bar {
+            window_id: connection.create_bar_window(),
+            tab_bar: tab_bar.clone(),
+        });
+        if moved {
+            connection.configure_window_rectangle(&bar.window_id, &tab_bar.rectangle);
+        }
+        bar.tab_bar = tab_bar;
+        connection.map_window(&bar.window_id);
+        connection.raise_window(&bar.window_id);
+    }
+
+    /// Removes the tab bars of the screens that no longer exist, those from
+    /// index `count` on.
+    pub fn retain_screens(&mut self, count: usize) {
+        let connection = &self.connection;
+        self.bars.retain(|screen, bar| {
+            if *screen < count {
+                return true;
+            }
+            connection.destroy_window(&bar.window_id);
+            false
+        });
+    }
+
+    /// Draws every tab bar, colouring the tabs of the focused window and of
+    /// the windows asking for attention.
+    pub fn draw<F>(&mut self, config: &TabBarConfig, focused: Option<WindowId>, is_urgent: F)
+    where
+        F: Fn(&WindowId) -> bool,
+    {
+        for bar in self.bars.values() {
+            for (window_id, _) in &bar.tab_bar.tabs {
+                if !self.titles.contains_key(window_id) {
+                    let title = self.connection.get_window_title(window_id);
+                    self.titles.insert(*window_id, title.unwrap_or_default());
+                }
+            }
+        }
+
+        for bar in self.bars.values() {
+            let origin = &bar.tab_bar.rectangle;
+            let tabs: Vec<Tab> = bar
+                .tab_bar
+                .tabs
+                .iter()
+                .map(|(window_id, rectangle)| {
+                    let background = if Some(*window_id) == focused {
+                        config.focused
+                    } else if is_urgent(window_id) {
+                        config.urgent
+                    } else {
+                        config.unfocused
+                    };
+                    Tab {
+                        rectangle: relative(origin, rectangle),
+                        title: &self.titles[window_id],
+                        background,
+                        foreground: config.text,
+                        centered: false,
+                    }
+                })
+                .collect();
+            self.connection
+                .draw_tabs(&bar.window_id, &config.font, &tabs);
+        }
+    }
+
+    /// Reads the window's title again the next time its tab is drawn, e.g.
+    /// because it changed.
+    pub fn forget_title(&mut self, window_id: &WindowId) {
+        self.titles.remove(window_id);
+    }
+
+    /// Returns the window whose tab is at a position in one of the bars.
+    pub fn window_at(&self, bar: &WindowId, x: u32, y: u32) -> Option<WindowId> {
+        self.bars
+            .values()
+            .find(|b| b.window_id == *bar)
+            .and_then(|b| tab_at(&b.tab_bar, x, y))
+    }
+}
+
+/// Returns a rectangle relative to another one's top left corner.
+fn relative(origin: &Rectangle, rectangle: &Rectangle) -> Rectangle {
+    Rectangle {
+        x: rectangle.x.saturating_sub(origin.x),
+        y: rectangle.y.saturating_sub(origin.y),
+        ..*rectangle
+    }
+}
+
+/// Returns the window whose tab is at a position relative to the bar.
+fn tab_at(tab_bar: &TabBar, x: u32, y: u32) -> Option<WindowId> {
+    tab_bar
+        .tabs
+        .iter()
+        .find(|(_, rectangle)| relative(&tab_bar.rectangle, rectangle).contains(x, y))
+        .map(|(window_id, _)| *window_id)
+}
+
+#[cfg(test)]
+mod test {
+    use super::tab_at;
+    use crate::layout::test::{rectangle, window};
+    use crate::layout::TabBar;
+
+    #[test]
+    fn test_tab_at() {
+        let tab_bar = TabBar {
+            rectangle: rectangle(100, 50, 90, 20),
+            tabs: vec![
+                (window(1), rectangle(100, 50, 30, 20)),
+                (window(2), rectangle(130, 50, 60, 20)),
+            ],
+        };
+        assert_eq!(tab_at(&tab_bar, 0, 0), Some(window(1)));
+        assert_eq!(tab_at(&tab_bar, 29, 19), Some(window(1)));
+        assert_eq!(tab_at(&tab_bar, 30, 10), Some(window(2)));
+        assert_eq!(tab_at(&tab_bar, 90, 10), None);
+        assert_eq!(tab_at(&tab_bar, 10, 20), None);
+    }
+}
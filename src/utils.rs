@@ -1,4 +1,8 @@
-#[derive(Clone, Copy, Debug, Default)]
+use std::cmp;
+
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
 pub struct Rectangle {
     pub x: u32,
     pub y: u32,
@@ -6,7 +10,40 @@ pub struct Rectangle {
     pub height: u32,
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+impl Rectangle {
+    /// Returns the rectangle with `amount` removed from each side, without
+    /// ever collapsing below 1x1.
+    pub fn shrink(&self, amount: u32) -> Rectangle {
+        let amount_x = cmp::min(amount, self.width.saturating_sub(1) / 2);
+        let amount_y = cmp::min(amount, self.height.saturating_sub(1) / 2);
+        Rectangle {
+            x: self.x + amount_x,
+            y: self.y + amount_y,
+            width: self.width - 2 * amount_x,
+            height: self.height - 2 * amount_y,
+        }
+    }
+
+    /// Returns the point in the middle of the rectangle.
+    pub fn center(&self) -> (u32, u32) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+
+    /// Returns whether a point is inside the rectangle.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
 pub struct Reserved {
     pub left: u32,
     pub right: u32,
@@ -14,9 +51,84 @@ pub struct Reserved {
     pub top: u32,
 }
 
-#[derive(Default)]
+impl Reserved {
+    pub fn is_empty(&self) -> bool {
+        *self == Reserved::default()
+    }
+}
+
+/// A dock's _NET_WM_STRUT_PARTIAL: the space it reserves at each edge of the
+/// root window, and the part of the edge it reserves it along.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Strut {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+    pub left_start_y: u32,
+    pub left_end_y: u32,
+    pub right_start_y: u32,
+    pub right_end_y: u32,
+    pub top_start_x: u32,
+    pub top_end_x: u32,
+    pub bottom_start_x: u32,
+    pub bottom_end_x: u32,
+}
+
+impl Strut {
+    /// Returns the space reserved at each edge of a screen, given the root
+    /// window's area.
+    ///
+    /// Only the screens the strut reaches into are affected: a bar at the
+    /// top of the right screen reserves nothing on the left one.
+    pub fn reserved_on(&self, screen: &Rectangle, root: &Rectangle) -> Reserved {
+        // A strut without a range, such as a _NET_WM_STRUT, covers the
+        // whole edge.
+        let along = |start: u32, end: u32, from: u32, length: u32| {
+            (start == 0 && end == 0) || (start < from + length && end >= from)
+        };
+        // Space between the screen's edges and the root window's.
+        let left = screen.x.saturating_sub(root.x);
+        let right = (root.x + root.width).saturating_sub(screen.x + screen.width);
+        let top = screen.y.saturating_sub(root.y);
+        let bottom = (root.y + root.height).saturating_sub(screen.y + screen.height);
+
+        let mut reserved = Reserved::default();
+        if along(self.left_start_y, self.left_end_y, screen.y, screen.height) {
+            reserved.left = self.left.saturating_sub(left);
+        }
+        if along(self.right_start_y, self.right_end_y, screen.y, screen.height) {
+            reserved.right = self.right.saturating_sub(right);
+        }
+        if along(self.top_start_x, self.top_end_x, screen.x, screen.width) {
+            reserved.top = self.top.saturating_sub(top);
+        }
+        if along(self.bottom_start_x, self.bottom_end_x, screen.x, screen.width) {
+            reserved.bottom = self.bottom.saturating_sub(bottom);
+        }
+        reserved
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
 pub struct ScreenInfo {
     pub id: u8,
+    /// Name of the RandR output, such as `HDMI-1`.
+    pub name: String,
+    pub x: u32,
+    pub y: u32,
     pub width: u32,
     pub height: u32,
-}
\ No newline at end of file
+}
+
+impl ScreenInfo {
+    /// Returns the area of the screen.
+    pub fn rectangle(&self) -> Rectangle {
+        Rectangle {
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
@@ -1,50 +1,113 @@
 use std::cmp;
 use std::collections::hash_map::HashMap;
+use std::collections::{BTreeMap, HashSet};
+use std::iter;
+use std::mem;
+use std::str::FromStr;
 
-use log::{debug, error, log};
+use error_chain::bail;
+use log::{debug, error, log, warn};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     bsplayout::BSPLayout,
     config::WorkspaceConfig,
-    utils::{Rectangle, ScreenInfo, Reserved},
+    errors::*,
+    layout::{self, Layout, Placement, TabBar, Tiled},
+    utils::{Direction, Rectangle, Reserved, ScreenInfo, Strut},
     x::WindowId,
 };
 
 /// XMonad inspired structure
 pub struct WindowSet {
     /// Focused screen, between visible screens
-    currentScreen: usize,
+    current_screen: usize,
     /// Avialable screens
     visible: Vec<Screen>,
     /// All Workspaces
     workspaces: Vec<Workspace>,
     /// Windows internal status
     windows_status: HashMap<WindowId, Status>,
+    /// Windows asking for attention
+    urgent: HashSet<WindowId>,
+    /// Scratchpad windows by name, hidden or shown as floating windows
+    scratchpads: BTreeMap<String, WindowId>,
+    /// Floating windows shown on every workspace of a screen, with the
+    /// position of their screen
+    sticky: HashMap<WindowId, usize>,
+    /// Layouts each new workspace gets a copy of
+    layouts: Vec<Box<dyn Layout>>,
+}
+
+/// A screen, relative to the focused one or by position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScreenTarget {
+    Next,
+    Previous,
+    /// The nearest screen in a direction.
+    Direction(Direction),
+    Index(usize),
+}
+
+impl FromStr for ScreenTarget {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "next" => ScreenTarget::Next,
+            "previous" => ScreenTarget::Previous,
+            "left" => ScreenTarget::Direction(Direction::Left),
+            "right" => ScreenTarget::Direction(Direction::Right),
+            "up" => ScreenTarget::Direction(Direction::Up),
+            "down" => ScreenTarget::Direction(Direction::Down),
+            _ => match s.parse() {
+                Ok(ix) => ScreenTarget::Index(ix),
+                Err(_) => bail!(
+                    "Invalid screen '{}', expected next, previous, left, right, up, down or a position",
+                    s
+                ),
+            },
+        })
+    }
 }
 
 /// TODO Maybe i need add more types, Panels and others or remove
 /// workspace where the window is locate?
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Status {
-    /// Tiled with the postion into the tree
-    Tiled(usize),
+    /// Tiled into the workspace's tree
+    Tiled,
     /// Is floating
     Floating,
     /// The windows is unmapped on screen
     Minimized,
     /// Take whole screen, don't hide docks (Not fullscreen)
     Maximized,
-    /// App take all screen hide docks, and set fullscreen mode on app
-    FullScreen,
     /// Special
     Dock,
+    /// A hidden scratchpad, in no workspace
+    Scratchpad,
 }
 
 impl WindowSet {
-    pub fn new(workspaces_config: Vec<WorkspaceConfig>, screens: Vec<ScreenInfo>) -> Self {
-        let workspaces: Vec<Workspace> =
-            workspaces_config.into_iter().map(Workspace::new).collect();
+    pub fn new(
+        workspaces_config: Vec<WorkspaceConfig>,
+        layouts: Vec<Box<dyn Layout>>,
+        screens: Vec<ScreenInfo>,
+    ) -> Self {
+        let layouts = if layouts.is_empty() {
+            error!("No layouts, using the default ones");
+            layout::defaults()
+        } else {
+            layouts
+        };
+        let workspaces: Vec<Workspace> = workspaces_config
+            .into_iter()
+            .map(|config| Workspace::new(config, &layouts))
+            .collect();
 
-        if screens.len() == 0 {
+        if screens.is_empty() {
             // TODO: exit
             error!("No screens, you didn't need a wm");
         }
@@ -59,29 +122,184 @@ impl WindowSet {
 
         let visible = screens
             .into_iter()
-            .zip(1..workspaces.len())
+            .zip(0..workspaces.len())
             .map(|(info, workspace)| Screen::new(workspace, info))
             .collect();
 
         Self {
-            currentScreen: 0,
+            current_screen: 0,
             visible,
             workspaces,
             windows_status: HashMap::new(),
+            urgent: HashSet::new(),
+            scratchpads: BTreeMap::new(),
+            sticky: HashMap::new(),
+            layouts,
+        }
+    }
+
+    /// Applies a new list of workspace configurations.
+    ///
+    /// Workspaces are matched by name, so that those renamed, moved or added
+    /// at runtime stay as they are: existing ones switch to their configured
+    /// layout, if any, and missing ones are appended. Workspaces that are no
+    /// longer configured are dropped if they are empty and not visible,
+    /// otherwise they are kept.
+    pub fn set_workspaces_config(&mut self, workspaces_config: Vec<WorkspaceConfig>) {
+        let configured: Vec<String> = workspaces_config.iter().map(|c| c.name.clone()).collect();
+        for workspace_config in workspaces_config {
+            match self.workspace_index(&workspace_config.name) {
+                Some(ix) => {
+                    if !workspace_config.layout.is_empty() {
+                        self.workspaces[ix].set_layout(&workspace_config.layout);
+                    }
+                }
+                None => {
+                    let workspace = Workspace::new(workspace_config, &self.layouts);
+                    self.workspaces.push(workspace);
+                }
+            }
+        }
+        for ix in (0..self.workspaces.len()).rev() {
+            let workspace = &self.workspaces[ix];
+            if configured.contains(&workspace.name) {
+                continue;
+            }
+            if !workspace.is_empty() || !workspace.minimized.is_empty() || self.is_visible(ix) {
+                warn!(
+                    "Keeping workspace {} as it is not empty or visible",
+                    workspace.name
+                );
+                continue;
+            }
+            if let Err(error) = self.remove_workspace(ix, None) {
+                warn!("Keeping workspace {}: {}", self.workspaces[ix].name, error);
+            }
+        }
+    }
+
+    /// Replaces the layouts of every workspace. Workspaces keep using the
+    /// layout of the same name if there is one.
+    pub fn set_layouts(&mut self, layouts: Vec<Box<dyn Layout>>) {
+        if layouts.is_empty() {
+            return;
+        }
+        for workspace in &mut self.workspaces {
+            let name = workspace.layout().name().to_owned();
+            workspace.layouts = layouts.clone();
+            workspace.current_layout = 0;
+            workspace.set_layout(&name);
+        }
+        self.layouts = layouts;
+    }
+
+    /// Appends a new, empty workspace, returning its position.
+    pub fn add_workspace(&mut self, workspace_config: WorkspaceConfig) -> Result<usize> {
+        if self.workspace_index(&workspace_config.name).is_some() {
+            bail!("There is already a workspace named '{}'", workspace_config.name);
+        }
+        self.workspaces
+            .push(Workspace::new(workspace_config, &self.layouts));
+        Ok(self.workspaces.len() - 1)
+    }
+
+    pub fn rename_workspace(&mut self, workspace: usize, name: &str) -> Result<()> {
+        match self.workspace_index(name) {
+            Some(ix) if ix != workspace => {
+                bail!("There is already a workspace named '{}'", name)
+            }
+            _ => self.workspaces[workspace].name = name.to_owned(),
+        }
+        Ok(())
+    }
+
+    /// Removes a workspace. Its windows go to the `merge_into` workspace,
+    /// which is needed unless it's empty. If a screen shows it, the screen
+    /// shows a hidden workspace instead.
+    pub fn remove_workspace(&mut self, workspace: usize, merge_into: Option<usize>) -> Result<()> {
+        let has_windows = {
+            let workspace = &self.workspaces[workspace];
+            !workspace.is_empty() || !workspace.minimized.is_empty()
+        };
+        match merge_into {
+            Some(target) if target == workspace => {
+                bail!("Can't merge a workspace into itself")
+            }
+            None if has_windows => bail!(
+                "Workspace '{}' has windows, give a workspace to move them to",
+                self.workspaces[workspace].name
+            ),
+            _ => {}
+        }
+        if let Some(screen) = self.visible.iter().position(|s| s.workspace == workspace) {
+            let hidden = (0..self.workspaces.len())
+                .find(|ix| *ix != workspace && !self.is_visible(*ix) && Some(*ix) != merge_into)
+                .or_else(|| merge_into.filter(|ix| !self.is_visible(*ix)));
+            match hidden {
+                Some(hidden) => self.visible[screen].workspace = hidden,
+                None => bail!(
+                    "Workspace '{}' is shown and no hidden workspace can replace it",
+                    self.workspaces[workspace].name
+                ),
+            }
+        }
+
+        let removed = self.workspaces.remove(workspace);
+        if let Some(target) = merge_into {
+            let target = &mut self.workspaces[if target > workspace { target - 1 } else { target }];
+            let focused = target.focused;
+            for (_, window_id) in removed.tree.leaves() {
+                target.add_tiled(*window_id);
+            }
+            for window_id in &removed.floats {
+                target.add_floating(*window_id);
+            }
+            target.minimized.extend(removed.minimized);
+            target.focused = focused.or_else(|| target.windows().first().cloned());
+        }
+        for screen in &mut self.visible {
+            if screen.workspace > workspace {
+                screen.workspace -= 1;
+            }
+        }
+        self.follow_screens();
+        Ok(())
+    }
+
+    /// Moves a workspace to another position, shifting those in between.
+    pub fn move_workspace(&mut self, from: usize, to: usize) {
+        let workspace = self.workspaces.remove(from);
+        self.workspaces.insert(to, workspace);
+        for screen in &mut self.visible {
+            screen.workspace = match screen.workspace {
+                ix if ix == from => to,
+                ix if from < ix && ix <= to => ix - 1,
+                ix if to <= ix && ix < from => ix + 1,
+                ix => ix,
+            };
         }
     }
 
     pub fn remove_window(&mut self, window_id: &WindowId) {
         debug!("Unmanaging window: {}", window_id);
+        self.urgent.remove(window_id);
+        self.scratchpads.retain(|_, w| w != window_id);
+        self.sticky.remove(window_id);
 
-        match self.windows_status.get(window_id) {
+        match self.windows_status.remove(window_id) {
             Some(status) => match status {
-                Status::Tiled(_pos) => {}
-                Status::Floating => {}
-                Status::Minimized => {}
+                Status::Tiled | Status::Floating | Status::Minimized => {
+                    for workspace in &mut self.workspaces {
+                        workspace.remove(window_id);
+                    }
+                }
                 Status::Maximized => {}
-                Status::FullScreen => {}
-                Status::Dock => {} // viewport changed?
+                Status::Scratchpad => {}
+                Status::Dock => {
+                    for screen in &mut self.visible {
+                        screen.remove_dock(window_id);
+                    }
+                }
             },
             None => debug!("Trying to unmanage, an unmanage window"),
         }
@@ -89,11 +307,485 @@ impl WindowSet {
 
     /// Returns whether the window is a member of any group.
     pub fn contains(&self, window_id: &WindowId) -> bool {
-        self.windows_status.get(window_id).is_some()
+        self.windows_status.contains_key(window_id)
+    }
+
+    /// Returns the state to carry over a restart: the workspaces with their
+    /// windows and focus, and the workspace shown on each screen.
+    pub fn save(&self) -> Result<String> {
+        let state = SavedState {
+            current_screen: self.current_screen,
+            screens: self
+                .visible
+                .iter()
+                .map(|screen| self.workspaces[screen.workspace].name.clone())
+                .collect(),
+            workspaces: self
+                .workspaces
+                .iter()
+                .map(|workspace| SavedWorkspace {
+                    name: workspace.name.clone(),
+                    layout: workspace.tree.clone(),
+                    layout_name: Some(workspace.layout().name().to_owned()),
+                    floats: workspace.floats.clone(),
+                    minimized: workspace.minimized.clone(),
+                    maximized: workspace.maximized,
+                    focused: workspace.focused,
+                })
+                .collect(),
+            scratchpads: self.scratchpads.clone(),
+            sticky: self.sticky.iter().map(|(w, screen)| (*w, *screen)).collect(),
+        };
+        serde_json::to_string(&state).chain_err(|| "Could not save the window set")
+    }
+
+    /// Restores the state returned by `save()`, before any window is added.
+    ///
+    /// Workspaces are matched by name, those that no longer exist are added
+    /// back if they had windows. Windows for which `exists` returns false are
+    /// left out. Returns the windows restored.
+    pub fn restore<F>(&mut self, state: &str, exists: F) -> Result<Vec<WindowId>>
+    where
+        F: Fn(&WindowId) -> bool,
+    {
+        let state: SavedState =
+            serde_json::from_str(state).chain_err(|| "Invalid saved window set")?;
+        let mut restored = vec![];
+
+        for saved in state.workspaces {
+            let workspace = match self.workspace_index(&saved.name) {
+                Some(ix) => &mut self.workspaces[ix],
+                None if saved.is_empty() => {
+                    warn!("Not restoring workspace {} as it no longer exists", saved.name);
+                    continue;
+                }
+                None => {
+                    // Added at runtime, keep it for its windows.
+                    let config = WorkspaceConfig::new(saved.name.clone());
+                    self.workspaces.push(Workspace::new(config, &self.layouts));
+                    self.workspaces.last_mut().unwrap()
+                }
+            };
+
+            workspace.tree = saved.layout;
+            if let Some(ref name) = saved.layout_name {
+                workspace.set_layout(name);
+            }
+            let missing: Vec<WindowId> = workspace
+                .tree
+                .leaves()
+                .into_iter()
+                .map(|(_, window_id)| *window_id)
+                .filter(|window_id| !exists(window_id))
+                .collect();
+            for window_id in missing {
+                if let Some(ix) = workspace.tree.find_leaf(&window_id) {
+                    workspace.tree.remove(ix);
+                }
+            }
+            workspace.floats = saved.floats.into_iter().filter(&exists).collect();
+            workspace.minimized = saved.minimized.into_iter().filter(&exists).collect();
+            workspace.maximized = saved
+                .maximized
+                .filter(|w| workspace.tree.find_leaf(w).is_some());
+            workspace.focused = saved.focused.filter(|w| workspace.contains(w));
+
+            let statuses = workspace
+                .tree
+                .leaves()
+                .into_iter()
+                .map(|(_, window_id)| (*window_id, Status::Tiled))
+                .chain(workspace.floats.iter().map(|w| (*w, Status::Floating)))
+                .chain(workspace.minimized.iter().map(|w| (*w, Status::Minimized)));
+            for (window_id, status) in statuses {
+                self.windows_status.insert(window_id, status);
+                restored.push(window_id);
+            }
+        }
+
+        for (name, window_id) in state.scratchpads {
+            if !exists(&window_id) {
+                continue;
+            }
+            if !self.contains(&window_id) {
+                self.windows_status.insert(window_id, Status::Scratchpad);
+                restored.push(window_id);
+            }
+            self.scratchpads.insert(name, window_id);
+        }
+
+        for (window_id, screen) in state.sticky {
+            if self.status(&window_id) == Some(Status::Floating) && screen < self.visible.len() {
+                self.sticky.insert(window_id, screen);
+            }
+        }
+
+        for (screen, name) in state.screens.iter().enumerate().take(self.visible.len()) {
+            if let Some(workspace) = self.workspace_index(name) {
+                // Keep each workspace on a single screen.
+                if let Some(other) = self.visible.iter().position(|s| s.workspace == workspace) {
+                    self.visible[other].workspace = self.visible[screen].workspace;
+                }
+                self.visible[screen].workspace = workspace;
+            }
+        }
+        self.current_screen = cmp::min(state.current_screen, self.visible.len() - 1);
+
+        Ok(restored)
     }
 
-    /// it should focus?
-    pub fn add_window(&mut self, window_id: WindowId) {
+    /// Adds a dock sitting on a screen, reserving space for it on the
+    /// screens its strut reaches into.
+    pub fn add_dock(&mut self, window_id: WindowId, screen: usize, strut: Strut) {
+        if self.contains(&window_id) {
+            error!("Asked to add a dock that's already managed: {}", window_id);
+            return;
+        }
+        self.place_dock(window_id, screen, strut);
+        self.windows_status.insert(window_id, Status::Dock);
+    }
+
+    fn place_dock(&mut self, window_id: WindowId, screen: usize, strut: Strut) {
+        let root = self.root_rectangle();
+        let mut added = false;
+        for (ix, s) in self.visible.iter_mut().enumerate() {
+            let reserved = strut.reserved_on(&s.info.rectangle(), &root);
+            // Keep track of docks reserving nothing on the screen they're on.
+            if !reserved.is_empty() || (ix == screen && !added) {
+                s.add_dock(window_id, strut, reserved);
+                added = true;
+            }
+        }
+    }
+
+    /// Replaces the screens with those now available, e.g. after a monitor
+    /// was plugged in. Screens are matched by name: those still there keep
+    /// their workspace, and new ones show a hidden workspace, if one is left.
+    /// Docks reserve space on the new screens.
+    ///
+    /// Returns the names of the screens added and of those removed.
+    pub fn set_screens(&mut self, screens: Vec<ScreenInfo>) -> (Vec<String>, Vec<String>) {
+        let old = mem::take(&mut self.visible);
+        let find = |name: &str| old.iter().position(|s| s.info.name == name);
+
+        let mut workspaces: Vec<Option<usize>> = screens
+            .iter()
+            .map(|info| find(&info.name).map(|ix| old[ix].workspace))
+            .collect();
+        for ix in 0..screens.len() {
+            if workspaces[ix].is_none() {
+                let hidden = (0..self.workspaces.len()).find(|w| !workspaces.contains(&Some(*w)));
+                workspaces[ix] = hidden;
+            }
+        }
+
+        let mut added = Vec::new();
+        let mut positions = HashMap::new();
+        for (info, workspace) in screens.into_iter().zip(workspaces) {
+            let workspace = match workspace {
+                Some(workspace) => workspace,
+                None => {
+                    error!("No workspace left to show on screen {}", info.name);
+                    continue;
+                }
+            };
+            match find(&info.name) {
+                Some(ix) => {
+                    positions.insert(ix, self.visible.len());
+                }
+                None => added.push(info.name.clone()),
+            }
+            self.visible.push(Screen::new(workspace, info));
+        }
+        let removed = old
+            .iter()
+            .enumerate()
+            .filter(|(ix, _)| !positions.contains_key(ix))
+            .map(|(_, s)| s.info.name.clone())
+            .collect();
+
+        // What was on a removed screen goes to the first one.
+        let position = |ix: &usize| positions.get(ix).cloned().unwrap_or(0);
+        self.current_screen = position(&self.current_screen);
+        for screen in self.sticky.values_mut() {
+            *screen = position(screen);
+        }
+        self.carry_sticky();
+        let mut docks = HashSet::new();
+        for (ix, screen) in old.iter().enumerate() {
+            for dock in &screen.docks {
+                if docks.insert(dock.window_id) {
+                    self.place_dock(dock.window_id, position(&ix), dock.strut);
+                }
+            }
+        }
+        (added, removed)
+    }
+
+    /// Returns the area covered by the screens, which is the root window's.
+    fn root_rectangle(&self) -> Rectangle {
+        let right = self.visible.iter().map(|s| s.info.x + s.info.width).max();
+        let bottom = self.visible.iter().map(|s| s.info.y + s.info.height).max();
+        Rectangle {
+            x: 0,
+            y: 0,
+            width: right.unwrap_or(0),
+            height: bottom.unwrap_or(0),
+        }
+    }
+
+    /// Minimizes a window, taking it out of its workspace's tree and
+    /// floating windows. Returns false if it's not a tiled or floating
+    /// window.
+    pub fn minimize(&mut self, window_id: &WindowId) -> bool {
+        match self.status(window_id) {
+            Some(Status::Tiled) | Some(Status::Floating) => {}
+            _ => return false,
+        }
+        // A scratchpad is hidden, not minimized.
+        if self.scratchpad_name(window_id).is_some() {
+            return false;
+        }
+        match self.workspace_of(window_id) {
+            Some(ix) => {
+                self.sticky.remove(window_id);
+                self.workspaces[ix].minimize(window_id);
+                self.windows_status.insert(*window_id, Status::Minimized);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Puts a minimized window back into the tree of the focused workspace,
+    /// and focuses it. Returns false if it's not minimized.
+    pub fn unminimize(&mut self, window_id: &WindowId) -> bool {
+        if self.status(window_id) != Some(Status::Minimized) {
+            return false;
+        }
+        for workspace in &mut self.workspaces {
+            workspace.remove(window_id);
+        }
+        self.workspace_mut().add_tiled(*window_id);
+        self.windows_status.insert(*window_id, Status::Tiled);
+        true
+    }
+
+    /// Returns the minimized windows of every workspace, the focused
+    /// workspace's first and the most recently minimized first.
+    pub fn minimized_windows(&self) -> Vec<WindowId> {
+        let focused = self.focused_workspace();
+        let others = self
+            .workspaces
+            .iter()
+            .enumerate()
+            .filter(|(ix, _)| *ix != focused)
+            .map(|(_, workspace)| workspace);
+        iter::once(&self.workspaces[focused])
+            .chain(others)
+            .flat_map(|workspace| workspace.minimized.iter().rev().cloned())
+            .collect()
+    }
+
+    /// Makes a window sticky, or not. A sticky window floats on every
+    /// workspace of the screen showing it: it moves along when the screen
+    /// shows another workspace. Returns whether that changed.
+    pub fn set_sticky(&mut self, window_id: &WindowId, sticky: bool) -> bool {
+        if !sticky {
+            return self.sticky.remove(window_id).is_some();
+        }
+        if self.sticky.contains_key(window_id) || self.scratchpad_name(window_id).is_some() {
+            return false;
+        }
+        self.set_floating(window_id, true);
+        let ix = match (self.status(window_id), self.workspace_of(window_id)) {
+            (Some(Status::Floating), Some(ix)) => ix,
+            _ => return false,
+        };
+        let screen = self
+            .visible
+            .iter()
+            .position(|s| s.workspace == ix)
+            .unwrap_or(self.current_screen);
+        self.sticky.insert(*window_id, screen);
+        true
+    }
+
+    /// Makes a tiled window float over the others, keeping its focus, or
+    /// puts a floating window back into its workspace's tree and focuses
+    /// it. Returns whether that changed.
+    ///
+    /// Scratchpads always float, and a window that no longer does is no
+    /// longer sticky.
+    pub fn set_floating(&mut self, window_id: &WindowId, floating: bool) -> bool {
+        let ix = match self.workspace_of(window_id) {
+            Some(ix) => ix,
+            None => return false,
+        };
+        let scratchpad = self.scratchpad_name(window_id).is_some();
+        let status = self.status(window_id);
+        let workspace = &mut self.workspaces[ix];
+        match status {
+            Some(Status::Tiled) | Some(Status::Maximized) if floating => {
+                let focused = workspace.focused() == Some(*window_id);
+                workspace.remove(window_id);
+                workspace.floats.push(*window_id);
+                if focused {
+                    workspace.focused = Some(*window_id);
+                }
+                self.windows_status.insert(*window_id, Status::Floating);
+                true
+            }
+            Some(Status::Floating) if !floating && !scratchpad => {
+                self.sticky.remove(window_id);
+                workspace.remove(window_id);
+                workspace.add_tiled(*window_id);
+                self.windows_status.insert(*window_id, Status::Tiled);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn is_sticky(&self, window_id: &WindowId) -> bool {
+        self.sticky.contains_key(window_id)
+    }
+
+    /// Moves the sticky windows to the workspace their screen shows.
+    fn carry_sticky(&mut self) {
+        for (window_id, screen) in &self.sticky {
+            let target = self.visible[*screen].workspace;
+            match self.workspaces.iter().position(|w| w.contains(window_id)) {
+                Some(ix) if ix != target => {
+                    self.workspaces[ix].remove(window_id);
+                    self.workspaces[target].floats.push(*window_id);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Adds the window of a scratchpad, hidden. Returns false, leaving the
+    /// window unmanaged, if the scratchpad already has one.
+    pub fn add_scratchpad(&mut self, window_id: WindowId, name: &str) -> bool {
+        if self.contains(&window_id) {
+            error!("Asked to add a scratchpad that's already managed: {}", window_id);
+            return false;
+        }
+        if self.scratchpads.contains_key(name) {
+            return false;
+        }
+        self.scratchpads.insert(name.to_owned(), window_id);
+        self.windows_status.insert(window_id, Status::Scratchpad);
+        true
+    }
+
+    /// Returns the window of the named scratchpad.
+    pub fn scratchpad(&self, name: &str) -> Option<WindowId> {
+        self.scratchpads.get(name).cloned()
+    }
+
+    /// Returns the name of the scratchpad the window belongs to.
+    pub fn scratchpad_name(&self, window_id: &WindowId) -> Option<&str> {
+        self.scratchpads
+            .iter()
+            .find(|(_, w)| *w == window_id)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Returns the scratchpad windows that are hidden.
+    pub fn hidden_scratchpads(&self) -> Vec<WindowId> {
+        self.scratchpads
+            .values()
+            .filter(|w| self.status(w) == Some(Status::Scratchpad))
+            .cloned()
+            .collect()
+    }
+
+    /// Shows the named scratchpad as a floating window of the focused
+    /// workspace and focuses it, or hides it if it is already shown there.
+    ///
+    /// Returns whether the scratchpad is shown, or `None` if it has no window.
+    pub fn toggle_scratchpad(&mut self, name: &str) -> Option<bool> {
+        let window_id = self.scratchpad(name)?;
+        let focused = self.focused_workspace();
+        match self.workspace_of(&window_id) {
+            Some(ix) if ix == focused => {
+                self.workspaces[ix].remove(&window_id);
+                self.windows_status.insert(window_id, Status::Scratchpad);
+                Some(false)
+            }
+            shown => {
+                if let Some(ix) = shown {
+                    self.workspaces[ix].remove(&window_id);
+                }
+                self.workspaces[focused].add_floating(window_id);
+                self.windows_status.insert(window_id, Status::Floating);
+                Some(true)
+            }
+        }
+    }
+
+    /// Moves the shown scratchpads to the focused workspace, so that they
+    /// follow the focus around.
+    fn bring_scratchpads(&mut self) {
+        let focused = self.focused_workspace();
+        for window_id in self.scratchpads.values() {
+            match self.workspaces.iter().position(|w| w.contains(window_id)) {
+                Some(ix) if ix != focused => {
+                    let focus = self.workspaces[ix].focused() == Some(*window_id);
+                    self.workspaces[ix].remove(window_id);
+                    self.workspaces[focused].floats.push(*window_id);
+                    if focus {
+                        self.workspaces[focused].focused = Some(*window_id);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the status of a managed window.
+    pub fn status(&self, window_id: &WindowId) -> Option<Status> {
+        match self.windows_status.get(window_id).cloned() {
+            // The maximized window stays in the tree, as a tiled window.
+            Some(Status::Tiled) => {
+                let maximized = self
+                    .workspaces
+                    .iter()
+                    .any(|workspace| workspace.maximized.as_ref() == Some(window_id));
+                if maximized {
+                    Some(Status::Maximized)
+                } else {
+                    Some(Status::Tiled)
+                }
+            }
+            status => status,
+        }
+    }
+
+    /// Records whether a window asks for attention, returning whether that
+    /// changed.
+    pub fn set_urgent(&mut self, window_id: &WindowId, urgent: bool) -> bool {
+        if urgent {
+            self.urgent.insert(*window_id)
+        } else {
+            self.urgent.remove(window_id)
+        }
+    }
+
+    pub fn is_urgent(&self, window_id: &WindowId) -> bool {
+        self.urgent.contains(window_id)
+    }
+
+    /// Returns every managed window.
+    pub fn windows(&self) -> Vec<WindowId> {
+        self.windows_status.keys().cloned().collect()
+    }
+
+    /// Adds a window to a workspace (or the focused one) and focuses it
+    /// there.
+    pub fn add_window(&mut self, window_id: WindowId, workspace: Option<usize>, floating: bool) {
         if self.contains(&window_id) {
             error!(
                 "Asked to add a window that's already managed: {}",
@@ -101,17 +793,70 @@ impl WindowSet {
             );
             return;
         }
+
+        let ix = workspace.unwrap_or_else(|| self.focused_workspace());
+        let workspace = &mut self.workspaces[ix];
+        if floating {
+            workspace.add_floating(window_id);
+            self.windows_status.insert(window_id, Status::Floating);
+        } else {
+            workspace.add_tiled(window_id);
+            self.windows_status.insert(window_id, Status::Tiled);
+        }
     }
 
+    /// Focuses a window in its workspace, and its screen when the
+    /// workspace is visible.
     pub fn focus(&mut self, window_id: &WindowId) {
-        // TODO
+        if let Some(ix) = self.workspace_of(window_id) {
+            self.workspaces[ix].focus(window_id);
+            if let Some(screen) = self.visible.iter().position(|s| s.workspace == ix) {
+                self.current_screen = screen;
+            }
+        }
     }
 
-    pub fn switch_workspace<'a, S>(&'a mut self, name: S)
-    where
-        S: Into<&'a str>,
-    {
-        // self.connection.update_ewmh_desktops(&self.groups);
+    /// Returns the focused window of the focused workspace.
+    pub fn focused_window(&self) -> Option<WindowId> {
+        self.workspace().focused()
+    }
+
+    /// Shows a workspace on the focused screen, like XMonad's `greedyView`.
+    /// If another screen shows it, the two screens swap workspaces.
+    pub fn greedy_view(&mut self, workspace: usize) {
+        match self.visible.iter().position(|s| s.workspace == workspace) {
+            Some(screen) => self.swap_screens(self.current_screen, screen),
+            None => {
+                self.visible[self.current_screen].workspace = workspace;
+                self.follow_screens();
+            }
+        }
+    }
+
+    /// Shows a workspace, like XMonad's `view`. If a screen shows it already,
+    /// that screen is focused, otherwise it's shown on the focused screen.
+    pub fn view(&mut self, workspace: usize) {
+        match self.visible.iter().position(|s| s.workspace == workspace) {
+            Some(screen) => self.current_screen = screen,
+            None => self.visible[self.current_screen].workspace = workspace,
+        }
+        self.follow_screens();
+    }
+
+    /// Swaps the workspaces shown on two screens. The focus stays on the
+    /// same screen.
+    pub fn swap_screens(&mut self, a: usize, b: usize) {
+        let workspace = self.visible[a].workspace;
+        self.visible[a].workspace = self.visible[b].workspace;
+        self.visible[b].workspace = workspace;
+        self.follow_screens();
+    }
+
+    /// Moves the windows that follow a screen or the focus, after the
+    /// workspaces shown changed.
+    fn follow_screens(&mut self) {
+        self.carry_sticky();
+        self.bring_scratchpads();
     }
 
     /// Get workspace
@@ -122,21 +867,160 @@ impl WindowSet {
             .collect()
     }
 
+    /// Returns the position of the workspace with the given name.
+    pub fn workspace_index(&self, name: &str) -> Option<usize> {
+        self.workspaces.iter().position(|w| w.name == name)
+    }
+
+    /// Returns the position of the workspace holding the window.
+    pub fn workspace_of(&self, window_id: &WindowId) -> Option<usize> {
+        self.workspaces.iter().position(|w| w.contains(window_id))
+    }
+
+    /// Returns whether the workspace is shown on any screen.
+    pub fn is_visible(&self, workspace: usize) -> bool {
+        self.visible.iter().any(|s| s.workspace == workspace)
+    }
+
+    /// Returns the position of a screen, if there is such a screen.
+    pub fn find_screen(&self, target: ScreenTarget) -> Option<usize> {
+        let screens = self.visible.len();
+        let current = self.current_screen;
+        match target {
+            ScreenTarget::Next => Some((current + 1) % screens),
+            ScreenTarget::Previous => Some((current + screens - 1) % screens),
+            ScreenTarget::Index(ix) if ix < screens => Some(ix),
+            ScreenTarget::Index(_) => None,
+            ScreenTarget::Direction(direction) => {
+                let center = |screen: &Screen| {
+                    let (x, y) = screen.info.rectangle().center();
+                    (i64::from(x), i64::from(y))
+                };
+                let (x, y) = center(&self.visible[current]);
+                // The nearest screen whose center lies in that direction,
+                // favouring those straight ahead.
+                self.visible
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(ix, screen)| {
+                        let (dx, dy) = center(screen);
+                        let (dx, dy) = (dx - x, dy - y);
+                        let (ahead, aside) = match direction {
+                            Direction::Left => (-dx, dy.abs()),
+                            Direction::Right => (dx, dy.abs()),
+                            Direction::Up => (-dy, dx.abs()),
+                            Direction::Down => (dy, dx.abs()),
+                        };
+                        if ahead > 0 {
+                            Some((ahead + 2 * aside, ix))
+                        } else {
+                            None
+                        }
+                    })
+                    .min()
+                    .map(|(_, ix)| ix)
+            }
+        }
+    }
+
+    /// Focuses a screen, and the workspace it shows.
+    pub fn focus_screen(&mut self, screen: usize) {
+        self.current_screen = screen;
+        self.follow_screens();
+    }
+
     /// Get focused workspace position. You can get the its name with
     /// `self.get_workspace_names()[ix]`
     pub fn focused_workspace(&self) -> usize {
-        self.visible[self.currentScreen].workspace
+        self.visible[self.current_screen].workspace
     }
 
-    /// Move the focused window from the active group to another named group.
-    ///
-    /// If the other named group does not exist, then the window is
-    /// (unfortunately) lost.
-    pub fn move_focused_to_workspace<'a, S>(&'a mut self, name: S)
-    where
-        S: Into<&'a str>,
-    {
-        // TODO
+    /// Returns the position of the focused screen.
+    pub fn focused_screen(&self) -> usize {
+        self.current_screen
+    }
+
+    /// Returns the position of the screen containing the point, if any.
+    pub fn screen_at(&self, x: u32, y: u32) -> Option<usize> {
+        self.visible.iter().position(|screen| {
+            let info = &screen.info;
+            x >= info.x && x < info.x + info.width && y >= info.y && y < info.y + info.height
+        })
+    }
+
+    /// Returns the focused workspace.
+    pub fn workspace(&self) -> &Workspace {
+        &self.workspaces[self.focused_workspace()]
+    }
+
+    /// Returns the focused workspace mutably.
+    pub fn workspace_mut(&mut self) -> &mut Workspace {
+        let ix = self.focused_workspace();
+        &mut self.workspaces[ix]
+    }
+
+    /// Returns all workspaces, in order.
+    pub fn workspaces(&self) -> &[Workspace] {
+        &self.workspaces
+    }
+
+    /// Returns the screens, in order.
+    pub fn screens(&self) -> &[Screen] {
+        &self.visible
+    }
+
+    /// Moves the focused window to another workspace, where it's focused.
+    /// It keeps floating or being tiled, and stops being sticky. Returns
+    /// the window moved.
+    pub fn move_focused_to_workspace(&mut self, workspace: usize) -> Option<WindowId> {
+        let window_id = self.focused_window()?;
+        let source = self.focused_workspace();
+        if workspace == source || self.scratchpad_name(&window_id).is_some() {
+            return None;
+        }
+        self.sticky.remove(&window_id);
+        let floating = self.status(&window_id) == Some(Status::Floating);
+        self.workspaces[source].remove(&window_id);
+        if floating {
+            self.workspaces[workspace].add_floating(window_id);
+        } else {
+            self.workspaces[workspace].add_tiled(window_id);
+        }
+        Some(window_id)
+    }
+}
+
+/// The part of a `WindowSet` kept over a restart.
+#[derive(Deserialize, Serialize)]
+struct SavedState {
+    current_screen: usize,
+    /// Name of the workspace shown on each screen.
+    screens: Vec<String>,
+    workspaces: Vec<SavedWorkspace>,
+    /// Windows of the scratchpads, by name.
+    #[serde(default)]
+    scratchpads: BTreeMap<String, WindowId>,
+    /// Sticky windows, with the position of their screen.
+    #[serde(default)]
+    sticky: Vec<(WindowId, usize)>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct SavedWorkspace {
+    name: String,
+    layout: BSPLayout<WindowId>,
+    /// Name of the layout in use.
+    #[serde(default)]
+    layout_name: Option<String>,
+    floats: Vec<WindowId>,
+    minimized: Vec<WindowId>,
+    maximized: Option<WindowId>,
+    focused: Option<WindowId>,
+}
+
+impl SavedWorkspace {
+    fn is_empty(&self) -> bool {
+        self.layout.leaves().is_empty() && self.floats.is_empty() && self.minimized.is_empty()
     }
 }
 
@@ -156,9 +1040,23 @@ impl Screen {
         }
     }
 
-    pub fn add_dock(&mut self, window_id: WindowId, reserved: Reserved) {
+    /// Returns the position of the workspace shown on this screen.
+    pub fn workspace(&self) -> usize {
+        self.workspace
+    }
+
+    pub fn info(&self) -> &ScreenInfo {
+        &self.info
+    }
+
+    pub fn docks(&self) -> &[Dock] {
+        &self.docks
+    }
+
+    pub fn add_dock(&mut self, window_id: WindowId, strut: Strut, reserved: Reserved) {
         self.docks.push(Dock {
             window_id,
+            strut,
             reserved,
         });
     }
@@ -170,7 +1068,7 @@ impl Screen {
 
     /// Figure out the usable area of the screen based on the STRUT_PARTIAL of
     /// all docks.
-    pub fn viewport(&self, screen_width: u32, screen_height: u32) -> Rectangle {
+    pub fn viewport(&self) -> Rectangle {
         let (left, right, top, bottom) = self
             .docks
             .iter()
@@ -185,10 +1083,10 @@ impl Screen {
                 )
             });
         let viewport = Rectangle {
-            x: left,
-            y: top,
-            width: screen_width - left - right,
-            height: screen_height - top - bottom,
+            x: self.info.x + left,
+            y: self.info.y + top,
+            width: self.info.width.saturating_sub(left + right),
+            height: self.info.height.saturating_sub(top + bottom),
         };
         debug!("Calculated Viewport as {:?}", viewport);
         viewport
@@ -197,10 +1095,22 @@ impl Screen {
 
 pub struct Dock {
     window_id: WindowId,
+    strut: Strut,
+    /// The space reserved on the screen the dock belongs to.
     reserved: Reserved,
 }
 
-#[derive(Default)]
+impl Dock {
+    pub fn window_id(&self) -> WindowId {
+        self.window_id
+    }
+
+    /// Returns the space reserved at each edge of the screen.
+    pub fn reserved(&self) -> &Reserved {
+        &self.reserved
+    }
+}
+
 pub struct Workspace {
     /// Workspace name
     name: String,
@@ -211,21 +1121,743 @@ pub struct Workspace {
     minimized: Vec<WindowId>,
     /// Just one window maximized?
     maximized: Option<WindowId>,
-    /// Focused windows into the layout
-    focused: Option<usize>,
+    /// Focused window, either tiled or floating
+    focused: Option<WindowId>,
     /// Tiled windows
-    layout: BSPLayout<WindowId>,
+    tree: BSPLayout<WindowId>,
+    /// Layouts to choose from, never empty
+    layouts: Vec<Box<dyn Layout>>,
+    /// Position of the layout in use
+    current_layout: usize,
 }
 
 impl Workspace {
-    pub fn new(workspace_config: WorkspaceConfig) -> Self {
+    /// Creates an empty workspace using the configured layout, or the first
+    /// one if there is no layout by that name.
+    pub fn new(workspace_config: WorkspaceConfig, layouts: &[Box<dyn Layout>]) -> Self {
+        let current_layout = layouts
+            .iter()
+            .position(|layout| layout.name() == workspace_config.layout)
+            .unwrap_or(0);
         Self {
             name: workspace_config.name,
             floats: vec![],
             minimized: vec![],
             maximized: None,
             focused: None,
-            layout: BSPLayout::empty(),
+            tree: BSPLayout::empty(),
+            layouts: layouts.to_vec(),
+            current_layout,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Adds a window into the tree and focuses it. If a window is maximized,
+    /// the new one takes its place.
+    pub fn add_tiled(&mut self, window_id: WindowId) {
+        self.tree.push(window_id);
+        self.focused = Some(window_id);
+        if self.maximized.is_some() {
+            self.maximized = Some(window_id);
+        }
+    }
+
+    /// Adds a floating window and focuses it.
+    pub fn add_floating(&mut self, window_id: WindowId) {
+        self.floats.push(window_id);
+        self.focused = Some(window_id);
+    }
+
+    /// Removes a window, moving the focus to the next one if it was focused.
+    pub fn remove(&mut self, window_id: &WindowId) {
+        if self.focused.as_ref() == Some(window_id) {
+            self.focus_next();
+            if self.focused.as_ref() == Some(window_id) {
+                self.focused = None;
+            }
+        }
+        if let Some(ix) = self.tree.find_leaf(window_id) {
+            self.tree.remove(ix);
+        }
+        self.floats.retain(|w| w != window_id);
+        self.minimized.retain(|w| w != window_id);
+        if self.maximized.as_ref() == Some(window_id) {
+            // Keep showing a single window, the focused one if it's tiled.
+            let tiled = self.tiled();
+            self.maximized = self
+                .focused
+                .filter(|w| tiled.contains(w))
+                .or_else(|| tiled.first().cloned());
+        }
+    }
+
+    /// Moves a window to the minimized windows.
+    pub fn minimize(&mut self, window_id: &WindowId) {
+        if self.contains(window_id) {
+            self.remove(window_id);
+            self.minimized.push(*window_id);
+        }
+    }
+
+    pub fn contains(&self, window_id: &WindowId) -> bool {
+        self.tree.find_leaf(window_id).is_some() || self.floats.contains(window_id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows().is_empty()
+    }
+
+    /// Returns the tiled windows in order, followed by the floating ones.
+    pub fn windows(&self) -> Vec<WindowId> {
+        self.tree
+            .leaves()
+            .into_iter()
+            .map(|(_, w)| *w)
+            .chain(self.floats.iter().cloned())
+            .collect()
+    }
+
+    pub fn floats(&self) -> &[WindowId] {
+        &self.floats
+    }
+
+    pub fn minimized(&self) -> &[WindowId] {
+        &self.minimized
+    }
+
+    pub fn maximized(&self) -> Option<WindowId> {
+        self.maximized
+    }
+
+    /// Returns the tree of tiled windows.
+    pub fn tree(&self) -> &BSPLayout<WindowId> {
+        &self.tree
+    }
+
+    /// Returns the layout in use.
+    pub fn layout(&self) -> &dyn Layout {
+        &*self.layouts[self.current_layout]
+    }
+
+    pub fn layout_mut(&mut self) -> &mut dyn Layout {
+        &mut *self.layouts[self.current_layout]
+    }
+
+    /// Switches to the next layout, wrapping around.
+    pub fn next_layout(&mut self) {
+        self.current_layout = (self.current_layout + 1) % self.layouts.len();
+    }
+
+    /// Switches to the named layout, returning false if there is none.
+    pub fn set_layout(&mut self, name: &str) -> bool {
+        match self.layouts.iter().position(|layout| layout.name() == name) {
+            Some(ix) => {
+                self.current_layout = ix;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn focused(&self) -> Option<WindowId> {
+        self.focused
+    }
+
+    pub fn focus(&mut self, window_id: &WindowId) {
+        if self.contains(window_id) {
+            self.focused = Some(*window_id);
+            if self.maximized.is_some() && self.tree.find_leaf(window_id).is_some() {
+                self.maximized = Some(*window_id);
+            }
         }
     }
+
+    /// Shows the focused tiled window over the whole viewport, hiding the
+    /// rest of the tree, or goes back to showing the tree. The tree itself is
+    /// left untouched.
+    pub fn toggle_maximized(&mut self) {
+        self.maximized = match self.maximized {
+            Some(_) => None,
+            None => self.focused.filter(|w| self.tree.find_leaf(w).is_some()),
+        };
+    }
+
+    /// Returns the tiled windows, in the tree's order.
+    pub fn tiled(&self) -> Vec<WindowId> {
+        self.tree.leaves().into_iter().map(|(_, w)| *w).collect()
+    }
+
+    /// Moves the focus to the next window, wrapping around.
+    pub fn focus_next(&mut self) {
+        self.focus_offset(1);
+    }
+
+    /// Moves the focus to the previous window, wrapping around.
+    pub fn focus_previous(&mut self) {
+        let len = if self.maximized.is_some() {
+            self.tiled().len()
+        } else {
+            self.windows().len()
+        };
+        self.focus_offset(len.saturating_sub(1));
+    }
+
+    fn focus_offset(&mut self, offset: usize) {
+        // While a window is maximized, cycle through the tiled windows to
+        // change which one is shown.
+        let windows = if self.maximized.is_some() {
+            self.tiled()
+        } else {
+            self.windows()
+        };
+        if windows.is_empty() {
+            return;
+        }
+        let position = self
+            .focused
+            .and_then(|f| windows.iter().position(|w| *w == f))
+            .unwrap_or(0);
+        let focused = windows[(position + offset) % windows.len()];
+        self.focused = Some(focused);
+        if self.maximized.is_some() {
+            self.maximized = Some(focused);
+        }
+    }
+
+    /// Swaps the focused tiled window with the next one in the tree.
+    pub fn shuffle_next(&mut self) {
+        self.shuffle_offset(1);
+    }
+
+    /// Swaps the focused tiled window with the previous one in the tree.
+    pub fn shuffle_previous(&mut self) {
+        let len = self.tree.leaves().len();
+        self.shuffle_offset(len.saturating_sub(1));
+    }
+
+    fn shuffle_offset(&mut self, offset: usize) {
+        let leaves: Vec<usize> = self.tree.leaves().into_iter().map(|(ix, _)| ix).collect();
+        let focused = match self.focused.and_then(|f| self.tree.find_leaf(&f)) {
+            Some(focused) => focused,
+            None => return,
+        };
+        let position = leaves.iter().position(|ix| *ix == focused).unwrap();
+        let other = leaves[(position + offset) % leaves.len()];
+        self.tree.interchange_leaf(focused, other);
+    }
+
+    /// Returns where the layout places each tiled window in the viewport,
+    /// in the tree's order. A maximized window takes the whole viewport and
+    /// hides the others.
+    pub fn placements(&self, viewport: Rectangle) -> Vec<Placement> {
+        let placed = match self.maximized {
+            Some(maximized) => vec![Placement::shown(maximized, viewport)],
+            None => {
+                let tiled = Tiled::new(&self.tree, self.focused);
+                self.layout().layout(viewport, &tiled)
+            }
+        };
+        // Keep a single placement per window, hiding those left out.
+        self.tiled()
+            .into_iter()
+            .map(|window_id| {
+                placed
+                    .iter()
+                    .find(|placement| placement.window_id == window_id)
+                    .cloned()
+                    .unwrap_or_else(|| Placement::hidden(window_id))
+            })
+            .collect()
+    }
+
+    /// Returns the tab bar the layout draws in the viewport, if any. There
+    /// is none while a window is maximized.
+    pub fn tab_bar(&self, viewport: Rectangle) -> Option<TabBar> {
+        if self.maximized.is_some() {
+            return None;
+        }
+        let tiled = Tiled::new(&self.tree, self.focused);
+        self.layout().tab_bar(viewport, &tiled)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::{ScreenTarget, Status, WindowSet};
+    use crate::config::WorkspaceConfig;
+    use crate::layout::test::{rectangle, shown as visible};
+    use crate::layout::{self, Placement};
+    use crate::utils::{Direction, Rectangle, ScreenInfo, Strut};
+    use crate::x::WindowId;
+
+    fn window(id: u32) -> WindowId {
+        serde_json::from_value(serde_json::json!(id)).unwrap()
+    }
+
+    fn hidden(placements: Vec<Placement>) -> Vec<WindowId> {
+        placements
+            .into_iter()
+            .filter(|placement| !placement.visible)
+            .map(|placement| placement.window_id)
+            .collect()
+    }
+
+    fn window_set(names: &[&str]) -> WindowSet {
+        let workspaces = names.iter().map(|name| WorkspaceConfig::new(*name)).collect();
+        WindowSet::new(workspaces, layout::defaults(), vec![ScreenInfo::default()])
+    }
+
+    #[test]
+    fn test_save_and_restore() {
+        let mut original = window_set(&["web", "code"]);
+        original.add_window(window(1), Some(0), false);
+        original.add_window(window(2), Some(0), false);
+        original.add_window(window(3), Some(0), false);
+        original.add_window(window(4), Some(1), true);
+        original.focus(&window(2));
+        let state = original.save().unwrap();
+
+        // The configuration changed: "web" is gone, "mail" is new.
+        let mut restored = window_set(&["mail", "code", "web"]);
+        let windows = restored.restore(&state, |w| *w != window(3)).unwrap();
+        assert_eq!(windows.len(), 3);
+        assert!(!restored.contains(&window(3)));
+        assert_eq!(restored.workspace_of(&window(1)), Some(2));
+        assert_eq!(restored.workspace_of(&window(4)), Some(1));
+        assert_eq!(restored.status(&window(4)), Some(Status::Floating));
+        assert_eq!(restored.workspaces()[2].windows(), vec![window(1), window(2)]);
+        assert_eq!(restored.focused_workspace(), 2);
+        assert_eq!(restored.focused_window(), Some(window(2)));
+
+        // Workspaces added at runtime come back if they had windows.
+        let mut added = window_set(&["code"]);
+        added.restore(&state, |_| true).unwrap();
+        assert_eq!(added.get_workspace_names(), vec!["code", "web"]);
+        assert_eq!(added.workspace_of(&window(1)), Some(1));
+
+        let mut other = window_set(&["web"]);
+        assert!(other.restore("not json", |_| true).is_err());
+    }
+
+    #[test]
+    fn test_scratchpads() {
+        let mut set = window_set(&["web", "code"]);
+        set.add_window(window(1), None, false);
+        assert_eq!(set.toggle_scratchpad("term"), None);
+        assert!(set.add_scratchpad(window(2), "term"));
+        assert!(!set.add_scratchpad(window(3), "term"));
+        assert_eq!(set.status(&window(2)), Some(Status::Scratchpad));
+        assert_eq!(set.hidden_scratchpads(), vec![window(2)]);
+
+        assert_eq!(set.toggle_scratchpad("term"), Some(true));
+        assert_eq!(set.status(&window(2)), Some(Status::Floating));
+        assert_eq!(set.focused_window(), Some(window(2)));
+        assert_eq!(set.scratchpad_name(&window(2)), Some("term"));
+        assert!(set.hidden_scratchpads().is_empty());
+
+        // It is kept over a restart, shown or hidden.
+        let mut restored = window_set(&["web", "code"]);
+        restored.restore(&set.save().unwrap(), |_| true).unwrap();
+        assert_eq!(restored.scratchpad("term"), Some(window(2)));
+        assert_eq!(restored.status(&window(2)), Some(Status::Floating));
+
+        assert_eq!(set.toggle_scratchpad("term"), Some(false));
+        assert_eq!(set.status(&window(2)), Some(Status::Scratchpad));
+        assert_eq!(set.workspace_of(&window(2)), None);
+        assert_eq!(set.focused_window(), Some(window(1)));
+
+        set.remove_window(&window(2));
+        assert_eq!(set.scratchpad("term"), None);
+        assert!(set.add_scratchpad(window(3), "term"));
+    }
+
+    #[test]
+    fn test_minimize() {
+        let mut set = window_set(&["web", "code"]);
+        set.add_window(window(1), Some(0), false);
+        set.add_window(window(2), Some(0), false);
+        set.add_window(window(3), Some(1), true);
+        set.add_window(window(4), Some(1), false);
+
+        assert!(set.minimize(&window(2)));
+        assert!(!set.minimize(&window(2)));
+        assert!(set.minimize(&window(3)));
+        assert!(set.minimize(&window(1)));
+        assert_eq!(set.status(&window(2)), Some(Status::Minimized));
+        assert_eq!(set.focused_window(), None);
+        assert!(set.workspace().is_empty());
+        assert_eq!(set.minimized_windows(), vec![window(1), window(2), window(3)]);
+
+        // It comes back tiled, in the focused workspace.
+        assert!(set.unminimize(&window(3)));
+        assert!(!set.unminimize(&window(4)));
+        assert_eq!(set.status(&window(3)), Some(Status::Tiled));
+        assert_eq!(set.workspace_of(&window(3)), Some(0));
+        assert_eq!(set.focused_window(), Some(window(3)));
+        assert!(set.workspaces()[1].minimized().is_empty());
+
+        set.remove_window(&window(2));
+        assert_eq!(set.minimized_windows(), vec![window(1)]);
+    }
+
+    #[test]
+    fn test_maximize() {
+        let viewport = Rectangle {
+            x: 0,
+            y: 0,
+            width: 100,
+            height: 100,
+        };
+        let mut set = window_set(&["web"]);
+        for id in 1..=3 {
+            set.add_window(window(id), None, false);
+        }
+        set.add_window(window(4), None, true);
+        set.focus(&window(2));
+        let tree = visible(set.workspace().placements(viewport));
+
+        set.workspace_mut().toggle_maximized();
+        assert_eq!(set.status(&window(2)), Some(Status::Maximized));
+        assert_eq!(visible(set.workspace().placements(viewport)), vec![(window(2), viewport)]);
+        assert_eq!(hidden(set.workspace().placements(viewport)).len(), 2);
+        assert!(!hidden(set.workspace().placements(viewport)).contains(&window(2)));
+
+        // Cycling the focus changes which window is shown, in the tree's
+        // order, skipping the floating one.
+        let tiled = &set.workspace().windows()[..3];
+        let shown = |position: usize| Some(tiled[position % 3]);
+        let position = tiled.iter().position(|w| *w == window(2)).unwrap();
+        set.workspace_mut().focus_next();
+        assert_eq!(set.workspace().maximized(), shown(position + 1));
+        set.workspace_mut().focus_next();
+        assert_eq!(set.workspace().maximized(), shown(position + 2));
+        set.workspace_mut().focus_previous();
+        assert_eq!(set.workspace().maximized(), shown(position + 1));
+        assert_eq!(set.status(&window(2)), Some(Status::Tiled));
+
+        // Toggling back restores the tree exactly.
+        set.workspace_mut().toggle_maximized();
+        assert!(hidden(set.workspace().placements(viewport)).is_empty());
+        assert_eq!(visible(set.workspace().placements(viewport)), tree);
+
+        // New windows take the maximized window's place, which another
+        // window takes when they go.
+        set.workspace_mut().toggle_maximized();
+        set.add_window(window(5), None, false);
+        assert_eq!(set.workspace().maximized(), Some(window(5)));
+        set.remove_window(&window(5));
+        assert!(set.workspace().maximized().is_some());
+    }
+
+    #[test]
+    fn test_layouts() {
+        let mut set = window_set(&["web"]);
+        set.add_window(window(1), None, false);
+        set.add_window(window(2), None, false);
+        set.add_window(window(3), None, true);
+        set.focus(&window(2));
+        let viewport = Rectangle {
+            x: 0,
+            y: 0,
+            width: 100,
+            height: 90,
+        };
+        assert_eq!(set.workspace().layout().name(), "bsp");
+        let tree = visible(set.workspace().placements(viewport));
+        assert_eq!(tree.len(), 2);
+
+        set.workspace_mut().next_layout();
+        assert_eq!(set.workspace().layout().name(), "tall");
+        assert!(set.workspace_mut().set_layout("monocle"));
+        assert_eq!(visible(set.workspace().placements(viewport)), vec![(window(2), viewport)]);
+        assert_eq!(hidden(set.workspace().placements(viewport)), vec![window(1)]);
+        // The first tiled window is shown while a floating one is focused.
+        set.focus(&window(3));
+        assert_eq!(visible(set.workspace().placements(viewport)).len(), 1);
+
+        assert!(set.workspace_mut().set_layout("tiled"));
+        let rectangles = visible(set.workspace().placements(viewport));
+        assert_eq!(rectangles.len(), 2);
+        assert_eq!(rectangles[1].1.y, 45);
+        assert!(!set.workspace_mut().set_layout("nope"));
+        assert_eq!(set.workspace().tab_bar(viewport), None);
+
+        // Maximizing a window hides the tab bar.
+        assert!(set.workspace_mut().set_layout("tabbed"));
+        assert_eq!(set.workspace().tab_bar(viewport).unwrap().tabs.len(), 2);
+        set.focus(&window(2));
+        set.workspace_mut().toggle_maximized();
+        assert_eq!(set.workspace().tab_bar(viewport), None);
+        set.workspace_mut().toggle_maximized();
+
+        // Switching layouts keeps the windows and the tree.
+        assert!(set.workspace_mut().set_layout("bsp"));
+        assert_eq!(visible(set.workspace().placements(viewport)), tree);
+        assert_eq!(set.workspace().windows().len(), 3);
+    }
+
+    #[test]
+    fn test_sticky() {
+        let mut set = window_set(&["web", "code"]);
+        set.add_window(window(1), Some(0), false);
+        set.add_window(window(2), Some(0), false);
+        assert!(set.set_sticky(&window(1), true));
+        assert!(!set.set_sticky(&window(1), true));
+        assert!(set.is_sticky(&window(1)));
+        assert_eq!(set.status(&window(1)), Some(Status::Floating));
+        assert_eq!(set.workspace().floats(), &[window(1)]);
+        assert_eq!(set.workspace().windows().len(), 2);
+
+        // It follows its screen to the other workspace.
+        set.visible[0].workspace = 1;
+        set.carry_sticky();
+        assert_eq!(set.workspace_of(&window(1)), Some(1));
+        assert_eq!(set.workspace_of(&window(2)), Some(0));
+
+        let mut restored = window_set(&["web", "code"]);
+        restored.restore(&set.save().unwrap(), |_| true).unwrap();
+        assert!(restored.is_sticky(&window(1)));
+
+        assert!(set.set_sticky(&window(1), false));
+        set.visible[0].workspace = 0;
+        set.carry_sticky();
+        assert_eq!(set.workspace_of(&window(1)), Some(1));
+
+        assert!(set.set_sticky(&window(1), true));
+        assert!(set.minimize(&window(1)));
+        assert!(!set.is_sticky(&window(1)));
+    }
+
+    #[test]
+    fn test_floating() {
+        let mut set = window_set(&["web"]);
+        set.add_window(window(1), None, false);
+        set.add_window(window(2), None, false);
+        set.add_window(window(3), None, true);
+        set.focus(&window(1));
+        assert!(set.set_floating(&window(1), true));
+        assert!(!set.set_floating(&window(1), true));
+        assert_eq!(set.status(&window(1)), Some(Status::Floating));
+        assert_eq!(set.workspace().tiled(), vec![window(2)]);
+        assert_eq!(set.focused_window(), Some(window(1)));
+
+        assert!(set.set_sticky(&window(3), true));
+        assert!(set.set_floating(&window(3), false));
+        assert_eq!(set.status(&window(3)), Some(Status::Tiled));
+        assert!(!set.is_sticky(&window(3)));
+        assert_eq!(set.focused_window(), Some(window(3)));
+        assert_eq!(set.workspace().floats(), &[window(1)]);
+    }
+
+    fn two_screens(names: &[&str]) -> WindowSet {
+        let workspaces = names.iter().map(|name| WorkspaceConfig::new(*name)).collect();
+        let screen = |id: u8| ScreenInfo {
+            id,
+            name: format!("screen {}", id),
+            x: u32::from(id) * 100,
+            y: 0,
+            width: 100,
+            height: 100,
+        };
+        WindowSet::new(workspaces, layout::defaults(), vec![screen(0), screen(1)])
+    }
+
+    #[test]
+    fn test_docks() {
+        let mut set = two_screens(&["a", "b"]);
+        // A bar at the top of the right screen.
+        let strut = Strut {
+            top: 20,
+            top_start_x: 100,
+            top_end_x: 199,
+            ..Strut::default()
+        };
+        set.add_dock(window(1), 1, strut);
+        assert_eq!(set.screens()[0].viewport(), rectangle(0, 0, 100, 100));
+        assert_eq!(set.screens()[1].viewport(), rectangle(100, 20, 100, 80));
+        assert!(set.screens()[0].docks().is_empty());
+
+        // A bar along the right edge of the left screen, which is not the
+        // root window's.
+        let strut = Strut {
+            right: 110,
+            ..Strut::default()
+        };
+        set.add_dock(window(2), 0, strut);
+        assert_eq!(set.screens()[0].viewport(), rectangle(0, 0, 90, 100));
+        assert_eq!(set.screens()[1].viewport(), rectangle(100, 20, 0, 80));
+
+        set.remove_window(&window(2));
+        set.remove_window(&window(1));
+        assert!(set.screens().iter().all(|s| s.docks().is_empty()));
+    }
+
+    #[test]
+    fn test_set_screens() {
+        let mut set = two_screens(&["a", "b", "c"]);
+        let strut = Strut {
+            top: 20,
+            ..Strut::default()
+        };
+        set.add_dock(window(1), 1, strut);
+        set.focus_screen(1);
+        let mut screens: Vec<_> = set.screens().iter().map(|s| s.info().clone()).collect();
+
+        // The second screen is unplugged: the focus and the dock go to the
+        // first one.
+        let second = screens.pop().unwrap();
+        let (added, removed) = set.set_screens(screens.clone());
+        assert!(added.is_empty());
+        assert_eq!(removed, vec!["screen 1"]);
+        assert_eq!(set.focused_screen(), 0);
+        assert_eq!(set.screens()[0].viewport(), rectangle(0, 20, 100, 80));
+
+        // It comes back, showing a hidden workspace.
+        screens.push(second);
+        let (added, removed) = set.set_screens(screens);
+        assert_eq!(added, vec!["screen 1"]);
+        assert!(removed.is_empty());
+        assert_eq!(set.screens()[0].workspace(), 0);
+        assert_eq!(set.screens()[1].workspace(), 1);
+        assert_eq!(set.screens()[1].viewport(), rectangle(100, 20, 100, 80));
+    }
+
+    #[test]
+    fn test_greedy_view_and_view() {
+        let mut set = two_screens(&["a", "b", "c"]);
+        set.add_window(window(1), Some(0), false);
+        set.add_window(window(2), Some(0), true);
+        set.set_sticky(&window(2), true);
+        assert_eq!(set.screens()[1].workspace(), 1);
+
+        // "c" is hidden: it's shown on the focused screen, and the sticky
+        // window follows.
+        set.greedy_view(2);
+        assert_eq!(set.focused_workspace(), 2);
+        assert!(!set.is_visible(0));
+        assert_eq!(set.workspace_of(&window(2)), Some(2));
+        assert_eq!(set.workspace_of(&window(1)), Some(0));
+
+        // "b" is on the other screen: greedy view swaps, view focuses it.
+        set.greedy_view(1);
+        assert_eq!(set.focused_screen(), 0);
+        assert_eq!(set.screens()[1].workspace(), 2);
+        assert_eq!(set.workspace_of(&window(2)), Some(1));
+        set.view(2);
+        assert_eq!(set.focused_screen(), 1);
+        assert_eq!(set.focused_workspace(), 2);
+
+        set.swap_screens(0, 1);
+        assert_eq!(set.focused_screen(), 1);
+        assert_eq!(set.focused_workspace(), 1);
+        assert_eq!(set.screens()[0].workspace(), 2);
+        assert_eq!(set.workspace_of(&window(2)), Some(2));
+    }
+
+    #[test]
+    fn test_dynamic_workspaces() {
+        let mut set = two_screens(&["a", "b", "c"]);
+        set.add_window(window(1), Some(1), false);
+        set.add_window(window(2), Some(2), false);
+        set.add_window(window(3), Some(2), true);
+        assert_eq!(set.add_workspace(WorkspaceConfig::new("d")).unwrap(), 3);
+        assert!(set.add_workspace(WorkspaceConfig::new("a")).is_err());
+        assert!(set.rename_workspace(3, "c").is_err());
+        set.rename_workspace(3, "mail").unwrap();
+        assert_eq!(set.get_workspace_names(), vec!["a", "b", "c", "mail"]);
+
+        // Screen 1 shows "b", which follows it when it moves.
+        set.move_workspace(1, 3);
+        assert_eq!(set.get_workspace_names(), vec!["a", "c", "mail", "b"]);
+        assert_eq!(set.screens()[1].workspace(), 3);
+        set.move_workspace(3, 0);
+        assert_eq!(set.get_workspace_names(), vec!["b", "a", "c", "mail"]);
+        assert_eq!(set.screens()[0].workspace(), 1);
+        assert_eq!(set.screens()[1].workspace(), 0);
+
+        // "c" has windows.
+        assert!(set.remove_workspace(2, None).is_err());
+        assert!(set.remove_workspace(2, Some(2)).is_err());
+        set.remove_workspace(2, Some(0)).unwrap();
+        assert_eq!(set.get_workspace_names(), vec!["b", "a", "mail"]);
+        assert_eq!(set.workspace_of(&window(2)), Some(0));
+        assert_eq!(set.workspace_of(&window(3)), Some(0));
+        assert_eq!(set.workspaces()[0].floats(), &[window(3)]);
+        assert_eq!(set.workspaces()[0].focused(), Some(window(1)));
+
+        // "a" is shown on screen 0, which shows "mail" instead.
+        set.remove_workspace(1, None).unwrap();
+        assert_eq!(set.get_workspace_names(), vec!["b", "mail"]);
+        assert_eq!(set.screens()[0].workspace(), 1);
+        assert_eq!(set.screens()[1].workspace(), 0);
+        assert!(set.remove_workspace(1, None).is_err());
+    }
+
+    #[test]
+    fn test_set_workspaces_config() {
+        let config = |names: &[&str]| -> Vec<WorkspaceConfig> {
+            names.iter().map(|name| WorkspaceConfig::new(*name)).collect()
+        };
+        let mut set = two_screens(&["a", "b", "c", "d"]);
+        set.add_window(window(1), Some(2), false);
+        set.rename_workspace(2, "mail").unwrap();
+        set.move_workspace(1, 3);
+        set.add_workspace(WorkspaceConfig::new("e")).unwrap();
+        set.add_workspace(WorkspaceConfig::new("chat")).unwrap();
+        assert_eq!(set.get_workspace_names(), vec!["a", "mail", "d", "b", "e", "chat"]);
+
+        // "mail" has a window and "b" is shown on screen 1, "d" and "chat"
+        // are dropped, "e" gets configured, and "c" is added back.
+        let mut workspaces = config(&["a", "b", "c", "e"]);
+        workspaces[3].layout = "monocle".to_owned();
+        set.set_workspaces_config(workspaces);
+        assert_eq!(set.get_workspace_names(), vec!["a", "mail", "b", "e", "c"]);
+        assert_eq!(set.workspace_of(&window(1)), Some(1));
+        assert_eq!(set.screens()[0].workspace(), 0);
+        assert_eq!(set.screens()[1].workspace(), 2);
+        assert_eq!(set.workspaces()[3].layout().name(), "monocle");
+
+        // A configured layout applies to existing workspaces too.
+        let mut workspaces = config(&["a", "b", "c", "e"]);
+        workspaces[0].layout = "monocle".to_owned();
+        set.set_workspaces_config(workspaces);
+        assert_eq!(set.get_workspace_names(), vec!["a", "mail", "b", "e", "c"]);
+        assert_eq!(set.workspaces()[0].layout().name(), "monocle");
+    }
+
+    #[test]
+    fn test_find_screen() {
+        let set = two_screens(&["a", "b"]);
+        let left = ScreenTarget::Direction(Direction::Left);
+        assert_eq!("left".parse::<ScreenTarget>().unwrap(), left);
+        assert!("sideways".parse::<ScreenTarget>().is_err());
+        assert_eq!(set.find_screen(ScreenTarget::Next), Some(1));
+        assert_eq!(set.find_screen(ScreenTarget::Previous), Some(1));
+        assert_eq!(set.find_screen(ScreenTarget::Index(1)), Some(1));
+        assert_eq!(set.find_screen(ScreenTarget::Index(2)), None);
+        assert_eq!(set.find_screen(ScreenTarget::Direction(Direction::Right)), Some(1));
+        assert_eq!(set.find_screen(left), None);
+        assert_eq!(set.find_screen(ScreenTarget::Direction(Direction::Up)), None);
+        assert_eq!(set.screen_at(150, 50), Some(1));
+    }
+
+    #[test]
+    fn test_move_focused_to_workspace() {
+        let mut set = window_set(&["a", "b"]);
+        set.add_window(window(1), None, false);
+        set.add_window(window(2), None, true);
+        set.set_sticky(&window(2), true);
+        assert_eq!(set.move_focused_to_workspace(1), Some(window(2)));
+        assert!(!set.is_sticky(&window(2)));
+        assert_eq!(set.workspaces()[1].floats(), &[window(2)]);
+        assert_eq!(set.focused_window(), Some(window(1)));
+        assert_eq!(set.move_focused_to_workspace(1), Some(window(1)));
+        assert_eq!(set.workspaces()[1].tree().leaves().len(), 1);
+        assert_eq!(set.move_focused_to_workspace(1), None);
+        assert_eq!(set.workspaces()[1].focused(), Some(window(1)));
+    }
 }
@@ -1,23 +1,29 @@
+use std::cell::RefCell;
+use std::cmp;
 use std::collections::HashMap;
 use std::fmt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::rc::Rc;
 
-use log::{error, info, log};
+use log::{error, info, log, warn};
+use serde::{Deserialize, Serialize};
 
-use xcb;
+use xcb::randr;
 use xcb_util::keysyms::KeySymbols;
 use xcb_util::{ewmh, icccm};
 
 use crate::errors::*;
 use crate::keys::{KeyCombo, KeyHandlers};
-
-pub use self::ewmh::StrutPartial;
+use crate::utils::{Rectangle, ScreenInfo, Strut};
 
 /// A handle to an X Window.
-#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+///
+/// It is serialized as the X window id.
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash, Deserialize, Serialize)]
 pub struct WindowId(xcb::Window);
 
 impl WindowId {
-    fn to_x(&self) -> xcb::Window {
+    fn to_x(self) -> xcb::Window {
         self.0
     }
 }
@@ -85,7 +91,37 @@ macro_rules! atoms {
     ( $( $name:ident ),+ , ) => (atoms!($( $name ),+);)
 }
 
-atoms!(WM_DELETE_WINDOW, WM_PROTOCOLS,);
+atoms!(
+    WM_DELETE_WINDOW,
+    WM_PROTOCOLS,
+    WM_STATE,
+    WM_CHANGE_STATE,
+    UTF8_STRING,
+    _LANTA_STATE,
+    _NET_STARTUP_ID,
+    _XEMBED_INFO,
+);
+
+/// A core X font, opened by `Connection::draw_tabs()`.
+#[derive(Clone, Copy)]
+struct Font {
+    id: xcb::Font,
+    ascent: u32,
+    descent: u32,
+    /// Width of the widest character.
+    width: u32,
+}
+
+/// A tab drawn by `Connection::draw_tabs()`.
+pub struct Tab<'a> {
+    /// Where the tab goes, relative to the window it is drawn in.
+    pub rectangle: Rectangle,
+    pub title: &'a str,
+    pub background: u32,
+    pub foreground: u32,
+    /// Whether the title is centered rather than on the left.
+    pub centered: bool,
+}
 
 pub struct Connection {
     conn: ewmh::Connection,
@@ -94,6 +130,10 @@ pub struct Connection {
     atoms: InternedAtoms,
     window_type_lookup: HashMap<xcb::Atom, WindowType>,
     window_state_lookup: HashMap<xcb::Atom, WindowState>,
+    /// The fonts opened so far, by name.
+    fonts: RefCell<HashMap<String, Font>>,
+    /// The code of the first RandR event, if the X server has RandR.
+    randr_event: Option<u8>,
 }
 
 impl Connection {
@@ -109,7 +149,17 @@ impl Connection {
             .ok_or("Invalid screen")?
             .root();
 
+        // Don't let the processes we spawn, or the binary we exec on restart,
+        // inherit the connection.
+        unsafe {
+            libc::fcntl(conn.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+
         let atoms = InternedAtoms::new(&conn).or(Err("Failed to intern atoms"))?;
+        let randr_event = conn
+            .get_extension_data(randr::id())
+            .filter(|randr| randr.present())
+            .map(|randr| randr.first_event());
 
         let mut types = HashMap::new();
         types.insert(conn.WM_WINDOW_TYPE_DESKTOP(), WindowType::Desktop);
@@ -154,6 +204,8 @@ impl Connection {
             atoms,
             window_type_lookup: types,
             window_state_lookup: state,
+            fonts: RefCell::new(HashMap::new()),
+            randr_event,
         })
     }
 
@@ -162,7 +214,8 @@ impl Connection {
         Ok(xcb::intern_atom(conn, false, atom_name).get_reply()?.atom())
     }
 
-    fn flush(&self) {
+    /// Sends the queued requests to the X server.
+    pub fn flush(&self) {
         self.conn.flush();
     }
 
@@ -189,6 +242,14 @@ impl Connection {
             .request_check()
             .or(Err("Could not register SUBSTRUCTURE_NOTIFY/REDIRECT"))?;
 
+        // Hear about monitors being plugged, unplugged or rearranged.
+        if self.randr_event.is_some() {
+            let mask = randr::NOTIFY_MASK_SCREEN_CHANGE
+                | randr::NOTIFY_MASK_OUTPUT_CHANGE
+                | randr::NOTIFY_MASK_CRTC_CHANGE;
+            randr::select_input(&self.conn, self.root.to_x(), mask as u16);
+        }
+
         self.enable_grab_pointer(&self.root);
         self.enable_window_key_events(&self.root, key_handlers);
 
@@ -206,6 +267,67 @@ impl Connection {
         ewmh::set_current_desktop(&self.conn, self.screen_idx, focused as u32);
     }
 
+    /// Returns the screens available to lay out windows on: the RandR
+    /// outputs that are connected and enabled, from left to right. Outputs
+    /// showing the same area, such as mirrored ones, make a single screen.
+    ///
+    /// Without RandR, there is a single screen covering the root window.
+    pub fn get_screens(&self) -> Vec<ScreenInfo> {
+        let mut screens = self.get_randr_screens().unwrap_or_default();
+        if screens.is_empty() {
+            let (width, height) = self.get_window_geometry(&self.root);
+            screens.push(ScreenInfo {
+                id: 0,
+                name: "root".to_owned(),
+                x: 0,
+                y: 0,
+                width,
+                height,
+            });
+        }
+        screens
+    }
+
+    fn get_randr_screens(&self) -> Option<Vec<ScreenInfo>> {
+        let resources = randr::get_screen_resources_current(&self.conn, self.root.to_x())
+            .get_reply()
+            .ok()?;
+        let timestamp = resources.config_timestamp();
+        let mut screens: Vec<ScreenInfo> = Vec::new();
+        for output in resources.outputs() {
+            let cookie = randr::get_output_info(&self.conn, *output, timestamp);
+            let output = match cookie.get_reply() {
+                Ok(output) => output,
+                Err(_) => continue,
+            };
+            let connected = u32::from(output.connection()) == randr::CONNECTION_CONNECTED;
+            if !connected || output.crtc() == 0 {
+                continue;
+            }
+            let cookie = randr::get_crtc_info(&self.conn, output.crtc(), timestamp);
+            let crtc = match cookie.get_reply() {
+                Ok(crtc) => crtc,
+                Err(_) => continue,
+            };
+            let screen = ScreenInfo {
+                id: 0,
+                name: String::from_utf8_lossy(output.name()).into_owned(),
+                x: crtc.x().max(0) as u32,
+                y: crtc.y().max(0) as u32,
+                width: u32::from(crtc.width()),
+                height: u32::from(crtc.height()),
+            };
+            if crtc.mode() != 0 && !screens.iter().any(|s| s.rectangle() == screen.rectangle()) {
+                screens.push(screen);
+            }
+        }
+        screens.sort_by_key(|screen| (screen.x, screen.y));
+        for (id, screen) in screens.iter_mut().enumerate() {
+            screen.id = id as u8;
+        }
+        Some(screens)
+    }
+
     pub fn top_level_windows(&self) -> Result<Vec<WindowId>> {
         let windows = xcb::query_tree(&self.conn, self.root.to_x())
             .get_reply()?
@@ -255,10 +377,194 @@ impl Connection {
             .unwrap_or_else(|_| Vec::new())
     }
 
-    pub fn get_strut_partial(&self, window_id: &WindowId) -> Option<StrutPartial> {
+    /// Returns whether a window that already existed when we started should be
+    /// managed: it must be mapped and must not be override-redirect.
+    pub fn should_adopt(&self, window_id: &WindowId) -> bool {
+        xcb::get_window_attributes(&self.conn, window_id.to_x())
+            .get_reply()
+            .map(|reply| {
+                !reply.override_redirect()
+                    && reply.map_state() == xcb::MAP_STATE_VIEWABLE as u8
+            })
+            .unwrap_or(false)
+    }
+
+    /// Returns the class part of the window's ICCCM WM_CLASS property.
+    pub fn get_window_class(&self, window_id: &WindowId) -> Option<String> {
+        icccm::get_wm_class(&self.conn, window_id.to_x())
+            .get_reply()
+            .ok()
+            .map(|reply| reply.class().to_owned())
+    }
+
+    /// Returns the window's title, preferring EWMH's _NET_WM_NAME over ICCCM's
+    /// WM_NAME.
+    pub fn get_window_title(&self, window_id: &WindowId) -> Option<String> {
+        ewmh::get_wm_name(&self.conn, window_id.to_x())
+            .get_reply()
+            .map(|reply| reply.string().to_owned())
+            .or_else(|_| {
+                icccm::get_wm_name(&self.conn, window_id.to_x())
+                    .get_reply()
+                    .map(|reply| reply.name().to_owned())
+            })
+            .ok()
+    }
+
+    /// Returns the pid of the process owning the window, from EWMH's
+    /// _NET_WM_PID.
+    pub fn get_window_pid(&self, window_id: &WindowId) -> Option<u32> {
+        ewmh::get_wm_pid(&self.conn, window_id.to_x())
+            .get_reply()
+            .ok()
+    }
+
+    /// Returns the startup notification id of the window, from
+    /// _NET_STARTUP_ID.
+    pub fn get_window_startup_id(&self, window_id: &WindowId) -> Option<String> {
+        let reply = xcb::get_property(
+            &self.conn,
+            false,
+            window_id.to_x(),
+            self.atoms._NET_STARTUP_ID,
+            self.atoms.UTF8_STRING,
+            0,
+            1024,
+        )
+        .get_reply()
+        .ok()?;
+        if reply.value_len() == 0 {
+            return None;
+        }
+        String::from_utf8(reply.value::<u8>().to_vec()).ok()
+    }
+
+    /// Returns whether the window asks for attention, through the ICCCM
+    /// urgency hint or EWMH's _NET_WM_STATE_DEMANDS_ATTENTION.
+    pub fn is_window_urgent(&self, window_id: &WindowId) -> bool {
+        let urgency_hint = icccm::get_wm_hints(&self.conn, window_id.to_x())
+            .get_reply()
+            .ok()
+            .and_then(|hints| hints.is_urgent())
+            .unwrap_or(false);
+        urgency_hint
+            || self
+                .get_window_states(window_id)
+                .contains(&WindowState::DemandsAttention)
+    }
+
+    /// Returns whether the window is an XEmbed client, such as a tray icon,
+    /// which is embedded by another application rather than framed by us.
+    pub fn is_window_embedded(&self, window_id: &WindowId) -> bool {
+        xcb::get_property(
+            &self.conn,
+            false,
+            window_id.to_x(),
+            self.atoms._XEMBED_INFO,
+            xcb::ATOM_ANY,
+            0,
+            2,
+        )
+        .get_reply()
+        .map(|reply| reply.value_len() > 0)
+        .unwrap_or(false)
+    }
+
+    /// Marks a window as minimized or not: its ICCCM WM_STATE becomes
+    /// Iconic or Normal and _NET_WM_STATE_HIDDEN is added or removed.
+    pub fn set_window_iconic(&self, window_id: &WindowId, iconic: bool) {
+        let state = if iconic {
+            icccm::WM_STATE_ICONIC
+        } else {
+            icccm::WM_STATE_NORMAL
+        } as u32;
+        xcb::change_property(
+            &self.conn,
+            xcb::PROP_MODE_REPLACE as u8,
+            window_id.to_x(),
+            self.atoms.WM_STATE,
+            self.atoms.WM_STATE,
+            32,
+            &[state, xcb::NONE],
+        );
+
+        self.set_window_state(window_id, self.conn.WM_STATE_HIDDEN(), iconic);
+    }
+
+    /// Marks a window as sticky or not: _NET_WM_STATE_STICKY is added or
+    /// removed, and its _NET_WM_DESKTOP becomes 0xFFFFFFFF, meaning all
+    /// desktops, or the given one.
+    pub fn set_window_sticky(&self, window_id: &WindowId, sticky: bool, desktop: usize) {
+        self.set_window_state(window_id, self.conn.WM_STATE_STICKY(), sticky);
+        let desktop = if sticky { 0xFFFF_FFFF } else { desktop as u32 };
+        ewmh::set_wm_desktop(&self.conn, window_id.to_x(), desktop);
+    }
+
+    /// Adds or removes one of the window's _NET_WM_STATE atoms.
+    fn set_window_state(&self, window_id: &WindowId, state: xcb::Atom, set: bool) {
+        let mut states: Vec<xcb::Atom> = ewmh::get_wm_state(&self.conn, window_id.to_x())
+            .get_reply()
+            .map(|reply| reply.atoms().to_vec())
+            .unwrap_or_default();
+        states.retain(|atom| *atom != state);
+        if set {
+            states.push(state);
+        }
+        ewmh::set_wm_state(&self.conn, window_id.to_x(), &states);
+    }
+
+    /// Stores a string on the root window, for the next instance to read
+    /// after a restart.
+    pub fn save_state(&self, state: &str) {
+        xcb::change_property(
+            &self.conn,
+            xcb::PROP_MODE_REPLACE as u8,
+            self.root.to_x(),
+            self.atoms._LANTA_STATE,
+            self.atoms.UTF8_STRING,
+            8,
+            state.as_bytes(),
+        );
+    }
+
+    /// Returns and removes the string stored by `save_state()`, if any.
+    pub fn take_saved_state(&self) -> Option<String> {
+        let reply = xcb::get_property(
+            &self.conn,
+            true,
+            self.root.to_x(),
+            self.atoms._LANTA_STATE,
+            xcb::ATOM_ANY,
+            0,
+            u32::MAX / 4,
+        )
+        .get_reply()
+        .ok()?;
+        if reply.value_len() == 0 {
+            return None;
+        }
+        String::from_utf8(reply.value::<u8>().to_vec()).ok()
+    }
+
+    /// Returns the dock's _NET_WM_STRUT_PARTIAL.
+    pub fn get_strut_partial(&self, window_id: &WindowId) -> Option<Strut> {
         ewmh::get_wm_strut_partial(&self.conn, window_id.to_x())
             .get_reply()
             .ok()
+            .map(|strut| Strut {
+                left: strut.left(),
+                right: strut.right(),
+                top: strut.top(),
+                bottom: strut.bottom(),
+                left_start_y: strut.left_start_y(),
+                left_end_y: strut.left_end_y(),
+                right_start_y: strut.right_start_y(),
+                right_end_y: strut.right_end_y(),
+                top_start_x: strut.top_start_x(),
+                top_end_x: strut.top_end_x(),
+                bottom_start_x: strut.bottom_start_x(),
+                bottom_end_x: strut.bottom_end_x(),
+            })
     }
 
     /// Closes a window.
@@ -306,6 +612,31 @@ impl Connection {
         xcb::configure_window(&self.conn, window_id.to_x(), &values);
     }
 
+    /// Sets the window's position and size from a rectangle.
+    pub fn configure_window_rectangle(&self, window_id: &WindowId, rectangle: &Rectangle) {
+        self.configure_window(
+            window_id,
+            rectangle.x,
+            rectangle.y,
+            rectangle.width,
+            rectangle.height,
+        );
+    }
+
+    /// Sets the window's border width and colour.
+    pub fn set_window_border(&self, window_id: &WindowId, width: u32, color: u32) {
+        let values = [(xcb::CONFIG_WINDOW_BORDER_WIDTH as u16, width)];
+        xcb::configure_window(&self.conn, window_id.to_x(), &values);
+        let values = [(xcb::CW_BORDER_PIXEL, color)];
+        xcb::change_window_attributes(&self.conn, window_id.to_x(), &values);
+    }
+
+    /// Raises the window above its siblings.
+    pub fn raise_window(&self, window_id: &WindowId) {
+        let values = [(xcb::CONFIG_WINDOW_STACK_MODE as u16, xcb::STACK_MODE_ABOVE)];
+        xcb::configure_window(&self.conn, window_id.to_x(), &values);
+    }
+
     /// Get's the window's width and height.
     pub fn get_window_geometry(&self, window_id: &WindowId) -> (u32, u32) {
         let reply = xcb::get_geometry(&self.conn, window_id.to_x())
@@ -315,6 +646,19 @@ impl Connection {
         (u32::from(reply.width()), u32::from(reply.height()))
     }
 
+    /// Returns the window's position, relative to its parent, and size.
+    pub fn get_window_rectangle(&self, window_id: &WindowId) -> Option<Rectangle> {
+        xcb::get_geometry(&self.conn, window_id.to_x())
+            .get_reply()
+            .ok()
+            .map(|reply| Rectangle {
+                x: reply.x().max(0) as u32,
+                y: reply.y().max(0) as u32,
+                width: u32::from(reply.width()),
+                height: u32::from(reply.height()),
+            })
+    }
+
     /// Map a window.
     pub fn map_window(&self, window_id: &WindowId) {
         xcb::map_window(&self.conn, window_id.to_x());
@@ -353,6 +697,16 @@ impl Connection {
         }
     }
 
+    /// Unregisters all key events previously registered on the window.
+    pub fn disable_window_key_events(&self, window_id: &WindowId) {
+        xcb::ungrab_key(
+            &self.conn,
+            xcb::GRAB_ANY as u8,
+            window_id.to_x(),
+            xcb::MOD_MASK_ANY as u16,
+        );
+    }
+
     pub fn enable_grab_pointer(&self, window_id: &WindowId) {
         xcb::grab_pointer(
             &self.conn,
@@ -371,6 +725,38 @@ impl Connection {
         //  xcb::GRAB_MODE_ASYNC as u8, xcb::NONE, xcb::NONE, xcb::BUTTON_INDEX_ANY as u8, xcb::MOD_MASK_ANY as u16);
     }
 
+    /// Moves the pointer to a position on the root window.
+    pub fn warp_pointer(&self, x: u32, y: u32) {
+        xcb::warp_pointer(
+            &self.conn,
+            xcb::NONE,
+            self.root.to_x(),
+            0,
+            0,
+            0,
+            0,
+            x as i16,
+            y as i16,
+        );
+    }
+
+    /// Moves the pointer to the middle of a window.
+    pub fn warp_pointer_to_window(&self, window_id: &WindowId) {
+        if let Some(rectangle) = self.get_window_rectangle(window_id) {
+            xcb::warp_pointer(
+                &self.conn,
+                xcb::NONE,
+                window_id.to_x(),
+                0,
+                0,
+                0,
+                0,
+                (rectangle.width / 2) as i16,
+                (rectangle.height / 2) as i16,
+            );
+        }
+    }
+
     pub fn enable_window_tracking(&self, window_id: &WindowId) {
         let values = [(
             xcb::CW_EVENT_MASK,
@@ -384,7 +770,8 @@ impl Connection {
                 | xcb::EVENT_MASK_BUTTON_4_MOTION
                 | xcb::EVENT_MASK_BUTTON_5_MOTION
                 | xcb::EVENT_MASK_BUTTON_PRESS
-                | xcb::EVENT_MASK_BUTTON_RELEASE,
+                | xcb::EVENT_MASK_BUTTON_RELEASE
+                | xcb::EVENT_MASK_PROPERTY_CHANGE,
         )];
         xcb::change_window_attributes(&self.conn, window_id.to_x(), &values);
     }
@@ -409,8 +796,197 @@ impl Connection {
         ewmh::set_active_window(&self.conn, self.screen_idx, xcb::NONE);
     }
 
-    pub fn get_event_loop(&self) -> EventLoop {
-        EventLoop { connection: self }
+    /// Creates an unmapped window for Lanta to draw in, such as a tab bar.
+    ///
+    /// It is override-redirect, so that we don't try to manage it, and
+    /// reports `Event::Expose` and `Event::ButtonPress`.
+    pub fn create_bar_window(&self) -> WindowId {
+        self.create_window(&[
+            (xcb::CW_OVERRIDE_REDIRECT, 1),
+            (
+                xcb::CW_EVENT_MASK,
+                xcb::EVENT_MASK_EXPOSURE | xcb::EVENT_MASK_BUTTON_PRESS,
+            ),
+        ])
+    }
+
+    /// Creates an unmapped frame, for a client window to be put in with
+    /// `reparent_window()`.
+    ///
+    /// The requests of the window in it are still redirected to us, and it
+    /// reports `Event::Expose`, `Event::ButtonPress` and `Event::EnterNotify`.
+    pub fn create_frame_window(&self) -> WindowId {
+        self.create_window(&[(
+            xcb::CW_EVENT_MASK,
+            xcb::EVENT_MASK_SUBSTRUCTURE_REDIRECT
+                | xcb::EVENT_MASK_EXPOSURE
+                | xcb::EVENT_MASK_BUTTON_PRESS
+                | xcb::EVENT_MASK_ENTER_WINDOW,
+        )])
+    }
+
+    fn create_window(&self, values: &[(u32, u32)]) -> WindowId {
+        let window_id = self.conn.generate_id();
+        xcb::create_window(
+            &self.conn,
+            xcb::COPY_FROM_PARENT as u8,
+            window_id,
+            self.root.to_x(),
+            0,
+            0,
+            1,
+            1,
+            0,
+            xcb::WINDOW_CLASS_INPUT_OUTPUT as u16,
+            xcb::COPY_FROM_PARENT,
+            values,
+        );
+        WindowId(window_id)
+    }
+
+    /// Destroys one of our windows.
+    pub fn destroy_window(&self, window_id: &WindowId) {
+        xcb::destroy_window(&self.conn, window_id.to_x());
+    }
+
+    /// Puts a window into another one, at a position relative to it.
+    ///
+    /// A window put into one of our frames is added to our save-set, so that
+    /// the X server puts it back into the root window if we die. It leaves
+    /// the save-set when put back into the root window.
+    pub fn reparent_window(&self, window_id: &WindowId, parent: &WindowId, x: u32, y: u32) {
+        let mode = if *parent == self.root {
+            xcb::SET_MODE_DELETE
+        } else {
+            xcb::SET_MODE_INSERT
+        };
+        xcb::change_save_set(&self.conn, mode as u8, window_id.to_x());
+        xcb::reparent_window(
+            &self.conn,
+            window_id.to_x(),
+            parent.to_x(),
+            x as i16,
+            y as i16,
+        );
+    }
+
+    /// Tells a window in one of our frames where it is on the root window,
+    /// as ICCCM asks of reparenting window managers: the ConfigureNotify
+    /// events of the X server have coordinates relative to the frame.
+    pub fn send_configure_notify(&self, window_id: &WindowId, rectangle: &Rectangle) {
+        let event = xcb::ConfigureNotifyEvent::new(
+            window_id.to_x(),
+            window_id.to_x(),
+            xcb::NONE,
+            rectangle.x as i16,
+            rectangle.y as i16,
+            rectangle.width as u16,
+            rectangle.height as u16,
+            0,
+            false,
+        );
+        xcb::send_event(
+            &self.conn,
+            false,
+            window_id.to_x(),
+            xcb::EVENT_MASK_STRUCTURE_NOTIFY,
+            &event,
+        );
+    }
+
+    /// Sets EWMH's _NET_FRAME_EXTENTS, the space our frame takes on each side
+    /// of the window: left, right, top and bottom.
+    pub fn set_frame_extents(&self, window_id: &WindowId, extents: [u32; 4]) {
+        let [left, right, top, bottom] = extents;
+        ewmh::set_frame_extents(&self.conn, window_id.to_x(), left, right, top, bottom);
+    }
+
+    /// Draws tabs in a window, each filled with its background and showing
+    /// as much of its title as fits, in a core X font.
+    pub fn draw_tabs(&self, window_id: &WindowId, font: &str, tabs: &[Tab]) {
+        let font = match self.open_font(font) {
+            Some(font) => font,
+            None => return,
+        };
+        let gc = self.conn.generate_id();
+        xcb::create_gc(&self.conn, gc, window_id.to_x(), &[(xcb::GC_FONT, font.id)]);
+        for tab in tabs {
+            let rectangle = &tab.rectangle;
+            xcb::change_gc(&self.conn, gc, &[(xcb::GC_FOREGROUND, tab.background)]);
+            xcb::poly_fill_rectangle(
+                &self.conn,
+                window_id.to_x(),
+                gc,
+                &[xcb::Rectangle::new(
+                    rectangle.x as i16,
+                    rectangle.y as i16,
+                    rectangle.width as u16,
+                    rectangle.height as u16,
+                )],
+            );
+
+            // Core fonts are indexed by UCS-2 code points.
+            let padding = font.width / 2;
+            let fits = rectangle.width.saturating_sub(2 * padding) / font.width;
+            let title: Vec<xcb::Char2b> = tab
+                .title
+                .chars()
+                .take(cmp::min(fits, 255) as usize)
+                .map(|c| {
+                    let c = if c as u32 > 0xFFFF { '?' } else { c } as u32;
+                    xcb::Char2b::new((c >> 8) as u8, c as u8)
+                })
+                .collect();
+            let values = [
+                (xcb::GC_FOREGROUND, tab.foreground),
+                (xcb::GC_BACKGROUND, tab.background),
+            ];
+            xcb::change_gc(&self.conn, gc, &values);
+            let baseline =
+                rectangle.y + (rectangle.height + font.ascent).saturating_sub(font.descent) / 2;
+            let x = if tab.centered {
+                rectangle.x + rectangle.width.saturating_sub(title.len() as u32 * font.width) / 2
+            } else {
+                rectangle.x + padding
+            };
+            xcb::image_text_16(
+                &self.conn,
+                window_id.to_x(),
+                gc,
+                x as i16,
+                baseline as i16,
+                &title,
+            );
+        }
+        xcb::free_gc(&self.conn, gc);
+    }
+
+    /// Opens a core X font, or the "fixed" font if there is no such font.
+    fn open_font(&self, name: &str) -> Option<Font> {
+        if let Some(font) = self.fonts.borrow().get(name) {
+            return Some(*font);
+        }
+        let id = self.conn.generate_id();
+        let opened = xcb::open_font_checked(&self.conn, id, name).request_check();
+        if opened.is_err() {
+            if name == "fixed" {
+                error!("Could not open the fixed font");
+                return None;
+            }
+            warn!("Could not open font '{}', using fixed", name);
+            let font = self.open_font("fixed")?;
+            self.fonts.borrow_mut().insert(name.to_owned(), font);
+            return Some(font);
+        }
+        let reply = xcb::query_font(&self.conn, id).get_reply().ok()?;
+        let font = Font {
+            id,
+            ascent: reply.font_ascent().max(0) as u32,
+            descent: reply.font_descent().max(0) as u32,
+            width: reply.max_bounds().character_width().max(1) as u32,
+        };
+        self.fonts.borrow_mut().insert(name.to_owned(), font);
+        Some(font)
     }
 }
 
@@ -421,59 +997,115 @@ pub enum Event {
     DestroyNotify(WindowId),
     KeyPress(KeyCombo),
     EnterNotify(WindowId),
+    /// The window's title changed.
+    TitleChanged(WindowId),
+    /// The window's urgency hint or EWMH state changed.
+    UrgencyChanged(WindowId),
+    /// The window asked to be minimized, with an ICCCM WM_CHANGE_STATE
+    /// message.
+    MinimizeRequest(WindowId),
+    /// The window asked to be made sticky (`Some(true)`), not sticky
+    /// (`Some(false)`) or the opposite (`None`), with a _NET_WM_STATE
+    /// message.
+    StickyRequest(WindowId, Option<bool>),
+    /// The pointer moved, to the given position on the root window.
+    PointerMotion(u32, u32),
+    /// The window moved or resized itself.
+    ConfigureRequest(WindowId),
+    /// A mouse button was pressed in one of our windows, at the given
+    /// position in it.
+    ButtonPress(WindowId, u32, u32),
+    /// Part of one of our windows needs drawing again.
+    Expose(WindowId),
+    /// The RandR outputs changed, e.g. a monitor was plugged in.
+    ScreensChanged,
 }
 
-/// An iterator that yields events from the X event loop.
+/// The source of X events.
 ///
-/// Use `Connection::get_event_loop()` to get one.
-pub struct EventLoop<'a> {
-    connection: &'a Connection,
+/// It never blocks: the `Reactor` waits for `EventLoop::fd()` to be readable
+/// and then drains the events with `EventLoop::poll()`.
+pub struct EventLoop {
+    connection: Rc<Connection>,
 }
 
-impl<'a> Iterator for EventLoop<'a> {
-    type Item = Event;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            // Flush any pending operations that came out of the event we (might
-            // have) just yielded.
-            self.connection.flush();
-
-            let event = self
-                .connection
-                .conn
-                .wait_for_event()
-                .expect("wait_for_event() returned None: IO error?");
-
-            unsafe {
-                let propagate = match event.response_type() {
-                    xcb::CONFIGURE_REQUEST => self.on_configure_request(xcb::cast_event(&event)),
-                    xcb::MAP_REQUEST => self.on_map_request(xcb::cast_event(&event)),
-                    xcb::UNMAP_NOTIFY => self.on_unmap_notify(xcb::cast_event(&event)),
-                    xcb::DESTROY_NOTIFY => self.on_destroy_notify(xcb::cast_event(&event)),
-                    xcb::KEY_PRESS => self.on_key_press(xcb::cast_event(&event)),
-                    xcb::ENTER_NOTIFY => self.on_enter_notify(xcb::cast_event(&event)),
-                    xcb::MOTION_NOTIFY => self.on_motion_notify(xcb::cast_event(&event)),
-                    other => {
-                        info!("Other event {}", other);
-                        None
-                    }
-                };
-
-                if let Some(propagate_event) = propagate {
-                    return Some(propagate_event);
+impl EventLoop {
+    pub fn new(connection: Rc<Connection>) -> EventLoop {
+        EventLoop { connection }
+    }
+
+    /// Returns the file descriptor of the X connection.
+    pub fn fd(&self) -> RawFd {
+        self.connection.conn.as_raw_fd()
+    }
+
+    /// Returns the next queued event, or `None` once there are none left.
+    ///
+    /// Fails if the connection to the X server was lost.
+    pub fn poll(&self) -> Result<Option<Event>> {
+        // Flush any pending operations that came out of the event we (might
+        // have) just handled.
+        self.connection.flush();
+
+        while let Some(event) = self.connection.conn.poll_for_event() {
+            if let Some(propagate_event) = self.on_x_event(&event) {
+                return Ok(Some(propagate_event));
+            }
+        }
+        self.connection
+            .conn
+            .has_error()
+            .chain_err(|| "Lost the connection to the X server")?;
+        Ok(None)
+    }
+
+    fn on_x_event(&self, event: &xcb::GenericEvent) -> Option<Event> {
+        unsafe {
+            match event_type(event.response_type()) {
+                xcb::CONFIGURE_REQUEST => self.on_configure_request(xcb::cast_event(event)),
+                xcb::MAP_REQUEST => self.on_map_request(xcb::cast_event(event)),
+                xcb::UNMAP_NOTIFY => self.on_unmap_notify(xcb::cast_event(event)),
+                xcb::DESTROY_NOTIFY => self.on_destroy_notify(xcb::cast_event(event)),
+                xcb::KEY_PRESS => self.on_key_press(xcb::cast_event(event)),
+                xcb::ENTER_NOTIFY => self.on_enter_notify(xcb::cast_event(event)),
+                xcb::MOTION_NOTIFY => self.on_motion_notify(xcb::cast_event(event)),
+                xcb::PROPERTY_NOTIFY => self.on_property_notify(xcb::cast_event(event)),
+                xcb::CLIENT_MESSAGE => self.on_client_message(xcb::cast_event(event)),
+                xcb::BUTTON_PRESS => self.on_button_press(xcb::cast_event(event)),
+                xcb::EXPOSE => self.on_expose(xcb::cast_event(event)),
+                other if self.is_randr_event(other) => Some(Event::ScreensChanged),
+                other => {
+                    info!("Other event {}", other);
+                    None
                 }
             }
         }
     }
-}
 
-impl<'a> EventLoop<'a> {
+    /// Returns whether the event is a RandR screen change or notify event.
+    fn is_randr_event(&self, response_type: u8) -> bool {
+        match self.connection.randr_event {
+            Some(first) => {
+                response_type == first + randr::SCREEN_CHANGE_NOTIFY
+                    || response_type == first + randr::NOTIFY
+            }
+            None => false,
+        }
+    }
+
     fn on_configure_request(&self, event: &xcb::ConfigureRequestEvent) -> Option<Event> {
-        // This request is not interesting for us: grant it unchanged.
+        // Grant the request unchanged, the next layout puts tiled windows
+        // back in place. A window in a frame stays where it is in it, and
+        // only changes size.
         // Build a request with all attributes set, then filter out to only include
         // those from the original request.
         info!("Configure_Requests {}", event.window());
+        let framed = event.parent() != self.connection.root_window_id().to_x();
+        let mask = if framed {
+            xcb::CONFIG_WINDOW_WIDTH as u16 | xcb::CONFIG_WINDOW_HEIGHT as u16
+        } else {
+            event.value_mask()
+        };
         let values = vec![
             (xcb::CONFIG_WINDOW_X as u16, event.x() as u32),
             (xcb::CONFIG_WINDOW_Y as u16, event.y() as u32),
@@ -483,7 +1115,7 @@ impl<'a> EventLoop<'a> {
                 xcb::CONFIG_WINDOW_BORDER_WIDTH as u16,
                 u32::from(event.border_width()),
             ),
-            (xcb::CONFIG_WINDOW_SIBLING as u16, event.sibling() as u32),
+            (xcb::CONFIG_WINDOW_SIBLING as u16, event.sibling()),
             (
                 xcb::CONFIG_WINDOW_STACK_MODE as u16,
                 u32::from(event.stack_mode()),
@@ -491,12 +1123,11 @@ impl<'a> EventLoop<'a> {
         ];
         let filtered_values: Vec<_> = values
             .into_iter()
-            .filter(|&(mask, _)| mask & event.value_mask() != 0)
+            .filter(|&(value, _)| value & mask != 0)
             .collect();
         xcb::configure_window(&self.connection.conn, event.window(), &filtered_values);
 
-        // There's no value in propogating this event.
-        None
+        Some(Event::ConfigureRequest(WindowId(event.window())))
     }
 
     fn on_map_request(&self, event: &xcb::MapRequestEvent) -> Option<Event> {
@@ -534,8 +1165,94 @@ impl<'a> EventLoop<'a> {
         Some(Event::EnterNotify(WindowId(event.event())))
     }
 
+    fn on_property_notify(&self, event: &xcb::PropertyNotifyEvent) -> Option<Event> {
+        let conn = &self.connection.conn;
+        let window_id = WindowId(event.window());
+        match event.atom() {
+            atom if atom == xcb::ATOM_WM_NAME || atom == conn.WM_NAME() => {
+                Some(Event::TitleChanged(window_id))
+            }
+            atom if atom == xcb::ATOM_WM_HINTS || atom == conn.WM_STATE() => {
+                Some(Event::UrgencyChanged(window_id))
+            }
+            _ => None,
+        }
+    }
+
+    fn on_client_message(&self, event: &xcb::ClientMessageEvent) -> Option<Event> {
+        let conn = &self.connection.conn;
+        let window_id = WindowId(event.window());
+        if event.format() != 32 {
+            return None;
+        }
+        let data = event.data().data32();
+        if event.type_() == self.connection.atoms.WM_CHANGE_STATE
+            && data[0] == icccm::WM_STATE_ICONIC as u32
+        {
+            Some(Event::MinimizeRequest(window_id))
+        } else if event.type_() == conn.WM_STATE() && data[1..3].contains(&conn.WM_STATE_STICKY())
+        {
+            Some(Event::StickyRequest(window_id, state_action(data[0])))
+        } else {
+            None
+        }
+    }
+
+    fn on_button_press(&self, event: &xcb::ButtonPressEvent) -> Option<Event> {
+        let x = event.event_x().max(0) as u32;
+        let y = event.event_y().max(0) as u32;
+        Some(Event::ButtonPress(WindowId(event.event()), x, y))
+    }
+
+    fn on_expose(&self, event: &xcb::ExposeEvent) -> Option<Event> {
+        // Wait for the last of a series of exposures to draw everything.
+        if event.count() == 0 {
+            Some(Event::Expose(WindowId(event.window())))
+        } else {
+            None
+        }
+    }
+
     fn on_motion_notify(&self, event: &xcb::MotionNotifyEvent) -> Option<Event> {
-        info!("Motion {}", event.event());
-        None
+        let x = event.root_x().max(0) as u32;
+        let y = event.root_y().max(0) as u32;
+        Some(Event::PointerMotion(x, y))
+    }
+}
+
+/// The type of an event, without the bit set on the events clients send with
+/// SendEvent, such as the ClientMessages asking to iconify a window or to
+/// change its `_NET_WM_STATE`.
+fn event_type(response_type: u8) -> u8 {
+    response_type & !0x80
+}
+
+/// What a `_NET_WM_STATE` message asks for a state: `Some(true)` to add it,
+/// `Some(false)` to remove it, `None` to toggle it.
+fn state_action(action: u32) -> Option<bool> {
+    match action {
+        ewmh::STATE_REMOVE => Some(false),
+        ewmh::STATE_ADD => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{event_type, state_action};
+    use xcb_util::ewmh;
+
+    #[test]
+    fn test_event_type() {
+        assert_eq!(event_type(xcb::CLIENT_MESSAGE), xcb::CLIENT_MESSAGE);
+        assert_eq!(event_type(xcb::CLIENT_MESSAGE | 0x80), xcb::CLIENT_MESSAGE);
+        assert_eq!(event_type(xcb::MAP_REQUEST), xcb::MAP_REQUEST);
+    }
+
+    #[test]
+    fn test_state_action() {
+        assert_eq!(state_action(ewmh::STATE_ADD), Some(true));
+        assert_eq!(state_action(ewmh::STATE_REMOVE), Some(false));
+        assert_eq!(state_action(ewmh::STATE_TOGGLE), None);
     }
 }